@@ -26,6 +26,11 @@ impl<'a, K: Serialize + DeserializeOwned + Clone, V: Serialize + DeserializeOwne
             })
             .ok()?;
 
+        // keys are sorted, the first one without the prefix ends the map
+        if !key.starts_with(&self.prefix) {
+            return None;
+        }
+
         // Deserialize the key and value.
         let key = bincode::deserialize(&key[self.prefix.len()..])
             .map_err(|e| {
@@ -68,6 +73,11 @@ impl<'a, K: 'a + Clone + Serialize + DeserializeOwned> Iterator for Keys<'a, K>
             })
             .ok()?;
 
+        // keys are sorted, the first one without the prefix ends the map
+        if !key.starts_with(&self.prefix) {
+            return None;
+        }
+
         // Deserialize the key.
         let key = bincode::deserialize(&key[self.prefix.len()..])
             .map_err(|e| {
@@ -81,13 +91,14 @@ impl<'a, K: 'a + Clone + Serialize + DeserializeOwned> Iterator for Keys<'a, K>
 
 /// An iterator over the values of a prefix.
 pub struct Values<'a, V: 'a + Serialize + DeserializeOwned> {
+    prefix: Vec<u8>,
     db_iter: rocksdb::DBIterator<'a>,
     _phantom: PhantomData<V>,
 }
 
 impl<'a, V: 'a + Serialize + DeserializeOwned> Values<'a, V> {
-    pub(crate) fn new(db_iter: rocksdb::DBIterator<'a>) -> Self {
-        Self { db_iter, _phantom: PhantomData }
+    pub(crate) fn new(prefix: Vec<u8>, db_iter: rocksdb::DBIterator<'a>) -> Self {
+        Self { prefix, db_iter, _phantom: PhantomData }
     }
 }
 
@@ -95,7 +106,7 @@ impl<'a, V: 'a + Clone + Serialize + DeserializeOwned> Iterator for Values<'a, V
     type Item = Cow<'a, V>;
 
     fn next(&mut self) -> Option<Self::Item> {
-        let (_, value) = self
+        let (key, value) = self
             .db_iter
             .next()?
             .map_err(|e| {
@@ -103,6 +114,10 @@ impl<'a, V: 'a + Clone + Serialize + DeserializeOwned> Iterator for Values<'a, V
             })
             .ok()?;
 
+        if !key.starts_with(&self.prefix) {
+            return None;
+        }
+
         // Deserialize the value.
         let value = bincode::deserialize(&value)
             .map_err(|e| {
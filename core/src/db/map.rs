@@ -73,7 +73,7 @@ impl<'a, K: 'a + Serialize + DeserializeOwned, V: 'a + Serialize + DeserializeOw
     }
 
     pub fn values(&self) -> Values<V> {
-        Values::new(self.inner.prefix_iterator(self.prefix.clone()))
+        Values::new(self.prefix.clone(), self.inner.prefix_iterator(self.prefix.clone()))
     }
 
     pub fn get_all(&self) -> anyhow::Result<Vec<(K, V)>> {
@@ -111,3 +111,21 @@ impl<'a, K: 'a + Serialize + DeserializeOwned, V: 'a + Serialize + DeserializeOw
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use crate::db::{map::DBMap, RocksDB};
+
+    #[test]
+    fn test_iter_stays_in_prefix() {
+        let dir = tempfile::tempdir().unwrap();
+        let a: DBMap<String, u64> = RocksDB::open_map(dir.path(), "iter_a").unwrap();
+        let b: DBMap<String, u64> = RocksDB::open_map(dir.path(), "iter_b").unwrap();
+        a.insert("1".to_string(), 1).unwrap();
+        b.insert("2".to_string(), 2).unwrap();
+
+        assert_eq!(a.iter().map(|(k, v)| (k.into_owned(), v.into_owned())).collect::<Vec<_>>(), [("1".to_string(), 1)]);
+        assert_eq!(a.keys().count(), 1);
+        assert_eq!(a.values().map(|v| v.into_owned()).collect::<Vec<_>>(), [1]);
+    }
+}
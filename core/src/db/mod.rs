@@ -1,9 +1,13 @@
 pub mod iter;
 pub mod map;
 
-use std::{path::Path, sync::Arc};
+use std::{
+    collections::HashMap,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, Weak},
+};
 
-use once_cell::sync::OnceCell;
+use once_cell::sync::Lazy;
 use rocksdb::WriteBatch;
 use serde::{de::DeserializeOwned, Serialize};
 
@@ -14,28 +18,30 @@ pub struct RocksDB(Arc<rocksdb::DB>);
 
 impl RocksDB {
     pub fn open(path: impl AsRef<Path>) -> anyhow::Result<Self> {
-        static DB: OnceCell<RocksDB> = OnceCell::new();
+        // one handle per path, rocksdb refuses to open a path twice; closed once every map is dropped
+        static DBS: Lazy<Mutex<HashMap<PathBuf, Weak<rocksdb::DB>>>> = Lazy::new(Default::default);
 
-        // Retrieve the database.
-        let database = DB
-            .get_or_try_init(|| {
-                // Customize database options.
-                let mut options = rocksdb::Options::default();
-                options.set_compression_type(rocksdb::DBCompressionType::Lz4);
+        let path = path.as_ref().to_path_buf();
+        let mut dbs = DBS.lock().map_err(|_| anyhow::anyhow!("db registry poisoned"))?;
+        if let Some(db) = dbs.get(&path).and_then(Weak::upgrade) {
+            return Ok(RocksDB(db));
+        }
 
-                let rocksdb = {
-                    options.increase_parallelism(2);
-                    options.set_max_background_jobs(4);
-                    options.create_if_missing(true);
+        // Customize database options.
+        let mut options = rocksdb::Options::default();
+        options.set_compression_type(rocksdb::DBCompressionType::Lz4);
 
-                    Arc::new(rocksdb::DB::open(&options, path)?)
-                };
+        let rocksdb = {
+            options.increase_parallelism(2);
+            options.set_max_background_jobs(4);
+            options.create_if_missing(true);
 
-                Ok::<_, anyhow::Error>(RocksDB(rocksdb))
-            })?
-            .clone();
+            Arc::new(rocksdb::DB::open(&options, &path)?)
+        };
+        dbs.retain(|_, db| db.strong_count() > 0);
+        dbs.insert(path, Arc::downgrade(&rocksdb));
 
-        Ok(database)
+        Ok(RocksDB(rocksdb))
     }
 
     pub fn open_map<K: Serialize + DeserializeOwned, V: Serialize + DeserializeOwned>(
@@ -0,0 +1,234 @@
+use std::{collections::HashMap, str::FromStr, sync::Mutex};
+
+use aleo_rust::{Address, Network, PrivateKey};
+use ethers::{
+    signers::LocalWallet,
+    types::{RecoveryMessage, Signature as EthSignature, H160},
+    utils::{hash_message, hex, keccak256},
+};
+use serde::{Deserialize, Serialize};
+
+use crate::Signature;
+
+pub const SCHEME_HEADER: &str = "x-izar-scheme";
+pub const SIGNER_HEADER: &str = "x-izar-signer";
+pub const TIMESTAMP_HEADER: &str = "x-izar-timestamp";
+pub const NONCE_HEADER: &str = "x-izar-nonce";
+pub const SIGNATURE_HEADER: &str = "x-izar-signature";
+
+const DOMAIN: &str = "izar-voter-envelope";
+const DEFAULT_MAX_SKEW: u64 = 300;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum Scheme {
+    /// secp256k1 over the EIP-191 hash of the message
+    Evm,
+    /// an Aleo account signature over the message bytes
+    Aleo,
+}
+
+impl Scheme {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Self::Evm => "evm",
+            Self::Aleo => "aleo",
+        }
+    }
+}
+
+impl FromStr for Scheme {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "evm" => Ok(Self::Evm),
+            "aleo" => Ok(Self::Aleo),
+            _ => Err(format!("unknown scheme {s}")),
+        }
+    }
+}
+
+/// A validator key that can vouch for the requests of its voter.
+pub trait EnvelopeSigner: Send + Sync {
+    fn scheme(&self) -> Scheme;
+    fn signer(&self) -> anyhow::Result<String>;
+    fn sign_message(&self, message: &[u8]) -> anyhow::Result<String>;
+}
+
+impl EnvelopeSigner for LocalWallet {
+    fn scheme(&self) -> Scheme {
+        Scheme::Evm
+    }
+
+    fn signer(&self) -> anyhow::Result<String> {
+        Ok(format!("{:?}", ethers::signers::Signer::address(self)))
+    }
+
+    fn sign_message(&self, message: &[u8]) -> anyhow::Result<String> {
+        Ok(self.sign_hash(hash_message(message))?.to_string())
+    }
+}
+
+impl<N: Network> EnvelopeSigner for PrivateKey<N> {
+    fn scheme(&self) -> Scheme {
+        Scheme::Aleo
+    }
+
+    fn signer(&self) -> anyhow::Result<String> {
+        Ok(Address::try_from(self)?.to_string())
+    }
+
+    fn sign_message(&self, message: &[u8]) -> anyhow::Result<String> {
+        Ok(self.sign_bytes(message, &mut rand::thread_rng())?.to_string())
+    }
+}
+
+/// Proof of which voter sent a request, carried in the `x-izar-*` headers next to an unchanged body.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Envelope {
+    pub scheme: Scheme,
+    pub signer: String,
+    /// unix seconds the request was signed at
+    pub timestamp: u64,
+    /// random hex, never reused by a signer within the skew window
+    pub nonce: String,
+    pub signature: String,
+}
+
+impl Envelope {
+    /// The signed bytes: `izar-voter-envelope\n{timestamp}\n{nonce}\n{method}\n{path}\n` and the hex keccak256 of the
+    /// body. The host is left out so one envelope holds for every sequencer of a failover list.
+    pub fn message(timestamp: u64, nonce: &str, method: &str, path: &str, body: &[u8]) -> Vec<u8> {
+        let digest = hex::encode(keccak256(body));
+        format!("{DOMAIN}\n{timestamp}\n{nonce}\n{method}\n{path}\n{digest}").into_bytes()
+    }
+
+    pub fn seal(
+        key: &dyn EnvelopeSigner,
+        method: &str,
+        path: &str,
+        body: &[u8],
+        timestamp: u64,
+    ) -> anyhow::Result<Self> {
+        let nonce = hex::encode(rand::random::<[u8; 16]>());
+        let signature = key.sign_message(&Self::message(timestamp, &nonce, method, path, body))?;
+        Ok(Self { scheme: key.scheme(), signer: key.signer()?, timestamp, nonce, signature })
+    }
+
+    pub fn headers(&self) -> [(&'static str, String); 5] {
+        [
+            (SCHEME_HEADER, self.scheme.as_str().to_string()),
+            (SIGNER_HEADER, self.signer.clone()),
+            (TIMESTAMP_HEADER, self.timestamp.to_string()),
+            (NONCE_HEADER, self.nonce.clone()),
+            (SIGNATURE_HEADER, self.signature.clone()),
+        ]
+    }
+
+    /// Reads an envelope back with `header` looking up a request header by its lowercase name.
+    pub fn from_headers<'a>(header: impl Fn(&str) -> Option<&'a str>) -> Result<Self, String> {
+        let get = |name: &str| header(name).map(str::to_string).ok_or(format!("missing {name}"));
+        Ok(Self {
+            scheme: get(SCHEME_HEADER)?.parse()?,
+            signer: get(SIGNER_HEADER)?,
+            timestamp: get(TIMESTAMP_HEADER)?.parse().map_err(|_| "invalid timestamp")?,
+            nonce: get(NONCE_HEADER)?,
+            signature: get(SIGNATURE_HEADER)?,
+        })
+    }
+
+    /// Checks the signature alone, `N` is the Aleo network of `Scheme::Aleo` signers.
+    pub fn verify_signature<N: Network>(&self, method: &str, path: &str, body: &[u8]) -> Result<(), String> {
+        let message = Self::message(self.timestamp, &self.nonce, method, path, body);
+        match self.scheme {
+            Scheme::Evm => {
+                let signer = H160::from_str(&self.signer).map_err(|_| "invalid evm signer")?;
+                EthSignature::from_str(&self.signature)
+                    .map_err(|_| "invalid evm signature")?
+                    .verify(RecoveryMessage::Data(message), signer)
+                    .map_err(|_| "bad signature".to_string())
+            }
+            Scheme::Aleo => {
+                let signer = Address::<N>::from_str(&self.signer).map_err(|_| "invalid aleo signer")?;
+                let signature = Signature::<N>::from_str(&self.signature).map_err(|_| "invalid aleo signature")?;
+                match signature.verify_bytes(&signer, &message) {
+                    true => Ok(()),
+                    false => Err("bad signature".to_string()),
+                }
+            }
+        }
+    }
+}
+
+/// Authenticates envelopes for the sequencer: the signature has to hold, the timestamp has to be within `max_skew`
+/// seconds of now and the nonce must not have been seen from the same signer in that window.
+pub struct EnvelopeVerifier {
+    max_skew: u64,
+    // signer and nonce to the timestamp they came with
+    seen: Mutex<HashMap<(String, String), u64>>,
+}
+
+impl Default for EnvelopeVerifier {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_SKEW)
+    }
+}
+
+impl EnvelopeVerifier {
+    pub fn new(max_skew: u64) -> Self {
+        Self { max_skew, seen: Mutex::new(HashMap::new()) }
+    }
+
+    /// Returns the signer the request is attributed to, or why it is refused.
+    pub fn verify<N: Network>(
+        &self,
+        envelope: &Envelope,
+        method: &str,
+        path: &str,
+        body: &[u8],
+        now: u64,
+    ) -> Result<String, String> {
+        if envelope.timestamp.abs_diff(now) > self.max_skew {
+            return Err(format!("timestamp {} too far from {now}", envelope.timestamp));
+        }
+        envelope.verify_signature::<N>(method, path, body)?;
+
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        // a nonce older than the window cannot come back, its timestamp would be refused
+        seen.retain(|_, ts| ts.abs_diff(now) <= self.max_skew);
+        let key = (envelope.signer.to_lowercase(), envelope.nonce.clone());
+        if seen.insert(key, envelope.timestamp).is_some() {
+            return Err(format!("nonce {} replayed", envelope.nonce));
+        }
+        Ok(envelope.signer.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use aleo_rust::{PrivateKey, Testnet3};
+    use ethers::signers::LocalWallet;
+
+    use super::{Envelope, EnvelopeVerifier};
+
+    #[test]
+    fn test_envelope() {
+        let verifier = EnvelopeVerifier::new(300);
+        let wallet = LocalWallet::new(&mut rand::thread_rng());
+        let env = Envelope::seal(&wallet, "POST", "/api/v1/BridgeTx", b"{}", 1000).unwrap();
+        let headers = env.headers();
+        let read = Envelope::from_headers(|name| headers.iter().find(|(h, _)| *h == name).map(|(_, v)| v.as_str()));
+        assert_eq!(read.unwrap(), env);
+
+        assert!(verifier.verify::<Testnet3>(&env, "POST", "/api/v1/BridgeTx", b"{ }", 1000).is_err());
+        assert!(verifier.verify::<Testnet3>(&env, "POST", "/api/v1/BridgeTx", b"{}", 1400).is_err());
+        assert_eq!(verifier.verify::<Testnet3>(&env, "POST", "/api/v1/BridgeTx", b"{}", 1100), Ok(env.signer.clone()));
+        assert!(verifier.verify::<Testnet3>(&env, "POST", "/api/v1/BridgeTx", b"{}", 1100).is_err());
+
+        let key = PrivateKey::<Testnet3>::new(&mut rand::thread_rng()).unwrap();
+        let env = Envelope::seal(&key, "POST", "/api/v1/BridgeTxSpeedUp", b"{}", 1000).unwrap();
+        assert!(verifier.verify::<Testnet3>(&env, "POST", "/api/v1/BridgeTx", b"{}", 1000).is_err());
+        assert!(verifier.verify::<Testnet3>(&env, "POST", "/api/v1/BridgeTxSpeedUp", b"{}", 1000).is_ok());
+    }
+}
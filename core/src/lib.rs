@@ -1,6 +1,9 @@
 pub mod db;
+pub mod envelope;
 pub mod metrics;
+pub mod monitor;
 pub mod network;
+pub mod sequencer;
 pub mod serde;
 pub mod types;
 pub mod utils;
@@ -40,4 +40,12 @@ pub fn metrics_init(dest: &str, interval: Duration) -> anyhow::Result<()> {
 pub mod relayer {
     pub const RELAYER_BALANCE: &str = "relayer_balance";
     pub const RELAYER_BLOCK_HEIGHT: &str = "relayer_block_height";
+    pub const RELAYER_PROVER_QUEUE_DEPTH: &str = "relayer_prover_queue_depth";
+    pub const RELAYER_PROOF_DURATION: &str = "relayer_proof_duration_seconds";
+    pub const RELAYER_QUEUE_WAIT: &str = "relayer_queue_wait_seconds";
+    pub const RELAYER_PATCH_OUTBOX: &str = "relayer_patch_outbox";
+}
+
+pub mod voter {
+    pub const VOTER_BALANCE: &str = "voter_balance";
 }
@@ -0,0 +1,155 @@
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
+
+use serde::{Deserialize, Deserializer};
+
+/// Posts alerts to a webhook as `{"text": ...}`, the body most chat webhooks accept.
+#[derive(Clone)]
+pub struct Alerter {
+    webhook: Option<String>,
+    client: reqwest::Client,
+}
+
+impl Alerter {
+    pub fn new(webhook: Option<String>) -> Self {
+        Self { webhook, client: reqwest::Client::new() }
+    }
+
+    pub async fn alert(&self, text: &str) {
+        tracing::warn!("alert: {}", text);
+        let webhook = match &self.webhook {
+            Some(webhook) => webhook,
+            None => return,
+        };
+        let body = serde_json::json!({ "text": text });
+        match self.client.post(webhook).json(&body).send().await {
+            Ok(resp) if !resp.status().is_success() => tracing::error!("alert webhook answered {}", resp.status()),
+            Ok(_) => {}
+            Err(e) => tracing::error!("failed to send alert: {}", e),
+        }
+    }
+}
+
+/// Exports account balances and alerts once when one falls below its chain threshold, and once when it recovers.
+#[derive(Clone)]
+pub struct BalanceMonitor {
+    gauge: &'static str,
+    alerter: Alerter,
+    thresholds: HashMap<u32, u128>,
+    low: Arc<Mutex<HashSet<(u32, String)>>>,
+}
+
+impl BalanceMonitor {
+    pub fn new(gauge: &'static str, alerter: Alerter, thresholds: HashMap<u32, u128>) -> Self {
+        Self { gauge, alerter, thresholds, low: Default::default() }
+    }
+
+    pub fn alerter(&self) -> &Alerter {
+        &self.alerter
+    }
+
+    /// Records `balance` of `address` on `chain_id`, returns whether it is below the threshold.
+    pub async fn observe(&self, chain_id: u32, address: &str, balance: u128) -> bool {
+        ::metrics::gauge!(self.gauge, "chain" => chain_id.to_string(), "address" => address.to_string())
+            .set(balance as f64);
+        tracing::info!("balance of {} on chain {}: {}", address, chain_id, balance);
+
+        let threshold = match self.thresholds.get(&chain_id) {
+            Some(threshold) => *threshold,
+            None => return false,
+        };
+        let low = balance < threshold;
+        match self.transition(chain_id, address, low) {
+            Some(true) => {
+                let text = format!("balance of {address} on chain {chain_id} is low: {balance} < {threshold}");
+                self.alerter.alert(&text).await
+            }
+            Some(false) => {
+                let text = format!("balance of {address} on chain {chain_id} recovered: {balance} >= {threshold}");
+                self.alerter.alert(&text).await
+            }
+            None => {}
+        }
+        low
+    }
+
+    // Some(low) when the account just crossed the threshold
+    fn transition(&self, chain_id: u32, address: &str, low: bool) -> Option<bool> {
+        let mut lows = self.low.lock().unwrap_or_else(|e| e.into_inner());
+        let key = (chain_id, address.to_string());
+        let changed = match low {
+            true => lows.insert(key),
+            false => lows.remove(&key),
+        };
+        changed.then_some(low)
+    }
+}
+
+// a toml integer stops at i64, about 9.2 ether in wei, so larger thresholds are written as strings
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum Threshold {
+    Number(u64),
+    Text(String),
+}
+
+/// Reads `[monitor.thresholds]`, izar chain ids to the lowest balance in the smallest unit of the chain, given as
+/// numbers or as decimal strings.
+pub fn deserialize_thresholds<'de, D: Deserializer<'de>>(deserializer: D) -> Result<HashMap<u32, u128>, D::Error> {
+    use serde::de::Error;
+
+    HashMap::<String, Threshold>::deserialize(deserializer)?
+        .into_iter()
+        .map(|(chain, threshold)| {
+            let chain_id = chain.parse::<u32>().map_err(|e| D::Error::custom(format!("chain id {chain}: {e}")))?;
+            let threshold = match threshold {
+                Threshold::Number(threshold) => threshold as u128,
+                Threshold::Text(threshold) => threshold
+                    .parse::<u128>()
+                    .map_err(|e| D::Error::custom(format!("threshold of chain {chain}: {e}")))?,
+            };
+            Ok((chain_id, threshold))
+        })
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use serde::Deserialize;
+
+    use super::{deserialize_thresholds, Alerter, BalanceMonitor};
+
+    #[tokio::test]
+    async fn test_balance_monitor_alerts_on_crossing() {
+        let monitor = BalanceMonitor::new("test_balance", Alerter::new(None), HashMap::from([(1, 100)]));
+
+        assert!(!monitor.observe(1, "a", 150).await);
+        assert!(monitor.observe(1, "a", 50).await);
+        assert_eq!(monitor.transition(1, "a", true), None);
+        assert_eq!(monitor.transition(1, "a", false), Some(false));
+
+        // no threshold, never low
+        assert!(!monitor.observe(2, "a", 0).await);
+    }
+
+    #[test]
+    fn test_thresholds() {
+        #[derive(Deserialize)]
+        struct Monitor {
+            #[serde(deserialize_with = "deserialize_thresholds")]
+            thresholds: HashMap<u32, u128>,
+        }
+        let parse = |json: &str| serde_json::from_str::<Monitor>(json).map(|m| m.thresholds);
+
+        // 30 ether in wei does not fit a toml integer
+        let thresholds = parse(r#"{"thresholds": {"2": 10000000, "11155111": "30000000000000000000"}}"#).unwrap();
+        assert_eq!(thresholds, HashMap::from([(2, 10000000), (11155111, 30_000_000_000_000_000_000)]));
+
+        assert!(parse(r#"{"thresholds": {"sepolia": 1}}"#).unwrap_err().to_string().contains("chain id sepolia"));
+        assert!(parse(r#"{"thresholds": {"2": "lots"}}"#).unwrap_err().to_string().contains("threshold of chain 2"));
+    }
+}
@@ -13,6 +13,9 @@ pub trait EthNetwork: Send + Sync + Clone + 'static + std::fmt::Debug {
 
     const CONFIRMATIONS: usize = 0; // L1 need 12 confirmations, L2 need 0 confirmations
 
+    /// Predeploy pricing the L1 data of a rollup tx through `getL1Fee(bytes)`, none when the gas price covers it.
+    const L1_FEE_ORACLE: Option<&'static str> = None;
+
     fn format_str<T: std::fmt::LowerHex>(t: T) -> String {
         format!("{:#020x}", t)
     }
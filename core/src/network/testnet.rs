@@ -93,6 +93,7 @@ impl EthNetwork for ScrollTestnet {
     const IZAR_CHAIN_ID: u32 = 6;
     const GAS_LIMIT: u128 = 1_000_000;
     const START_HEIGHT: u64 = 3089109;
+    const L1_FEE_ORACLE: Option<&'static str> = Some("0x5300000000000000000000000000000000000002");
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -108,6 +109,7 @@ impl EthNetwork for OptimismTestnet {
     const IZAR_CHAIN_ID: u32 = 7;
     const GAS_LIMIT: u128 = 1_000_000;
     const START_HEIGHT: u64 = 8690339;
+    const L1_FEE_ORACLE: Option<&'static str> = Some("0x420000000000000000000000000000000000000F");
 }
 
 #[derive(Clone, Copy, Debug)]
@@ -0,0 +1,268 @@
+use std::{
+    sync::atomic::{AtomicUsize, Ordering},
+    time::Duration,
+};
+
+use serde::{de::DeserializeOwned, ser::SerializeStruct, Deserialize, Serialize};
+
+use crate::{
+    envelope::{Envelope, EnvelopeSigner},
+    types::transaction::{IzarTransaction, SpeedupTransaction},
+};
+
+const BRIDGE_TX: &str = "/api/v1/BridgeTx";
+const BRIDGE_TX_SPEEDUP: &str = "/api/v1/BridgeTxSpeedUp";
+const BRIDGE_TX_READY: &str = "/api/v1/BridgeTx/ready";
+
+/// Outcome of an execution as the sequencer knows it, sent as `{code, result}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum TransactionStatus {
+    Success(String),
+    NotBroadcasted(String),
+    Rejected(String),
+    Reverted(String),
+}
+
+impl TransactionStatus {
+    pub fn code(&self) -> u8 {
+        match self {
+            Self::Success(_) => 1,
+            Self::NotBroadcasted(_) => 2,
+            Self::Rejected(_) => 3,
+            Self::Reverted(_) => 4,
+        }
+    }
+
+    pub fn result(&self) -> &str {
+        match self {
+            Self::Success(r) | Self::NotBroadcasted(r) | Self::Rejected(r) | Self::Reverted(r) => r,
+        }
+    }
+}
+
+impl Serialize for TransactionStatus {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        let mut s = serializer.serialize_struct("status", 2)?;
+        s.serialize_field("code", &self.code())?;
+        s.serialize_field("result", self.result())?;
+        s.end()
+    }
+}
+
+impl<'de> Deserialize<'de> for TransactionStatus {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        #[derive(Deserialize)]
+        struct Status {
+            code: u8,
+            result: String,
+        }
+
+        let Status { code, result } = Status::deserialize(deserializer)?;
+        match code {
+            1 => Ok(Self::Success(result)),
+            2 => Ok(Self::NotBroadcasted(result)),
+            3 => Ok(Self::Rejected(result)),
+            4 => Ok(Self::Reverted(result)),
+            _ => Err(serde::de::Error::custom(format!("unknown status code {code}"))),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PatchRequest {
+    pub from_chain_tx_hash: String,
+    pub status: TransactionStatus,
+}
+
+impl PatchRequest {
+    pub fn new(from_chain_tx_hash: String, status: TransactionStatus) -> Self {
+        Self { from_chain_tx_hash, status }
+    }
+}
+
+/// A transfer the sequencer holds enough certificates for, `id` grows with every transfer it makes ready.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ReadyTx {
+    pub id: u64,
+    pub tx: IzarTransaction,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SequencerError {
+    /// no sequencer answered, the last transport error
+    Unreachable(String),
+    /// a sequencer answered with a status other than 2xx
+    Status { status: u16, body: String },
+    /// a 2xx answer whose body is not what was expected
+    Decode(String),
+    /// the request could not be encoded or signed, nothing was sent
+    Request(String),
+}
+
+impl SequencerError {
+    pub fn status(&self) -> Option<u16> {
+        match self {
+            Self::Status { status, .. } => Some(*status),
+            _ => None,
+        }
+    }
+
+    /// Sending the same request again cannot succeed, the body itself is refused.
+    pub fn is_permanent(&self) -> bool {
+        matches!(self.status(), Some(400 | 404 | 410 | 413 | 422))
+    }
+}
+
+impl std::fmt::Display for SequencerError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unreachable(e) => write!(f, "sequencer unreachable: {e}"),
+            Self::Status { status, body } => write!(f, "sequencer answered {status}: {body}"),
+            Self::Decode(e) => write!(f, "invalid sequencer answer: {e}"),
+            Self::Request(e) => write!(f, "request not sent: {e}"),
+        }
+    }
+}
+
+impl std::error::Error for SequencerError {}
+
+/// Talks to the sequencer API. Requests go to the url that answered last, and move on to the next one when a
+/// sequencer cannot be reached or answers 5xx. Any other answer is the sequencer's word on the request.
+pub struct SequencerClient {
+    urls: Vec<String>,
+    client: reqwest::Client,
+    active: AtomicUsize,
+}
+
+impl SequencerClient {
+    pub fn new(urls: Vec<String>, timeout: Duration) -> anyhow::Result<Self> {
+        if urls.is_empty() {
+            anyhow::bail!("no sequencer url");
+        }
+        let urls = urls.into_iter().map(|u| u.trim_end_matches('/').to_string()).collect();
+        let client = reqwest::Client::builder().timeout(timeout).build()?;
+        Ok(Self { urls, client, active: AtomicUsize::new(0) })
+    }
+
+    pub fn urls(&self) -> &[String] {
+        &self.urls
+    }
+
+    /// Url the next request is sent to first.
+    pub fn active(&self) -> &str {
+        &self.urls[self.active.load(Ordering::Relaxed)]
+    }
+
+    /// Posts a transfer with the certificates of the caller, in an envelope signed by `key`.
+    pub async fn submit_certificates(
+        &self,
+        tx: &IzarTransaction,
+        key: &dyn EnvelopeSigner,
+    ) -> Result<(), SequencerError> {
+        self.post_sealed(BRIDGE_TX, tx, key).await.map(drop)
+    }
+
+    pub async fn submit_speedup(
+        &self,
+        req: &SpeedupTransaction,
+        key: &dyn EnvelopeSigner,
+    ) -> Result<(), SequencerError> {
+        self.post_sealed(BRIDGE_TX_SPEEDUP, req, key).await.map(drop)
+    }
+
+    /// Reports the final status of an execution.
+    pub async fn patch_status(&self, req: &PatchRequest) -> Result<(), SequencerError> {
+        self.send(reqwest::Method::PATCH, BRIDGE_TX, |r| r.json(req)).await.map(drop)
+    }
+
+    /// Transfers made ready after `after`, in id order.
+    pub async fn ready(&self, after: u64, limit: u64) -> Result<Vec<ReadyTx>, SequencerError> {
+        let query = [("after", after), ("limit", limit)];
+        let body = self.send(reqwest::Method::GET, BRIDGE_TX_READY, |r| r.query(&query)).await?;
+        decode(&body)
+    }
+
+    async fn post_sealed<T: Serialize>(
+        &self,
+        path: &str,
+        value: &T,
+        key: &dyn EnvelopeSigner,
+    ) -> Result<String, SequencerError> {
+        let body = serde_json::to_vec(value).map_err(|e| SequencerError::Request(e.to_string()))?;
+        let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs();
+        let envelope =
+            Envelope::seal(key, "POST", path, &body, now).map_err(|e| SequencerError::Request(e.to_string()))?;
+        let build = |r: reqwest::RequestBuilder| {
+            let r = r.header(reqwest::header::CONTENT_TYPE, "application/json").body(body.clone());
+            envelope.headers().into_iter().fold(r, |r, (name, value)| r.header(name, value))
+        };
+        self.send(reqwest::Method::POST, path, build).await
+    }
+
+    async fn send(
+        &self,
+        method: reqwest::Method,
+        path: &str,
+        build: impl Fn(reqwest::RequestBuilder) -> reqwest::RequestBuilder,
+    ) -> Result<String, SequencerError> {
+        let start = self.active.load(Ordering::Relaxed);
+        let mut last = SequencerError::Unreachable("no attempt".to_string());
+        for i in 0..self.urls.len() {
+            let index = (start + i) % self.urls.len();
+            let url = format!("{}{}", self.urls[index], path);
+            let resp = match build(self.client.request(method.clone(), &url)).send().await {
+                Ok(resp) => resp,
+                Err(e) => {
+                    tracing::warn!("sequencer {} unreachable: {}", self.urls[index], e);
+                    last = SequencerError::Unreachable(e.to_string());
+                    continue;
+                }
+            };
+            let status = resp.status();
+            let body = resp.text().await.unwrap_or_default();
+            if status.is_server_error() {
+                tracing::warn!("sequencer {} answered {}", self.urls[index], status);
+                last = SequencerError::Status { status: status.as_u16(), body };
+                continue;
+            }
+
+            if index != start {
+                tracing::warn!("sequencer failover to {}", self.urls[index]);
+                self.active.store(index, Ordering::Relaxed);
+            }
+            return match status.is_success() {
+                true => Ok(body),
+                false => Err(SequencerError::Status { status: status.as_u16(), body }),
+            };
+        }
+        Err(last)
+    }
+}
+
+fn decode<T: DeserializeOwned>(body: &str) -> Result<T, SequencerError> {
+    serde_json::from_str(body).map_err(|e| SequencerError::Decode(e.to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PatchRequest, SequencerError, TransactionStatus};
+
+    #[test]
+    fn test_status_serde() {
+        let req = PatchRequest::new("tx1".to_string(), TransactionStatus::Reverted("nonce used".to_string()));
+        let json = serde_json::to_string(&req).unwrap();
+        assert_eq!(json, r#"{"from_chain_tx_hash":"tx1","status":{"code":4,"result":"nonce used"}}"#);
+        assert_eq!(serde_json::from_str::<PatchRequest>(&json).unwrap(), req);
+        assert_eq!(bincode::deserialize::<PatchRequest>(&bincode::serialize(&req).unwrap()).unwrap(), req);
+
+        assert!(SequencerError::Status { status: 422, body: String::new() }.is_permanent());
+        assert!(!SequencerError::Status { status: 429, body: String::new() }.is_permanent());
+        assert!(!SequencerError::Unreachable(String::new()).is_permanent());
+    }
+}
@@ -11,6 +11,25 @@ use snarkvm_utilities::{FromBytes, ToBytes};
 
 use super::transaction::IzarTransaction;
 
+/// Hash the keepers sign for the `receive_payload` call of `tx` on `E`.
+pub fn receive_hash<E: EthNetwork>(tx: &IzarTransaction) -> anyhow::Result<H256> {
+    let lock_addr: Address = E::LOCK_CONTRACT.parse()?;
+    let nonce = U256::from_dec_str(&tx.nonce)?;
+    let mut nonce_be = [0u8; 32];
+    nonce.to_big_endian(&mut nonce_be);
+
+    let encoded = abi::encode_packed(&[
+        abi::Token::Bytes((tx.from_chain_id as u16).to_be_bytes().to_vec()), // src_chain_id
+        abi::Token::Bytes((tx.to_chain_id as u16).to_be_bytes().to_vec()),   // to_chain_id
+        abi::Token::Bytes(nonce_be.to_vec()),                                // nonce
+        abi::Token::Bytes(tx.from_addr.as_bytes().to_vec()),                 // src_address
+        abi::Token::Address(lock_addr),                                      // lock_address
+        abi::Token::Bytes(tx.eth_payload()?),                                // payload
+    ])?;
+
+    Ok(H256(ethers::utils::keccak256(encoded)))
+}
+
 #[derive(Debug, Clone)]
 pub struct EthTransaction<E: EthNetwork> {
     tx_hash: H256,
@@ -1,10 +1,11 @@
 use base64::Engine;
 use serde::{Deserialize, Serialize};
 use serde_repr::{Deserialize_repr, Serialize_repr};
+use utoipa::ToSchema;
 
 use super::cert::Certificate;
 
-#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq)]
+#[derive(Debug, Clone, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
 pub struct IzarTransaction {
     pub priority: Priority,
     pub timestamp: u64,
@@ -27,7 +28,7 @@ pub struct IzarTransaction {
 }
 
 #[repr(u8)]
-#[derive(Debug, Clone, Copy, Serialize_repr, Deserialize_repr, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, Serialize_repr, Deserialize_repr, PartialEq, Eq, ToSchema)]
 pub enum Priority {
     High = 0x00,   // 0
     Medium = 0x77, // 119
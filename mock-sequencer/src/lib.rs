@@ -0,0 +1,317 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    body::Bytes,
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use izar_core::{
+    envelope::{Envelope, EnvelopeVerifier},
+    network::{testnet::IzarTestnet, IzarNetwork},
+    sequencer::PatchRequest,
+    types::transaction::{IzarTransaction, SpeedupTransaction},
+};
+use serde::{de::DeserializeOwned, Deserialize};
+use state::{SequencerState, Submitted};
+
+pub mod state;
+
+type Aleo = <IzarTestnet as IzarNetwork>::Aleo;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct MockConfig {
+    /// distinct certificates a transfer needs to be ready, 1 by default
+    pub threshold: Option<usize>,
+    /// relayer base url ready transfers are posted to as `/exec`, they can only be pulled without it
+    pub relayer: Option<String>,
+    /// sent to the relayer as `Authorization: Bearer <token>`
+    pub relayer_token: Option<String>,
+    /// refuse submissions without a valid voter envelope
+    #[serde(default)]
+    pub require_envelopes: bool,
+    /// envelope signers accepted, any when empty
+    #[serde(default)]
+    pub voters: Vec<String>,
+}
+
+/// Stands in for the sequencer behind `api_dest`: aggregates voter certificates, hands ready transfers to the
+/// relayer and records what it is told.
+pub struct MockSequencer {
+    config: MockConfig,
+    state: Mutex<SequencerState>,
+    verifier: EnvelopeVerifier,
+    client: reqwest::Client,
+    // route to how many of its next requests are answered 503
+    failing: Mutex<HashMap<&'static str, u32>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct ReadyQuery {
+    #[serde(default)]
+    after: u64,
+    limit: Option<u64>,
+}
+
+impl MockSequencer {
+    pub fn new(config: MockConfig) -> Arc<Self> {
+        Arc::new(Self {
+            config,
+            state: Mutex::new(SequencerState::default()),
+            verifier: EnvelopeVerifier::default(),
+            client: reqwest::Client::new(),
+            failing: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// The next `times` requests to `route` (`submit`, `speedup`, `patch` or `ready`) are answered 503.
+    pub fn fail(&self, route: &'static str, times: u32) {
+        *self.failing.lock().unwrap_or_else(|e| e.into_inner()).entry(route).or_default() += times;
+    }
+
+    fn unavailable(&self, route: &'static str) -> Result<(), (StatusCode, String)> {
+        let mut failing = self.failing.lock().unwrap_or_else(|e| e.into_inner());
+        match failing.get_mut(route) {
+            Some(left) if *left > 0 => {
+                *left -= 1;
+                Err((StatusCode::SERVICE_UNAVAILABLE, format!("{route} unavailable")))
+            }
+            _ => Ok(()),
+        }
+    }
+
+    pub fn snapshot(&self) -> SequencerState {
+        self.state.lock().unwrap_or_else(|e| e.into_inner()).clone()
+    }
+
+    pub fn router(self: Arc<Self>) -> Router {
+        Router::new()
+            .route("/api/v1/BridgeTx", post(submit).patch(patch))
+            .route("/api/v1/BridgeTxSpeedUp", post(speedup))
+            .route("/api/v1/BridgeTx/ready", get(ready))
+            .route("/state", get(snapshot))
+            .with_state(self)
+    }
+
+    /// Serves on `addr` in the background, returns the address bound, useful with port 0.
+    pub async fn spawn(self: Arc<Self>, addr: SocketAddr) -> anyhow::Result<SocketAddr> {
+        let listener = tokio::net::TcpListener::bind(addr).await?;
+        let local = listener.local_addr()?;
+        let router = self.router();
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, router).await {
+                tracing::error!("mock sequencer stopped: {}", e);
+            }
+        });
+        Ok(local)
+    }
+
+    fn with_state<T>(&self, f: impl FnOnce(&mut SequencerState) -> T) -> T {
+        f(&mut self.state.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// Authenticates a voter request, returns the signer it is attributed to.
+    fn authenticate(&self, path: &str, headers: &HeaderMap, body: &[u8]) -> Result<String, (StatusCode, String)> {
+        let signer = match headers.contains_key(izar_core::envelope::SIGNATURE_HEADER) {
+            false if self.config.require_envelopes => Err("missing envelope".to_string()),
+            false => return Ok("anonymous".to_string()),
+            true => Envelope::from_headers(|name| headers.get(name).and_then(|v| v.to_str().ok())).and_then(|env| {
+                let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default();
+                self.verifier.verify::<Aleo>(&env, "POST", path, body, now.as_secs())
+            }),
+        };
+        let signer = signer.and_then(|s| match self.is_voter(&s) {
+            true => Ok(s),
+            false => Err(format!("{s} is not a voter")),
+        });
+        signer.map_err(|reason| {
+            self.with_state(|s| s.refused += 1);
+            tracing::warn!("refused submission to {path}: {reason}");
+            (StatusCode::UNAUTHORIZED, reason)
+        })
+    }
+
+    fn is_voter(&self, signer: &str) -> bool {
+        self.config.voters.is_empty() || self.config.voters.iter().any(|v| v.eq_ignore_ascii_case(signer))
+    }
+
+    /// Posts `tx` to the relayer once, a failed push is left for the relayer to pull.
+    async fn forward(&self, path: &str, tx: &IzarTransaction) -> Option<u16> {
+        let relayer = self.config.relayer.as_ref()?;
+        let mut req = self.client.post(format!("{}{}", relayer.trim_end_matches('/'), path)).json(tx);
+        if let Some(token) = &self.config.relayer_token {
+            req = req.bearer_auth(token);
+        }
+        match req.send().await {
+            Ok(resp) => {
+                tracing::info!("forwarded tx {} to {path}: {}", tx.from_chain_tx_hash, resp.status());
+                Some(resp.status().as_u16())
+            }
+            Err(e) => {
+                tracing::warn!("forward tx {} to {path} failed: {}", tx.from_chain_tx_hash, e);
+                Some(0)
+            }
+        }
+    }
+}
+
+fn parse<T: DeserializeOwned>(body: &[u8]) -> Result<T, (StatusCode, String)> {
+    serde_json::from_slice(body).map_err(|e| (StatusCode::BAD_REQUEST, e.to_string()))
+}
+
+async fn submit(
+    State(seq): State<Arc<MockSequencer>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    seq.unavailable("submit")?;
+    let signer = seq.authenticate("/api/v1/BridgeTx", &headers, &body)?;
+    let tx: IzarTransaction = parse(&body)?;
+    let hash = tx.from_chain_tx_hash.clone();
+    let threshold = seq.config.threshold.unwrap_or(1);
+    let submitted = seq.with_state(|s| {
+        let submitted = s.submit(tx, threshold);
+        if submitted.is_ok() {
+            s.submitters.push(signer.clone());
+        }
+        submitted
+    });
+
+    match submitted.map_err(|e| (StatusCode::UNPROCESSABLE_ENTITY, e))? {
+        Submitted::Duplicate => Ok((StatusCode::CONFLICT, "certificate known")),
+        Submitted::Added { ready } => {
+            tracing::info!("tx {hash} certificate from {signer}, ready: {ready}");
+            if ready {
+                let seq = seq.clone();
+                tokio::spawn(async move {
+                    let Some(tx) = seq.with_state(|s| s.txs.get(&hash).map(|e| e.tx.clone())) else {
+                        return;
+                    };
+                    let forwarded = seq.forward("/exec", &tx).await;
+                    seq.with_state(|s| {
+                        if let Some(entry) = s.txs.get_mut(&hash) {
+                            entry.forwarded = forwarded;
+                        }
+                    });
+                });
+            }
+            Ok((StatusCode::CREATED, "certificate added"))
+        }
+    }
+}
+
+async fn speedup(
+    State(seq): State<Arc<MockSequencer>>,
+    headers: HeaderMap,
+    body: Bytes,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    seq.unavailable("speedup")?;
+    seq.authenticate("/api/v1/BridgeTxSpeedUp", &headers, &body)?;
+    let req: SpeedupTransaction = parse(&body)?;
+    let ready = seq.with_state(|s| s.speedup(req.clone()));
+    match ready {
+        Some(tx) => {
+            seq.forward("/speedup", &tx).await;
+        }
+        // the voter drops a rejected speedup, an unknown one is simply not passed on
+        None => tracing::warn!("speedup {} of a transfer not ready", req.speed_up_hash),
+    }
+    Ok(StatusCode::OK)
+}
+
+async fn patch(
+    State(seq): State<Arc<MockSequencer>>,
+    Json(req): Json<PatchRequest>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    seq.unavailable("patch")?;
+    let hash = req.from_chain_tx_hash.clone();
+    tracing::info!("tx {hash} patched: {:?}", req.status);
+    match seq.with_state(|s| s.patch(req)) {
+        true => Ok(StatusCode::OK),
+        false => Err((StatusCode::NOT_FOUND, format!("tx {hash} unknown"))),
+    }
+}
+
+async fn ready(
+    State(seq): State<Arc<MockSequencer>>,
+    Query(query): Query<ReadyQuery>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    seq.unavailable("ready")?;
+    Ok(Json(seq.with_state(|s| s.ready_after(query.after, query.limit.unwrap_or(100)))))
+}
+
+async fn snapshot(State(seq): State<Arc<MockSequencer>>) -> impl IntoResponse {
+    Json(seq.snapshot())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use ethers::signers::LocalWallet;
+    use izar_core::sequencer::{PatchRequest, SequencerClient, TransactionStatus};
+    use izar_testkit::transfer::TransferBuilder;
+
+    use super::{MockConfig, MockSequencer};
+
+    #[tokio::test]
+    async fn test_flow() {
+        let config = MockConfig { threshold: Some(2), require_envelopes: true, ..Default::default() };
+        let seq = MockSequencer::new(config);
+        let addr = seq.clone().spawn("127.0.0.1:0".parse().unwrap()).await.unwrap();
+        let client = SequencerClient::new(vec![format!("http://{addr}")], Duration::from_secs(5)).unwrap();
+
+        let tx = TransferBuilder::new("tx1", 1, 2);
+        for signer in ["a", "b"] {
+            let wallet = LocalWallet::new(&mut rand::thread_rng());
+            let tx = tx.clone().with_certificate(signer, "sig").build();
+            client.submit_certificates(&tx, &wallet).await.unwrap();
+            assert_eq!(client.submit_certificates(&tx, &wallet).await.unwrap_err().status(), Some(409));
+        }
+
+        let ready = client.ready(0, 10).await.unwrap();
+        assert_eq!((ready.len(), ready[0].tx.certificates.len()), (1, 2));
+
+        let status = TransactionStatus::Success("0x1".to_string());
+        client.patch_status(&PatchRequest::new("tx1".to_string(), status.clone())).await.unwrap();
+        let state = seq.snapshot();
+        assert_eq!(state.txs["tx1"].status, Some(status));
+        assert_eq!(state.submitters.len(), 2);
+    }
+
+    #[tokio::test]
+    async fn test_failover() {
+        let (first, second) = (MockSequencer::new(MockConfig::default()), MockSequencer::new(MockConfig::default()));
+        let first_url = format!("http://{}", first.clone().spawn("127.0.0.1:0".parse().unwrap()).await.unwrap());
+        let second_url = format!("http://{}", second.clone().spawn("127.0.0.1:0".parse().unwrap()).await.unwrap());
+        let client = SequencerClient::new(vec![first_url.clone(), second_url.clone()], Duration::from_secs(5)).unwrap();
+        let wallet = LocalWallet::new(&mut rand::thread_rng());
+        let tx = TransferBuilder::new("tx1", 1, 2).with_certificate("a", "sig").build();
+        client.submit_certificates(&tx, &wallet).await.unwrap();
+
+        // a 503 moves the client to the next sequencer, and it stays there
+        first.fail("ready", 1);
+        assert!(client.ready(0, 10).await.unwrap().is_empty());
+        assert_eq!(client.active(), second_url);
+
+        // the second does not know tx1, its 404 is the answer even though the first would take the patch
+        let status = TransactionStatus::Success("0x1".to_string());
+        let err = client.patch_status(&PatchRequest::new("tx1".to_string(), status)).await.unwrap_err();
+        assert_eq!(err.status(), Some(404));
+        assert_eq!(client.active(), second_url);
+        assert_eq!(first.snapshot().txs["tx1"].status, None);
+
+        // an unreachable sequencer is skipped the same way
+        let client =
+            SequencerClient::new(vec!["http://127.0.0.1:1".to_string(), first_url.clone()], Duration::from_secs(5))
+                .unwrap();
+        assert_eq!(client.ready(0, 10).await.unwrap().len(), 1);
+        assert_eq!(client.active(), first_url);
+    }
+}
@@ -0,0 +1,35 @@
+use std::net::SocketAddr;
+
+use clap::Parser;
+use izar_mock_sequencer::{MockConfig, MockSequencer};
+use serde::Deserialize;
+
+#[derive(Debug, Parser)]
+pub struct Cli {
+    #[clap(short, long, default_value = "./mock-sequencer.toml")]
+    pub config: String,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct Config {
+    pub port: u16,
+    #[serde(flatten)]
+    pub mock: MockConfig,
+}
+
+#[tokio::main]
+async fn main() {
+    let cli = Cli::parse();
+
+    // init logger
+    tracing_subscriber::fmt().init();
+    let config_str = std::fs::read_to_string(cli.config).expect("read config file");
+    let config: Config = toml::from_str(&config_str).expect("parse config");
+    tracing::info!("mock sequencer init with {:#?}", config);
+
+    let seq = MockSequencer::new(config.mock);
+    let addr = SocketAddr::from(([0, 0, 0, 0], config.port));
+    let listener = tokio::net::TcpListener::bind(addr).await.expect("bind mock sequencer");
+    tracing::info!("mock sequencer listening on {}", addr);
+    axum::serve(listener, seq.router()).await.expect("serve mock sequencer");
+}
@@ -0,0 +1,146 @@
+use std::collections::BTreeMap;
+
+use izar_core::{
+    sequencer::{PatchRequest, ReadyTx, TransactionStatus},
+    types::transaction::{IzarTransaction, SpeedupTransaction},
+};
+use serde::Serialize;
+
+/// A transfer as the sequencer aggregated it, certificates of every voter merged into `tx`.
+#[derive(Debug, Clone, Serialize)]
+pub struct TxEntry {
+    pub tx: IzarTransaction,
+    /// set once `tx` holds the threshold of certificates, grows with every ready transfer
+    pub ready_id: Option<u64>,
+    /// what the relayer answered to `/exec`, 0 when it could not be reached
+    pub forwarded: Option<u16>,
+    /// last status the relayer patched
+    pub status: Option<TransactionStatus>,
+    pub speedups: Vec<String>,
+}
+
+/// Everything the mock was told, `GET /state` returns it for assertions.
+#[derive(Debug, Clone, Default, Serialize)]
+pub struct SequencerState {
+    pub txs: BTreeMap<String, TxEntry>,
+    /// ready ids handed out so far
+    pub ready: u64,
+    /// signer of every accepted submission, in arrival order
+    pub submitters: Vec<String>,
+    /// submissions refused for a missing or invalid envelope
+    pub refused: u64,
+    /// every status patch received, in arrival order
+    pub patches: Vec<PatchRequest>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Submitted {
+    /// new certificates were added, `ready` when this submission reached the threshold
+    Added { ready: bool },
+    /// every certificate was known already
+    Duplicate,
+}
+
+impl SequencerState {
+    /// Merges the certificates of `tx`, or refuses it when it disagrees with what was submitted before.
+    pub fn submit(&mut self, mut tx: IzarTransaction, threshold: usize) -> Result<Submitted, String> {
+        let certificates = std::mem::take(&mut tx.certificates);
+        let entry = self.txs.entry(tx.from_chain_tx_hash.clone()).or_insert_with(|| TxEntry {
+            tx: tx.clone(),
+            ready_id: None,
+            forwarded: None,
+            status: None,
+            speedups: vec![],
+        });
+
+        let mut known = entry.tx.clone();
+        known.certificates.clear();
+        if known != tx {
+            return Err(format!("tx {} conflicts with an earlier submission", tx.from_chain_tx_hash));
+        }
+
+        let before = entry.tx.certificates.len();
+        for cert in certificates {
+            if !entry.tx.certificates.iter().any(|c| c.signer.eq_ignore_ascii_case(&cert.signer)) {
+                entry.tx.certificates.push(cert);
+            }
+        }
+        if entry.tx.certificates.len() == before {
+            return Ok(Submitted::Duplicate);
+        }
+
+        let ready = entry.ready_id.is_none() && entry.tx.certificates.len() >= threshold;
+        if ready {
+            self.ready += 1;
+            entry.ready_id = Some(self.ready);
+        }
+        Ok(Submitted::Added { ready })
+    }
+
+    /// Ready transfers with an id above `after`, in id order.
+    pub fn ready_after(&self, after: u64, limit: u64) -> Vec<ReadyTx> {
+        let mut ready = self
+            .txs
+            .values()
+            .filter_map(|e| e.ready_id.filter(|id| *id > after).map(|id| ReadyTx { id, tx: e.tx.clone() }))
+            .collect::<Vec<_>>();
+        ready.sort_by_key(|r| r.id);
+        ready.truncate(limit as usize);
+        ready
+    }
+
+    /// Returns false for a transfer the sequencer never saw.
+    pub fn patch(&mut self, req: PatchRequest) -> bool {
+        let Some(entry) = self.txs.get_mut(&req.from_chain_tx_hash) else {
+            return false;
+        };
+        entry.status = Some(req.status.clone());
+        self.patches.push(req);
+        true
+    }
+
+    /// Records a speedup, returns the transfer to pass on when it is ready already.
+    pub fn speedup(&mut self, req: SpeedupTransaction) -> Option<IzarTransaction> {
+        let entry = self.txs.get_mut(&req.from_chain_tx_hash)?;
+        if !entry.speedups.contains(&req.speed_up_hash) {
+            entry.speedups.push(req.speed_up_hash);
+        }
+        entry.ready_id.map(|_| entry.tx.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use izar_core::{
+        sequencer::{PatchRequest, TransactionStatus},
+        types::transaction::IzarTransaction,
+    };
+    use izar_testkit::transfer::TransferBuilder;
+
+    use super::{SequencerState, Submitted};
+
+    fn tx(signer: &str) -> IzarTransaction {
+        TransferBuilder::new("tx1", 1, 2).with_certificate(signer, "sig").build()
+    }
+
+    #[test]
+    fn test_aggregate() {
+        let mut state = SequencerState::default();
+        assert_eq!(state.submit(tx("a"), 2), Ok(Submitted::Added { ready: false }));
+        assert_eq!(state.submit(tx("A"), 2), Ok(Submitted::Duplicate));
+        assert!(state.ready_after(0, 10).is_empty());
+        assert_eq!(state.submit(tx("b"), 2), Ok(Submitted::Added { ready: true }));
+        assert_eq!(state.submit(tx("c"), 2), Ok(Submitted::Added { ready: false }));
+
+        let ready = state.ready_after(0, 10);
+        assert_eq!((ready.len(), ready[0].id, ready[0].tx.certificates.len()), (1, 1, 3));
+        assert!(state.ready_after(1, 10).is_empty());
+
+        let mut other = tx("d");
+        other.to_addr = "elsewhere".to_string();
+        assert!(state.submit(other, 2).is_err());
+
+        assert!(state.patch(PatchRequest::new("tx1".to_string(), TransactionStatus::Success("0x1".to_string()))));
+        assert!(!state.patch(PatchRequest::new("tx2".to_string(), TransactionStatus::Success("0x2".to_string()))));
+    }
+}
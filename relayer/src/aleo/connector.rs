@@ -1,42 +1,93 @@
-use std::{str::FromStr, sync::Arc};
+use std::{
+    path::Path,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        Arc, Mutex, TryLockError,
+    },
+    time::Duration,
+};
 
 use aleo_rust::{
-    Address, AleoAPIClient, Block, Ciphertext, Credits, Network, Plaintext, PrivateKey, ProgramManager, Record, Value,
-    ViewKey,
+    Address, AleoAPIClient, Block, Ciphertext, Credits, Field, Network, Plaintext, PrivateKey, ProgramManager, Record,
+    Transaction, Value, ViewKey,
 };
 use backon::{BlockingRetryable, ExponentialBuilder};
 use izar_core::{
     db::{map::DBMap, RocksDB},
     network::aleo::AleoNetworkExt,
+    types::{aleo::IzarRecvMsg, transaction::Priority},
     utils::PlaintextCodec,
+    Input,
 };
 use rayon::prelude::*;
 use tracing::error_span;
 
-use crate::DB_PATH;
+use crate::relayer::types::KeyBalance;
+
+use super::{
+    fee::{FeeManager, FeeSource, PriorityFeePolicy},
+    pool::ProvingPool,
+    prover::{DelegatedProver, Prover, ProvingService},
+    RECEIVE_FUNCTION,
+};
+
+const RECEIVED_PREFIX: &str = "aleo_received";
+
+#[derive(Clone)]
+pub struct AleoKey<N: Network> {
+    pub private_key: PrivateKey<N>,
+    pub view_key: ViewKey<N>,
+    pub address: Address<N>,
+}
+
+impl<N: Network> AleoKey<N> {
+    fn new(private_key: PrivateKey<N>) -> anyhow::Result<Self> {
+        Ok(Self { private_key, view_key: ViewKey::try_from(&private_key)?, address: Address::try_from(&private_key)? })
+    }
+}
 
 #[derive(Clone)]
 pub struct AleoOperator<N: Network> {
     pm: Arc<ProgramManager<N>>,
-    private_key: PrivateKey<N>,
-    view_key: ViewKey<N>,
+    keys: Arc<Vec<AleoKey<N>>>,
+    next_key: Arc<AtomicUsize>,
     current_height: DBMap<u16, u32>,
-    unspent_records: DBMap<String, Record<N, Plaintext<N>>>,
+    // source chain and nonce of every message a `receive_payload` execution landed, to that execution
+    received: DBMap<String, String>,
+    // one sync scans the blocks at a time
+    syncing: Arc<Mutex<()>>,
+    fees: FeeManager<N>,
+    prover: Arc<dyn Prover<N>>,
+    priority_fee: PriorityFeePolicy,
+    // aleo executions still queued in the relayer
+    backlog: Arc<AtomicUsize>,
     client: reqwest::Client,
 }
 
 impl<N: Network> AleoOperator<N> {
-    pub fn new(dest: Option<String>, pk: PrivateKey<N>, from_height: Option<u32>) -> anyhow::Result<Self> {
+    pub fn new(
+        dest: Option<String>,
+        pks: Vec<PrivateKey<N>>,
+        from_height: Option<u32>,
+        provers: usize,
+        prover_queue: usize,
+        db_path: impl AsRef<Path>,
+    ) -> anyhow::Result<Self> {
         let client = match dest {
             Some(base_url) => AleoAPIClient::new(&base_url, "testnet3")?,
             None => AleoAPIClient::local_testnet3("3030"),
         };
 
-        let view_key = ViewKey::try_from(&pk)?;
+        let keys = pks.into_iter().map(AleoKey::new).collect::<anyhow::Result<Vec<_>>>()?;
+        let pk = keys.first().ok_or(anyhow::anyhow!("no aleo private key"))?.private_key;
         let pm = ProgramManager::new(Some(pk), None, Some(client.clone()), None, true)?;
 
-        let unspent_records = RocksDB::open_map(DB_PATH, "unspent_records")?;
-        let current_height = RocksDB::open_map(DB_PATH, "aleo-chains")?;
+        let fees = FeeManager::new(&db_path)?;
+        let pool = ProvingPool::new(provers, prover_queue, client.clone())?;
+        let current_height = RocksDB::open_map(&db_path, "aleo-chains")?;
+        let received = RocksDB::open_map(&db_path, RECEIVED_PREFIX)?;
+        let priority_fee = PriorityFeePolicy::Fixed { fee: N::ALEO_PRIORITY_FEE };
 
         let cur = current_height.get(&N::ID)?.unwrap_or(N::START_HEIGHT);
         if let Some(from_height) = from_height {
@@ -45,7 +96,19 @@ impl<N: Network> AleoOperator<N> {
             }
         }
         let client = reqwest::Client::new();
-        Ok(Self { pm: Arc::new(pm), private_key: pk, view_key, unspent_records, current_height, client })
+        Ok(Self {
+            pm: Arc::new(pm),
+            keys: Arc::new(keys),
+            next_key: Arc::new(AtomicUsize::new(0)),
+            fees,
+            prover: Arc::new(pool),
+            priority_fee,
+            backlog: Arc::new(AtomicUsize::new(0)),
+            current_height,
+            received,
+            syncing: Default::default(),
+            client,
+        })
     }
 
     pub fn client(&self) -> anyhow::Result<&AleoAPIClient<N>> {
@@ -56,7 +119,111 @@ impl<N: Network> AleoOperator<N> {
         self.pm.clone()
     }
 
+    pub fn keys(&self) -> &[AleoKey<N>] {
+        &self.keys
+    }
+
+    /// Reserves the fee of `tid` on the keys in turn, skipping those that can't pay `amount`.
+    /// A `tid` that already holds a fee stays on the key it was reserved with.
+    pub fn reserve_fee(&self, tid: &str, amount: u64) -> anyhow::Result<(&AleoKey<N>, FeeSource<N>)> {
+        let client = self.client()?;
+        if let Some(owner) = self.fees.reserved_by(tid)? {
+            let key = self.keys.iter().find(|k| k.address.to_string() == owner);
+            let key = key.ok_or(anyhow::anyhow!("fee of {tid} is held by unknown key {owner}"))?;
+            return Ok((key, self.fees.reserve(tid, &key.address, amount, client)?));
+        }
+
+        let start = self.next_key.fetch_add(1, Ordering::SeqCst);
+        for i in 0..self.keys.len() {
+            let key = &self.keys[(start + i) % self.keys.len()];
+            match self.fees.reserve(tid, &key.address, amount, client) {
+                Ok(source) => return Ok((key, source)),
+                Err(e) => tracing::warn!("aleo key {} skipped: {:?}", key.address, e),
+            }
+        }
+        anyhow::bail!("no aleo key can pay {amount} microcredits")
+    }
+
+    /// Public balance plus unspent records of every key, in microcredits.
+    pub fn key_balances(&self) -> anyhow::Result<Vec<KeyBalance>> {
+        let mut balances = Vec::with_capacity(self.keys.len());
+        for key in self.keys.iter() {
+            let private = self
+                .fees
+                .records(&key.address)?
+                .iter()
+                .map(|(_, record)| record.microcredits().unwrap_or_default())
+                .sum::<u64>();
+            let balance = self.fees.public_balance(&key.address, self.client()?)?.saturating_add(private);
+            balances.push(KeyBalance { address: key.address.to_string(), balance: balance as u128 });
+        }
+        Ok(balances)
+    }
+
+    pub fn prover(&self) -> &dyn Prover<N> {
+        self.prover.as_ref()
+    }
+
+    /// Hands the proofs to the proving service at `url`, the local pool is kept only as a `fallback`.
+    pub fn with_delegated_prover(mut self, url: String, timeout: Duration, fallback: bool) -> anyhow::Result<Self> {
+        let service = ProvingService::new(url, timeout)?;
+        let fallback = fallback.then(|| self.prover.clone());
+        self.prover = Arc::new(DelegatedProver::new(service, self.client()?.clone(), fallback)?);
+        Ok(self)
+    }
+
+    pub fn with_priority_fee_policy(mut self, policy: PriorityFeePolicy) -> Self {
+        self.priority_fee = policy;
+        self
+    }
+
+    pub fn priority_fee_policy(&self) -> &PriorityFeePolicy {
+        &self.priority_fee
+    }
+
+    /// Base and priority fee an execution of `receive_payload` pays right now.
+    pub fn execution_fee(&self, priority: Priority) -> anyhow::Result<(u64, u64)> {
+        let base_fee = self.fees.estimate_base_fee(N::ALEO_PROXY_CONTRACT)?;
+        Ok((base_fee, self.priority_fee.priority_fee(priority, self.backlog())))
+    }
+
+    /// Aleo executions still waiting in the relayer execute queue.
+    pub fn backlog(&self) -> usize {
+        self.backlog.load(Ordering::Relaxed)
+    }
+
+    pub fn set_backlog(&self, queued: usize) {
+        self.backlog.store(queued, Ordering::Relaxed);
+    }
+
+    // a fee authorized apart from the execution only echoes the estimate, a local run reports the real cost
+    pub(crate) fn learn_base_fee(&self, program: &str, estimate: u64, transaction: &Transaction<N>) {
+        let base_fee = match transaction.fee_transition().map(|fee| fee.base_amount()) {
+            Some(Ok(base_fee)) => *base_fee,
+            _ => return,
+        };
+        if base_fee != estimate {
+            if let Err(e) = self.fees.record_base_fee(program, base_fee) {
+                tracing::warn!("failed to record base fee of {program}: {:?}", e);
+            }
+        }
+    }
+
+    pub async fn broadcast(&self, transaction: Transaction<N>) -> anyhow::Result<String> {
+        let url = format!("{}/testnet3/transaction/broadcast", self.client()?.base_url());
+        let resp = self.client.post(&url).json(&transaction).send().await?;
+        if !resp.status().is_success() {
+            anyhow::bail!("broadcast failed: {}", resp.text().await?);
+        }
+        Ok(resp.json::<String>().await?)
+    }
+
     pub fn sync(&self) -> anyhow::Result<()> {
+        let _syncing = self.syncing.lock().unwrap_or_else(|e| e.into_inner());
+        self.sync_blocks()
+    }
+
+    fn sync_blocks(&self) -> anyhow::Result<()> {
         let cur = self.current_height.get(&N::ID)?.unwrap_or(N::START_HEIGHT);
         let latest = self.client()?.latest_height()?;
         tracing::info!("sync aleo from {} to {}", cur, latest);
@@ -71,9 +238,7 @@ impl<N: Network> AleoOperator<N> {
             let end = (start + BATCH_SIZE as u32).min(latest);
             tracing::warn!("Fetched aleo blocks from {} to {}", start, end);
             self.client()?.get_blocks(start, end)?.into_iter().for_each(|b| {
-                if let Err(e) = self.handle_credits(&b) {
-                    tracing::error!("handle credits error: {:?}", e);
-                }
+                self.handle_block(&b);
             });
         }
 
@@ -84,6 +249,7 @@ impl<N: Network> AleoOperator<N> {
 
     // TODO: use tokio sync
     pub fn fast_sync(&self) -> anyhow::Result<()> {
+        let _syncing = self.syncing.lock().unwrap_or_else(|e| e.into_inner());
         let cur = self.current_height.get(&N::ID)?.unwrap_or(N::START_HEIGHT);
         let latest = self.client()?.latest_height()?;
         tracing::info!("sync aleo from {} to {}", cur, latest);
@@ -98,9 +264,7 @@ impl<N: Network> AleoOperator<N> {
             let end = (start + BATCH_SIZE as u32).min(latest);
 
             self.fast_get_blocks(start, end)?.into_iter().for_each(|b| {
-                if let Err(e) = self.handle_credits(&b) {
-                    tracing::error!("handle credits error: {:?}", e);
-                }
+                self.handle_block(&b);
             });
         }
 
@@ -132,50 +296,65 @@ impl<N: Network> AleoOperator<N> {
         Ok(blocks)
     }
 
-    fn initial(self) -> Self {
+    /// Collects fee records in the background, catching up with `fast_sync` first so startup is not held up.
+    pub fn initial(self) -> Self {
         let self_clone = self.clone();
-        std::thread::spawn(move || loop {
-            if let Err(e) = self_clone.sync() {
-                tracing::error!("failed to sync aleo: {}", e);
+        std::thread::spawn(move || {
+            let _span = error_span!("ALEO").entered();
+            let op = || self_clone.fast_sync();
+            if let Err(e) = op.retry(&ExponentialBuilder::default()).call() {
+                tracing::error!("failed to fast sync aleo: {}", e);
             }
+            loop {
+                if let Err(e) = self_clone.sync() {
+                    tracing::error!("failed to sync aleo: {}", e);
+                }
 
-            std::thread::sleep(std::time::Duration::from_secs(15));
+                std::thread::sleep(std::time::Duration::from_secs(15));
+            }
         });
 
         self
     }
 
-    pub fn sync_and_initial(self) -> Self {
-        error_span!("ALEO").in_scope(|| {
-            let op = || self.fast_sync();
-            op.retry(&ExponentialBuilder::default()).call().expect("sync aleo failed");
-            self.initial()
-        })
+    pub fn fees(&self) -> &FeeManager<N> {
+        &self.fees
     }
 
-    pub fn get_record(&self) -> anyhow::Result<(String, Record<N, Plaintext<N>>)> {
-        let record = self.unspent_records.iter().next();
-        let (rid, record) = record.ok_or(anyhow::anyhow!("no fee record"))?;
-
-        Ok((rid.into_owned(), record.into_owned()))
+    fn handle_block(&self, block: &Block<N>) {
+        if let Err(e) = self.handle_credits(block) {
+            tracing::error!("handle credits error: {:?}", e);
+        }
+        if let Err(e) = self.handle_received(block) {
+            tracing::error!("handle received error: {:?}", e);
+        }
     }
 
     fn handle_credits(&self, block: &Block<N>) -> anyhow::Result<()> {
+        self.handle_records(block.clone().into_serial_numbers(), block.clone().into_records())
+    }
+
+    fn handle_records(
+        &self,
+        spent: impl Iterator<Item = Field<N>>,
+        created: impl Iterator<Item = (Field<N>, Record<N, Ciphertext<N>>)>,
+    ) -> anyhow::Result<()> {
         // handle in
-        block.clone().into_serial_numbers().for_each(|sn| {
-            let _ = self.unspent_records.remove(&sn.to_string());
+        spent.for_each(|sn| {
+            let _ = self.fees.spend_record(&sn.to_string());
         });
         // handle out
-        for (commit, record) in block.clone().into_records() {
-            if !record.is_owner(&self.view_key) {
-                continue;
-            }
-            let sn = Record::<N, Ciphertext<N>>::serial_number(self.private_key, commit)?;
-            let record = record.decrypt(&self.view_key)?;
+        for (commit, record) in created {
+            let key = match self.keys.iter().find(|k| record.is_owner(&k.view_key)) {
+                Some(key) => key,
+                None => continue,
+            };
+            let sn = Record::<N, Ciphertext<N>>::serial_number(key.private_key, commit)?;
+            let record = record.decrypt(&key.view_key)?;
             if let Ok(credits) = record.microcredits() {
                 if credits > N::ALEO_FEE_LIMIT {
                     tracing::info!("got a new record {:?}", record);
-                    self.unspent_records.insert(sn.to_string(), record)?;
+                    self.fees.insert_record(sn.to_string(), record)?;
                 }
             }
         }
@@ -183,6 +362,44 @@ impl<N: Network> AleoOperator<N> {
         Ok(())
     }
 
+    // whichever relayer executed it, the message it landed is a public input
+    fn handle_received(&self, block: &Block<N>) -> anyhow::Result<()> {
+        for tx in block.transactions().iter().filter(|tx| tx.is_accepted()) {
+            for t in tx.transaction().transitions() {
+                if t.program_id().to_string() != N::ALEO_PROXY_CONTRACT
+                    || t.function_name().to_string() != RECEIVE_FUNCTION
+                {
+                    continue;
+                }
+                match t.inputs().get(2) {
+                    Some(Input::Public(_, Some(p))) => {
+                        let msg = IzarRecvMsg::<N>::decode(p)?;
+                        tracing::info!("message {} from chain {} landed in {}", msg.nonce, msg.from_chain_id, tx.id());
+                        self.received.insert(received_key(msg.from_chain_id, msg.nonce), tx.id().to_string())?;
+                    }
+                    _ => anyhow::bail!("{} carries no public message", tx.id()),
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// The execution that landed the message of `nonce` from `from_chain_id`, catching up with the chain first.
+    /// Fails while another sync is scanning the blocks, the answer is not known before it is done.
+    pub fn landed(&self, from_chain_id: u32, nonce: u128) -> anyhow::Result<Option<String>> {
+        let key = received_key(from_chain_id, nonce);
+        if let Some(tid) = self.received.get(&key)? {
+            return Ok(Some(tid));
+        }
+        let _syncing = match self.syncing.try_lock() {
+            Ok(guard) => guard,
+            Err(TryLockError::Poisoned(e)) => e.into_inner(),
+            Err(TryLockError::WouldBlock) => anyhow::bail!("aleo blocks are being synced"),
+        };
+        self.sync_blocks()?;
+        self.received.get(&key)
+    }
+
     pub fn get_current_keepers(&self) -> anyhow::Result<Vec<String>> {
         let flag = Plaintext::from_str("true")?;
         let keepers_value = self.client()?.get_mapping_value(N::ALEO_PROTOCOL_CONTRACT, "izar_keeper", flag)?;
@@ -202,3 +419,178 @@ impl<N: Network> AleoOperator<N> {
         self.client.clone()
     }
 }
+
+fn received_key(from_chain_id: u32, nonce: u128) -> String {
+    format!("{from_chain_id}:{nonce}")
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use aleo_rust::{
+        Address, Block, Ciphertext, Credits, Field, Network, Plaintext, PrivateKey, Record, Scalar, Testnet3, Uniform,
+    };
+    use izar_core::network::aleo::AleoNetworkExt;
+    use izar_testkit::aleo::MockAleo;
+
+    use super::{received_key, AleoOperator};
+
+    // a record of `microcredits` for `owner` as a block carries it, with the commitment it is spent by
+    fn record(
+        owner: &Address<Testnet3>,
+        microcredits: u64,
+    ) -> (Field<Testnet3>, Record<Testnet3, Ciphertext<Testnet3>>) {
+        let rng = &mut rand::thread_rng();
+        let randomizer = Scalar::<Testnet3>::rand(rng);
+        let nonce = Testnet3::g_scalar_multiply(&randomizer);
+        let record =
+            format!("{{ owner: {owner}.private, microcredits: {microcredits}u64.private, _nonce: {nonce}.public }}");
+        let record = Record::<Testnet3, Plaintext<Testnet3>>::from_str(&record).unwrap();
+        (Field::rand(rng), record.encrypt(randomizer).unwrap())
+    }
+
+    #[tokio::test]
+    async fn test_node() {
+        let start = Testnet3::START_HEIGHT;
+        let aleo = MockAleo::start().await.unwrap();
+        let genesis = Block::<Testnet3>::from_bytes_le(Testnet3::genesis_bytes()).unwrap();
+        aleo.set_empty_block(&genesis).unwrap();
+        aleo.set_height(start + 60);
+
+        let pk = PrivateKey::<Testnet3>::new(&mut rand::thread_rng()).unwrap();
+        let db = tempfile::tempdir().unwrap();
+        let op = AleoOperator::new(Some(aleo.url()), vec![pk], None, 1, 1, db.path()).unwrap();
+        let address = Address::try_from(pk).unwrap();
+        aleo.set_mapping("credits.aleo", "account", address, "5000u64");
+        let synced = op.clone();
+        let balances = tokio::task::spawn_blocking(move || {
+            synced.sync().unwrap();
+            synced.key_balances().unwrap()
+        })
+        .await
+        .unwrap();
+        assert_eq!(balances[0].balance, 5000);
+        assert_eq!(op.current_height.get(&Testnet3::ID).unwrap(), Some(start + 60));
+        // none of the genesis records is ours
+        assert!(op.fees().records(&address).unwrap().is_empty());
+
+        let tx = genesis.transactions().iter().next().unwrap().transaction().clone();
+        assert_eq!(op.broadcast(tx.clone()).await.unwrap(), tx.id().to_string());
+        assert_eq!(aleo.broadcast::<Testnet3>().unwrap(), vec![tx.clone()]);
+        aleo.fail("broadcast", 500, "transaction rejected");
+        assert!(op.broadcast(tx).await.unwrap_err().to_string().contains("transaction rejected"));
+    }
+
+    #[tokio::test]
+    async fn test_keepers() {
+        let aleo = MockAleo::start().await.unwrap();
+        let pk = PrivateKey::<Testnet3>::new(&mut rand::thread_rng()).unwrap();
+        let db = tempfile::tempdir().unwrap();
+        let op = AleoOperator::new(Some(aleo.url()), vec![pk], None, 1, 1, db.path()).unwrap();
+        let keepers = (0..2)
+            .map(|_| Address::try_from(PrivateKey::<Testnet3>::new(&mut rand::thread_rng()).unwrap()).unwrap())
+            .map(|a| a.to_string())
+            .collect::<Vec<_>>();
+        let contract = Testnet3::ALEO_PROTOCOL_CONTRACT;
+
+        let read = |op: &AleoOperator<Testnet3>| {
+            let op = op.clone();
+            tokio::task::spawn_blocking(move || op.get_current_keepers())
+        };
+        // no keepers set yet
+        assert!(read(&op).await.unwrap().is_err());
+
+        aleo.set_mapping(contract, "izar_keeper", "true", format!("[{}]", keepers.join(", ")));
+        assert_eq!(read(&op).await.unwrap().unwrap(), keepers);
+        assert_eq!(aleo.requests("mapping"), 2);
+
+        // the node is asked every time, a failure is not answered from an earlier read
+        aleo.fail("mapping", 500, "node down");
+        assert!(read(&op).await.unwrap().is_err());
+
+        aleo.set_mapping(contract, "izar_keeper", "true", "true");
+        assert!(read(&op).await.unwrap().unwrap_err().to_string().contains("invalid keepers"));
+        aleo.set_mapping(contract, "izar_keeper", "true", "[1u8, 2u8]");
+        assert!(read(&op).await.unwrap().is_err());
+    }
+
+    #[tokio::test]
+    async fn test_credits() {
+        let start = Testnet3::START_HEIGHT;
+        let aleo = MockAleo::start().await.unwrap();
+        let genesis = Block::<Testnet3>::from_bytes_le(Testnet3::genesis_bytes()).unwrap();
+        aleo.set_empty_block(&genesis).unwrap();
+        aleo.set_height(start + 5);
+
+        let pk = PrivateKey::<Testnet3>::new(&mut rand::thread_rng()).unwrap();
+        let db = tempfile::tempdir().unwrap();
+        let op = AleoOperator::new(Some(aleo.url()), vec![pk], None, 1, 1, db.path()).unwrap();
+        let address = Address::try_from(pk).unwrap();
+        let other = Address::try_from(PrivateKey::<Testnet3>::new(&mut rand::thread_rng()).unwrap()).unwrap();
+        aleo.set_mapping("credits.aleo", "account", address, "5000u64");
+
+        let synced = op.clone();
+        let balances = tokio::task::spawn_blocking(move || {
+            // none of the genesis records is ours
+            synced.sync().unwrap();
+            assert!(synced.fees().records(&address).unwrap().is_empty());
+
+            // a record of ours that pays a fee is kept by its serial number, dust and others' records are not
+            let (fee, dust, others) =
+                (record(&address, 2 * Testnet3::ALEO_FEE_LIMIT), record(&address, 10), record(&other, 5_000_000));
+            let sn = Record::<Testnet3, Ciphertext<Testnet3>>::serial_number(pk, fee.0).unwrap().to_string();
+            synced.handle_records(std::iter::empty(), vec![fee.clone(), dust, others].into_iter()).unwrap();
+            let records = synced.fees().records(&address).unwrap();
+            assert_eq!(records.iter().map(|(sn, _)| sn.clone()).collect::<Vec<_>>(), [sn.clone()]);
+            assert_eq!(records[0].1.microcredits().unwrap(), 2 * Testnet3::ALEO_FEE_LIMIT);
+            let balances = synced.key_balances().unwrap();
+
+            // and dropped once a block spends it
+            let spent = Field::<Testnet3>::from_str(&sn).unwrap();
+            synced.handle_records(std::iter::once(spent), std::iter::empty()).unwrap();
+            assert!(synced.fees().records(&address).unwrap().is_empty());
+            balances
+        })
+        .await
+        .unwrap();
+        assert_eq!(balances[0].balance, 5000 + 2 * Testnet3::ALEO_FEE_LIMIT as u128);
+        assert_eq!(aleo.requests("blocks"), 1);
+    }
+
+    #[tokio::test]
+    async fn test_landed() {
+        let start = Testnet3::START_HEIGHT;
+        let aleo = MockAleo::start().await.unwrap();
+        let genesis = Block::<Testnet3>::from_bytes_le(Testnet3::genesis_bytes()).unwrap();
+        aleo.set_empty_block(&genesis).unwrap();
+        aleo.set_height(start + 10);
+
+        let pk = PrivateKey::<Testnet3>::new(&mut rand::thread_rng()).unwrap();
+        let db = tempfile::tempdir().unwrap();
+        let op = AleoOperator::new(Some(aleo.url()), vec![pk], None, 1, 1, db.path()).unwrap();
+        let checked = op.clone();
+        let landed = tokio::task::spawn_blocking(move || {
+            // the blocks are scanned before a message is told not received
+            assert_eq!(op.landed(2, 7).unwrap(), None);
+            assert_eq!(op.current_height.get(&Testnet3::ID).unwrap(), Some(start + 10));
+
+            op.received.insert(received_key(2, 7), "at1landed".to_string()).unwrap();
+            let landed = op.landed(2, 7).unwrap();
+
+            // no answer while another sync is halfway through the blocks
+            let syncing = op.syncing.lock().unwrap();
+            assert!(op.landed(2, 8).is_err());
+            drop(syncing);
+            assert_eq!(op.landed(2, 8).unwrap(), None);
+            landed
+        })
+        .await
+        .unwrap();
+        assert_eq!(landed.as_deref(), Some("at1landed"));
+
+        // a node that can't answer leaves the message unknown
+        aleo.fail("latest/height", 500, "node down");
+        assert!(tokio::task::spawn_blocking(move || checked.landed(2, 9)).await.unwrap().is_err());
+    }
+}
@@ -0,0 +1,309 @@
+use std::{
+    collections::HashSet,
+    path::Path,
+    str::FromStr,
+    sync::{Arc, Mutex},
+};
+
+use aleo_rust::{Address, AleoAPIClient, Network, Plaintext, Record, Value};
+use izar_core::{
+    db::{map::DBMap, RocksDB},
+    network::aleo::AleoNetworkExt,
+    types::transaction::Priority,
+    utils::PlaintextCodec,
+};
+use serde::{Deserialize, Serialize};
+
+const CREDITS_PROGRAM: &str = "credits.aleo";
+const ACCOUNT_MAPPING: &str = "account";
+
+#[derive(Clone)]
+pub enum FeeSource<N: Network> {
+    Public,
+    Private(String, Record<N, Plaintext<N>>),
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Reservation {
+    /// address of the key paying the fee
+    pub owner: String,
+    // serial number of the reserved record, none for a public fee
+    pub record: Option<String>,
+    pub amount: u64,
+}
+
+/// How much priority fee an execution offers on top of its base fee, in microcredits.
+#[derive(Debug, Clone, Deserialize)]
+#[serde(tag = "policy", rename_all = "snake_case")]
+pub enum PriorityFeePolicy {
+    Fixed {
+        fee: u64,
+    },
+    /// `base` plus `per_tx` for every aleo execution still queued, capped at `max`.
+    Backlog {
+        base: u64,
+        per_tx: u64,
+        max: u64,
+    },
+    /// Follows `IzarTransaction::priority`.
+    Priority {
+        high: u64,
+        medium: u64,
+        low: u64,
+    },
+}
+
+impl PriorityFeePolicy {
+    pub fn priority_fee(&self, priority: Priority, backlog: usize) -> u64 {
+        match self {
+            Self::Fixed { fee } => *fee,
+            Self::Backlog { base, per_tx, max } => base.saturating_add(per_tx.saturating_mul(backlog as u64)).min(*max),
+            Self::Priority { high, medium, low } => match priority {
+                Priority::High => *high,
+                Priority::Medium => *medium,
+                Priority::Low => *low,
+            },
+        }
+    }
+}
+
+/// Keeps the fee records of every relayer key and hands out one fee source per in-flight execution.
+#[derive(Clone)]
+pub struct FeeManager<N: Network> {
+    unspent_records: DBMap<String, Record<N, Plaintext<N>>>,
+    reservations: DBMap<String, Reservation>,
+    base_fees: DBMap<String, u64>,
+    lock: Arc<Mutex<()>>,
+}
+
+impl<N: Network> FeeManager<N> {
+    pub fn new(db_path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let unspent_records = RocksDB::open_map(&db_path, "unspent_records")?;
+        let reservations = RocksDB::open_map(&db_path, "fee_reservations")?;
+        let base_fees = RocksDB::open_map(&db_path, "aleo_base_fees")?;
+
+        Ok(Self { unspent_records, reservations, base_fees, lock: Arc::new(Mutex::new(())) })
+    }
+
+    pub fn insert_record(&self, sn: String, record: Record<N, Plaintext<N>>) -> anyhow::Result<()> {
+        self.unspent_records.insert(sn, record)
+    }
+
+    pub fn spend_record(&self, sn: &str) -> anyhow::Result<()> {
+        self.unspent_records.remove(&sn.to_string())
+    }
+
+    pub fn records(&self, owner: &Address<N>) -> anyhow::Result<Vec<(String, Record<N, Plaintext<N>>)>> {
+        Ok(self.unspent_records.get_all()?.into_iter().filter(|(_, record)| **record.owner() == *owner).collect())
+    }
+
+    pub fn public_balance(&self, owner: &Address<N>, client: &AleoAPIClient<N>) -> anyhow::Result<u64> {
+        let key = Plaintext::<N>::from_str(&owner.to_string())?;
+        let value = client.get_mapping_value(CREDITS_PROGRAM, ACCOUNT_MAPPING, key)?;
+        match value {
+            Value::Plaintext(p) => PlaintextCodec::<N>::decode(&p),
+            _ => anyhow::bail!("invalid account balance"),
+        }
+    }
+
+    /// Base fee to pay for a call into `program`, learned from its last execution with a 10% margin.
+    /// A program never executed yet gets the network fee limit.
+    pub fn estimate_base_fee(&self, program: &str) -> anyhow::Result<u64> {
+        match self.base_fees.get(&program.to_string())? {
+            Some(fee) => Ok(fee.saturating_add(fee / 10)),
+            None => Ok(N::ALEO_FEE_LIMIT),
+        }
+    }
+
+    // aleo programs are immutable, a new version is deployed under a new id, so the id keys the estimate
+    pub fn record_base_fee(&self, program: &str, fee: u64) -> anyhow::Result<()> {
+        self.base_fees.insert(program.to_string(), fee)
+    }
+
+    /// Address of the key already holding a fee for `tid`.
+    pub fn reserved_by(&self, tid: &str) -> anyhow::Result<Option<String>> {
+        Ok(self.reservations.get(&tid.to_string())?.map(|r| r.owner))
+    }
+
+    /// Picks an unreserved private record of `owner` covering `amount`, falls back to its public balance.
+    /// Reserving again for the same `tid` returns the source it already holds.
+    pub fn reserve(
+        &self,
+        tid: &str,
+        owner: &Address<N>,
+        amount: u64,
+        client: &AleoAPIClient<N>,
+    ) -> anyhow::Result<FeeSource<N>> {
+        if let Some(source) = self.reserve_with(tid, owner, amount, None)? {
+            return Ok(source);
+        }
+        // read without the lock, every other reservation would wait for the round trip
+        let balance = self.public_balance(owner, client)?;
+        self.reserve_with(tid, owner, amount, Some(balance))?.ok_or(anyhow::anyhow!("no fee source of {owner}"))
+    }
+
+    // none when no record covers `amount` and the public `balance` is still to be read
+    fn reserve_with(
+        &self,
+        tid: &str,
+        owner: &Address<N>,
+        amount: u64,
+        balance: Option<u64>,
+    ) -> anyhow::Result<Option<FeeSource<N>>> {
+        let _guard = self.lock.lock().map_err(|_| anyhow::anyhow!("fee manager lock poisoned"))?;
+
+        if let Some(reservation) = self.reservations.get(&tid.to_string())? {
+            return self.source_of(&reservation).map(Some);
+        }
+
+        let owner_str = owner.to_string();
+        let reservations = self.reservations.get_all()?;
+        let reserved = reservations.iter().filter_map(|(_, r)| r.record.clone()).collect::<HashSet<String>>();
+        let record = self
+            .records(owner)?
+            .into_iter()
+            .find(|(sn, record)| !reserved.contains(sn) && record.microcredits().map(|c| c >= amount).unwrap_or(false));
+
+        let reservation = match record {
+            Some((sn, _)) => Reservation { owner: owner_str, record: Some(sn), amount },
+            None => {
+                let Some(balance) = balance else {
+                    return Ok(None);
+                };
+                let in_flight = reservations
+                    .iter()
+                    .filter(|(_, r)| r.record.is_none() && r.owner == owner_str)
+                    .map(|(_, r)| r.amount)
+                    .sum::<u64>();
+                if balance.saturating_sub(in_flight) < amount {
+                    anyhow::bail!(
+                        "no fee source of {owner} covers {amount}, public balance {balance}, in flight {in_flight}"
+                    );
+                }
+                Reservation { owner: owner_str, record: None, amount }
+            }
+        };
+
+        let source = self.source_of(&reservation)?;
+        self.reservations.insert(tid.to_string(), reservation)?;
+        Ok(Some(source))
+    }
+
+    /// The execution landed and paid its fee, so the reserved record is gone for good.
+    pub fn release(&self, tid: &str) -> anyhow::Result<()> {
+        let _guard = self.lock.lock().map_err(|_| anyhow::anyhow!("fee manager lock poisoned"))?;
+        if let Some(Reservation { record: Some(sn), .. }) = self.reservations.take(&tid.to_string())? {
+            self.unspent_records.remove(&sn)?;
+        }
+        Ok(())
+    }
+
+    /// The execution never made it onchain, the reserved source is usable again.
+    pub fn rollback(&self, tid: &str) -> anyhow::Result<()> {
+        let _guard = self.lock.lock().map_err(|_| anyhow::anyhow!("fee manager lock poisoned"))?;
+        self.reservations.remove(&tid.to_string())
+    }
+
+    fn source_of(&self, reservation: &Reservation) -> anyhow::Result<FeeSource<N>> {
+        match &reservation.record {
+            Some(sn) => {
+                let record = self.unspent_records.get(sn)?.ok_or(anyhow::anyhow!("reserved record {sn} was spent"))?;
+                Ok(FeeSource::Private(sn.clone(), record))
+            }
+            None => Ok(FeeSource::Public),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{
+        str::FromStr,
+        time::{Duration, Instant},
+    };
+
+    use aleo_rust::{Address, AleoAPIClient, Plaintext, PrivateKey, Record, Testnet3};
+    use izar_testkit::aleo::MockAleo;
+
+    use super::{FeeManager, FeeSource};
+
+    const NONCE: &str = "2845723700945552257439902855147005550602958568916589593762137374624015164729group";
+
+    fn record(owner: &Address<Testnet3>, microcredits: u64) -> Record<Testnet3, Plaintext<Testnet3>> {
+        let record =
+            format!("{{ owner: {owner}.private, microcredits: {microcredits}u64.private, _nonce: {NONCE}.public }}");
+        Record::from_str(&record).unwrap()
+    }
+
+    // serial number of a private source, none for the public balance
+    fn sn(source: FeeSource<Testnet3>) -> Option<String> {
+        match source {
+            FeeSource::Private(sn, _) => Some(sn),
+            FeeSource::Public => None,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_reserve() {
+        let aleo = MockAleo::start().await.unwrap();
+        let db = tempfile::tempdir().unwrap();
+        let fees = FeeManager::<Testnet3>::new(db.path()).unwrap();
+        let owner = Address::try_from(PrivateKey::<Testnet3>::new(&mut rand::thread_rng()).unwrap()).unwrap();
+        aleo.set_mapping("credits.aleo", "account", owner, "3000u64");
+        fees.insert_record("sn1".to_string(), record(&owner, 1000)).unwrap();
+        fees.insert_record("sn2".to_string(), record(&owner, 5000)).unwrap();
+        let client = AleoAPIClient::<Testnet3>::new(&aleo.url(), "testnet3").unwrap();
+
+        tokio::task::spawn_blocking(move || {
+            // the first record covering the fee, the same one again when the transfer asks twice
+            assert_eq!(sn(fees.reserve("t1", &owner, 2000, &client).unwrap()), Some("sn2".to_string()));
+            assert_eq!(sn(fees.reserve("t1", &owner, 2000, &client).unwrap()), Some("sn2".to_string()));
+            assert_eq!(fees.reserved_by("t1").unwrap(), Some(owner.to_string()));
+
+            // a reserved record is not handed out twice, the public balance pays instead
+            assert_eq!(sn(fees.reserve("t2", &owner, 2000, &client).unwrap()), None);
+            // and public fees in flight count against it
+            assert!(fees.reserve("t3", &owner, 2000, &client).is_err());
+
+            // a rollback makes the source usable again
+            fees.rollback("t2").unwrap();
+            assert_eq!(fees.reserved_by("t2").unwrap(), None);
+            assert_eq!(sn(fees.reserve("t3", &owner, 2000, &client).unwrap()), None);
+
+            // a release spends the record for good
+            fees.release("t1").unwrap();
+            assert_eq!(fees.reserved_by("t1").unwrap(), None);
+            assert_eq!(fees.records(&owner).unwrap().into_iter().map(|(sn, _)| sn).collect::<Vec<_>>(), ["sn1"]);
+            assert_eq!(sn(fees.reserve("t4", &owner, 500, &client).unwrap()), Some("sn1".to_string()));
+        })
+        .await
+        .unwrap();
+    }
+
+    #[tokio::test]
+    async fn test_reserve_off_lock() {
+        let aleo = MockAleo::start().await.unwrap();
+        let db = tempfile::tempdir().unwrap();
+        let fees = FeeManager::<Testnet3>::new(db.path()).unwrap();
+        let owner = Address::try_from(PrivateKey::<Testnet3>::new(&mut rand::thread_rng()).unwrap()).unwrap();
+        aleo.set_mapping("credits.aleo", "account", owner, "3000u64");
+        fees.insert_record("sn1".to_string(), record(&owner, 1000)).unwrap();
+        aleo.delay("mapping", Some(Duration::from_millis(500)));
+
+        // the public balance is read while another execution takes a record
+        let (public, client) = (fees.clone(), AleoAPIClient::<Testnet3>::new(&aleo.url(), "testnet3").unwrap());
+        let reading = tokio::task::spawn_blocking(move || public.reserve("t1", &owner, 2000, &client).map(sn));
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        let client = AleoAPIClient::<Testnet3>::new(&aleo.url(), "testnet3").unwrap();
+        let (took, private) = tokio::task::spawn_blocking(move || {
+            let started = Instant::now();
+            let private = fees.reserve("t2", &owner, 500, &client).map(sn);
+            (started.elapsed(), private)
+        })
+        .await
+        .unwrap();
+        assert_eq!(private.unwrap(), Some("sn1".to_string()));
+        assert!(took < Duration::from_millis(300), "waited {took:?} on the balance read");
+        assert_eq!(reading.await.unwrap().unwrap(), None);
+    }
+}
@@ -1,21 +1,42 @@
-use aleo_rust::Network;
+use aleo_rust::{Address, Network};
 use async_trait::async_trait;
 use izar_core::{
     network::{aleo::AleoNetworkExt, IzarNetwork},
-    types::{aleo::IzarRecvMsg, transaction::IzarTransaction},
+    types::{
+        aleo::IzarRecvMsg,
+        transaction::{IzarTransaction, Priority},
+    },
     utils::PlaintextCodec,
+    Signature, ToFields,
 };
 use std::str::FromStr;
 
-use crate::{relayer::types::IzarPendingTransaction, Operator};
+use crate::{
+    relayer::{
+        profit::Cost,
+        types::{IzarPendingTransaction, KeyBalance, ReceiveState, TransactionStatus},
+    },
+    Operator,
+};
 
-use super::{connector::AleoOperator, INVALID_SIGN, INVALID_VALIDATOR};
+use super::{
+    connector::AleoOperator, fee::FeeSource, pool::ProveRequest, INVALID_SIGN, INVALID_VALIDATOR, RECEIVE_FUNCTION,
+};
 
 #[async_trait]
 impl<I: IzarNetwork, N: Network> Operator<I> for AleoOperator<N> {
     async fn execute(&self, mut tx: IzarTransaction) -> anyhow::Result<IzarTransaction> {
         let payload = IzarRecvMsg::<N>::try_from(&tx)?.encode()?;
-        let keepers = self.get_current_keepers()?;
+        let (base_fee, priority_fee) = self.execution_fee(tx.priority)?;
+        // the keepers and the public balance are read over blocking http, off the runtime
+        let op = self.clone();
+        let tid = tx.from_chain_tx_hash.clone();
+        let (keepers, key, fee_source) = tokio::task::spawn_blocking(move || -> anyhow::Result<_> {
+            let keepers = op.get_current_keepers()?;
+            let (key, fee_source) = op.reserve_fee(&tid, base_fee.saturating_add(priority_fee))?;
+            Ok((keepers, key.clone(), fee_source))
+        })
+        .await??;
         let mut signatures = vec![INVALID_SIGN.to_string(); keepers.len()];
         for c in tx.certificates.iter() {
             for (i, k) in keepers.iter().enumerate() {
@@ -29,23 +50,100 @@ impl<I: IzarNetwork, N: Network> Operator<I> for AleoOperator<N> {
         }
         let inputs = vec![N::format_input_array(&signatures), N::format_input_array(&keepers), payload.to_string()];
 
-        let result = self.pm().execute_program(
-            N::ALEO_PROXY_CONTRACT,
-            "receive_payload",
-            inputs.iter(),
-            N::ALEO_PRIORITY_FEE,
-            None,
-            None,
-            None,
-        )?;
+        let program = N::ALEO_PROXY_CONTRACT;
+        let fee_record = match fee_source {
+            FeeSource::Private(_, record) => Some(record),
+            FeeSource::Public => None,
+        };
+        let request = ProveRequest {
+            tid: tx.from_chain_tx_hash.clone(),
+            private_key: key.private_key,
+            program_id: program.to_string(),
+            function: RECEIVE_FUNCTION.to_string(),
+            inputs,
+            fee_record,
+            base_fee,
+            priority_fee,
+        };
+        let result = match self.prover().prove(request).await {
+            Ok(transaction) => {
+                self.learn_base_fee(program, base_fee, &transaction);
+                self.broadcast(transaction).await
+            }
+            Err(e) => Err(e),
+        };
+        let result = match result {
+            Ok(result) => result,
+            Err(e) => {
+                self.fees().rollback(&tx.from_chain_tx_hash)?;
+                return Err(e);
+            }
+        };
         tx.to_chain_tx_hash = Some(result.trim_matches('"').to_string());
         Ok(tx)
     }
 
+    fn validate(&self, tx: &IzarTransaction) -> anyhow::Result<()> {
+        let fields = IzarRecvMsg::<N>::try_from(tx)?.encode()?.to_fields()?;
+        for c in tx.certificates.iter() {
+            let signer = Address::<N>::from_str(&c.signer)?;
+            if !Signature::<N>::from_str(&c.signature)?.verify(&signer, &fields) {
+                anyhow::bail!("certificate of {} does not sign the transfer", c.signer);
+            }
+        }
+        Ok(())
+    }
+
     fn pending(&self, tx: IzarTransaction) -> anyhow::Result<IzarPendingTransaction<I>> {
         let tx_hash =
             <I::Aleo as Network>::TransactionID::from_str(&tx.to_chain_tx_hash.expect("empty to chain tx hash"))
                 .map_err(|_| anyhow::anyhow!("parse txid failed"))?;
         Ok(IzarPendingTransaction::aleo(tx_hash, self.reqwest_client(), self.pm().api_client()?.base_url()))
     }
+
+    async fn received(&self, tx: &IzarTransaction) -> anyhow::Result<ReceiveState> {
+        let msg = IzarRecvMsg::<N>::try_from(tx)?;
+        let (from_chain_id, nonce) = (msg.from_chain_id, msg.nonce);
+        let op = self.clone();
+        let landed = tokio::task::spawn_blocking(move || op.landed(from_chain_id, nonce)).await??;
+        Ok(match landed {
+            Some(tid) => ReceiveState::Received(Some(tid)),
+            None => ReceiveState::NotReceived,
+        })
+    }
+
+    fn set_backlog(&self, queued: usize) {
+        AleoOperator::set_backlog(self, queued)
+    }
+
+    fn settle(&self, tx: &IzarTransaction, status: &TransactionStatus) -> anyhow::Result<()> {
+        match status {
+            // a rejected execution still pays its fee
+            TransactionStatus::Success(_) | TransactionStatus::Rejected(_) => {
+                self.fees().release(&tx.from_chain_tx_hash)
+            }
+            TransactionStatus::NotBroadcasted(_) | TransactionStatus::Reverted(_) => {
+                self.fees().rollback(&tx.from_chain_tx_hash)
+            }
+        }
+    }
+
+    fn cancel(&self, tx: &IzarTransaction) {
+        self.prover().cancel(&tx.from_chain_tx_hash)
+    }
+
+    async fn balances(&self) -> anyhow::Result<Vec<KeyBalance>> {
+        let op = self.clone();
+        tokio::task::spawn_blocking(move || op.key_balances()).await?
+    }
+
+    async fn execution_budget(&self) -> anyhow::Result<Option<u128>> {
+        let (base_fee, priority_fee) = self.execution_fee(Priority::High)?;
+        Ok(Some(base_fee.saturating_add(priority_fee) as u128))
+    }
+
+    async fn estimate_cost(&self, tx: &IzarTransaction) -> anyhow::Result<Option<Cost>> {
+        let (base_fee, priority_fee) = self.execution_fee(tx.priority)?;
+        Ok(Some(Cost::native(N::IZAR_CHAIN_ID, base_fee.saturating_add(priority_fee) as u128)))
+    }
 }
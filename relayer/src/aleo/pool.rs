@@ -0,0 +1,197 @@
+use std::{
+    collections::HashMap,
+    str::FromStr,
+    sync::{
+        atomic::{AtomicUsize, Ordering},
+        mpsc::{self, Receiver, SyncSender, TrySendError},
+        Arc, Mutex,
+    },
+    time::Instant,
+};
+
+use aleo_rust::{
+    AleoAPIClient, ConsensusMemory, ConsensusStore, Identifier, Network, Plaintext, PrivateKey, ProgramID, Query,
+    Record, Transaction, VM,
+};
+use async_trait::async_trait;
+use izar_core::metrics::relayer::{RELAYER_PROOF_DURATION, RELAYER_PROVER_QUEUE_DEPTH};
+use tokio::sync::oneshot;
+
+use super::prover::{load_program, Prover};
+
+#[derive(Clone)]
+pub struct ProveRequest<N: Network> {
+    pub tid: String,
+    pub private_key: PrivateKey<N>,
+    pub program_id: String,
+    pub function: String,
+    pub inputs: Vec<String>,
+    pub fee_record: Option<Record<N, Plaintext<N>>>,
+    /// Only used when the fee is authorized apart from the execution, a local run computes its own.
+    pub base_fee: u64,
+    pub priority_fee: u64,
+}
+
+struct Job<N: Network> {
+    request: ProveRequest<N>,
+    reply: oneshot::Sender<anyhow::Result<Transaction<N>>>,
+}
+
+/// A fixed set of blocking threads that build Aleo executions off the tokio runtime.
+/// Every worker owns its own VM, so the proving keys synthesized by a run stay loaded for the next one.
+pub struct ProvingPool<N: Network> {
+    sender: SyncSender<Job<N>>,
+    depth: Arc<AtomicUsize>,
+    jobs: Jobs,
+}
+
+/// Proofs asked for and not answered yet, by tid, true once cancelled. Entries leave with the answer, so a cancel
+/// reaches only a proof in flight.
+#[derive(Clone, Default)]
+pub(crate) struct Jobs(Arc<Mutex<HashMap<String, bool>>>);
+
+impl Jobs {
+    pub(crate) fn start(&self, tid: &str) {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).insert(tid.to_string(), false);
+    }
+
+    pub(crate) fn cancel(&self, tid: &str) {
+        if let Some(cancelled) = self.0.lock().unwrap_or_else(|e| e.into_inner()).get_mut(tid) {
+            *cancelled = true;
+        }
+    }
+
+    pub(crate) fn is_cancelled(&self, tid: &str) -> bool {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).get(tid).copied().unwrap_or(false)
+    }
+
+    /// Forgets `tid`, returns whether it was cancelled.
+    pub(crate) fn finish(&self, tid: &str) -> bool {
+        self.0.lock().unwrap_or_else(|e| e.into_inner()).remove(tid).unwrap_or(false)
+    }
+}
+
+impl<N: Network> ProvingPool<N> {
+    pub fn new(workers: usize, queue_size: usize, client: AleoAPIClient<N>) -> anyhow::Result<Self> {
+        let (sender, receiver) = mpsc::sync_channel::<Job<N>>(queue_size);
+        let receiver = Arc::new(Mutex::new(receiver));
+        let depth = Arc::new(AtomicUsize::new(0));
+        let jobs = Jobs::default();
+
+        for i in 0..workers.max(1) {
+            let vm = VM::from(ConsensusStore::<N, ConsensusMemory<N>>::open(None)?)?;
+            let worker = Worker { vm, client: client.clone(), depth: depth.clone(), jobs: jobs.clone() };
+            let receiver = receiver.clone();
+            std::thread::Builder::new().name(format!("aleo-prover-{i}")).spawn(move || worker.run(receiver))?;
+        }
+
+        Ok(Self { sender, depth, jobs })
+    }
+
+    pub fn queue_depth(&self) -> usize {
+        self.depth.load(Ordering::SeqCst)
+    }
+}
+
+#[async_trait]
+impl<N: Network> Prover<N> for ProvingPool<N> {
+    async fn prove(&self, request: ProveRequest<N>) -> anyhow::Result<Transaction<N>> {
+        let tid = request.tid.clone();
+        let (reply, rx) = oneshot::channel();
+        // counted before sending, a worker may pick the job up right away
+        let depth = self.depth.fetch_add(1, Ordering::SeqCst) + 1;
+        metrics::gauge!(RELAYER_PROVER_QUEUE_DEPTH).set(depth as f64);
+        self.jobs.start(&tid);
+        if let Err(e) = self.sender.try_send(Job { request, reply }) {
+            self.depth.fetch_sub(1, Ordering::SeqCst);
+            self.jobs.finish(&tid);
+            match e {
+                TrySendError::Full(_) => anyhow::bail!("aleo proving queue is full"),
+                TrySendError::Disconnected(_) => anyhow::bail!("aleo proving pool is down"),
+            }
+        }
+
+        let result = rx.await.map_err(|_| anyhow::anyhow!("aleo prover dropped the job"));
+        match self.jobs.finish(&tid) {
+            true => anyhow::bail!("proof of {tid} cancelled"),
+            false => result?,
+        }
+    }
+
+    /// Drops the proof of `tid`, a queued job is skipped and a running one is discarded once it finishes.
+    fn cancel(&self, tid: &str) {
+        self.jobs.cancel(tid)
+    }
+}
+
+struct Worker<N: Network> {
+    vm: VM<N, ConsensusMemory<N>>,
+    client: AleoAPIClient<N>,
+    depth: Arc<AtomicUsize>,
+    jobs: Jobs,
+}
+
+impl<N: Network> Worker<N> {
+    fn run(self, receiver: Arc<Mutex<Receiver<Job<N>>>>) {
+        loop {
+            let job = match receiver.lock().map(|r| r.recv()) {
+                Ok(Ok(job)) => job,
+                _ => return,
+            };
+            let depth = self.depth.fetch_sub(1, Ordering::SeqCst) - 1;
+            metrics::gauge!(RELAYER_PROVER_QUEUE_DEPTH).set(depth as f64);
+
+            // the caller tells a cancelled proof apart when the answer comes
+            let Job { request, reply } = job;
+            if self.jobs.is_cancelled(&request.tid) {
+                let _ = reply.send(Err(anyhow::anyhow!("proof of {} cancelled", request.tid)));
+                continue;
+            }
+
+            let start = Instant::now();
+            let result = self.prove(&request);
+            metrics::histogram!(RELAYER_PROOF_DURATION).record(start.elapsed().as_secs_f64());
+            tracing::info!("proved {} in {:?}", request.tid, start.elapsed());
+            let _ = reply.send(result);
+        }
+    }
+
+    fn prove(&self, request: &ProveRequest<N>) -> anyhow::Result<Transaction<N>> {
+        let program_id = ProgramID::<N>::from_str(&request.program_id)?;
+        load_program(&self.vm, &self.client, &program_id)?;
+
+        let function = Identifier::<N>::from_str(&request.function)?;
+        let query = Query::from(self.client.base_url());
+        self.vm.execute(
+            &request.private_key,
+            (program_id, function),
+            request.inputs.iter(),
+            request.fee_record.clone(),
+            request.priority_fee,
+            Some(query),
+            &mut rand::thread_rng(),
+        )
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::Jobs;
+
+    #[test]
+    fn test_jobs() {
+        let jobs = Jobs::default();
+        // nothing is kept for a proof that is not in flight, a later one of the same tid runs
+        jobs.cancel("t1");
+        jobs.start("t1");
+        assert!(!jobs.is_cancelled("t1"));
+
+        jobs.cancel("t1");
+        assert!(jobs.is_cancelled("t1"));
+        assert!(jobs.finish("t1"));
+        assert!(!jobs.finish("t1"));
+
+        jobs.start("t1");
+        assert!(!jobs.finish("t1"));
+    }
+}
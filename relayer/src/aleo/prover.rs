@@ -0,0 +1,198 @@
+use std::{str::FromStr, sync::Arc, time::Duration};
+
+use aleo_rust::{
+    AleoAPIClient, Authorization, ConsensusMemory, ConsensusStore, Identifier, Network, ProgramID, Transaction, VM,
+};
+use async_trait::async_trait;
+
+use super::pool::{Jobs, ProveRequest};
+
+/// Turns a `ProveRequest` into a transaction ready to broadcast.
+#[async_trait]
+pub trait Prover<N: Network>: Send + Sync {
+    async fn prove(&self, request: ProveRequest<N>) -> anyhow::Result<Transaction<N>>;
+    fn cancel(&self, tid: &str);
+}
+
+/// Client of an HTTP proving service, it takes the authorizations and answers with the finished transaction.
+#[derive(Clone)]
+pub struct ProvingService {
+    url: String,
+    client: reqwest::Client,
+}
+
+impl ProvingService {
+    pub fn new(url: String, timeout: Duration) -> anyhow::Result<Self> {
+        let client = reqwest::Client::builder().timeout(timeout).build()?;
+        Ok(Self { url, client })
+    }
+
+    pub async fn prove(&self, body: &serde_json::Value) -> anyhow::Result<serde_json::Value> {
+        let resp = self.client.post(format!("{}/prove", self.url)).json(body).send().await?;
+        let status = resp.status();
+        if !status.is_success() {
+            anyhow::bail!("proving service answered {}: {}", status, resp.text().await?);
+        }
+
+        let mut body = resp.json::<serde_json::Value>().await?;
+        body.get_mut("transaction")
+            .map(serde_json::Value::take)
+            .ok_or(anyhow::anyhow!("proving service response has no transaction"))
+    }
+}
+
+/// Authorizes locally, which is cheap, and leaves the proof to a `ProvingService`.
+pub struct DelegatedProver<N: Network> {
+    service: ProvingService,
+    authorizer: Arc<Authorizer<N>>,
+    fallback: Option<Arc<dyn Prover<N>>>,
+    jobs: Jobs,
+}
+
+impl<N: Network> DelegatedProver<N> {
+    pub fn new(
+        service: ProvingService,
+        client: AleoAPIClient<N>,
+        fallback: Option<Arc<dyn Prover<N>>>,
+    ) -> anyhow::Result<Self> {
+        let vm = VM::from(ConsensusStore::<N, ConsensusMemory<N>>::open(None)?)?;
+        let authorizer = Arc::new(Authorizer { vm, client });
+        Ok(Self { service, authorizer, fallback, jobs: Jobs::default() })
+    }
+
+    async fn delegate(&self, request: ProveRequest<N>) -> anyhow::Result<Transaction<N>> {
+        let authorizer = self.authorizer.clone();
+        let (authorization, fee_authorization) =
+            tokio::task::spawn_blocking(move || authorizer.authorize(&request)).await??;
+
+        let body = serde_json::json!({
+            "authorization": serde_json::to_value(authorization)?,
+            "fee_authorization": serde_json::to_value(fee_authorization)?,
+        });
+        let transaction = self.service.prove(&body).await?;
+        Ok(serde_json::from_value(transaction)?)
+    }
+}
+
+#[async_trait]
+impl<N: Network> Prover<N> for DelegatedProver<N> {
+    async fn prove(&self, request: ProveRequest<N>) -> anyhow::Result<Transaction<N>> {
+        let tid = request.tid.clone();
+        self.jobs.start(&tid);
+        let result = match (self.delegate(request.clone()).await, &self.fallback) {
+            (Ok(transaction), _) => Ok(transaction),
+            (Err(e), Some(fallback)) if !self.jobs.is_cancelled(&tid) => {
+                tracing::warn!("delegated proving of {tid} failed, proving locally: {:?}", e);
+                fallback.prove(request).await
+            }
+            (Err(e), _) => Err(e),
+        };
+
+        match self.jobs.finish(&tid) {
+            true => anyhow::bail!("proof of {tid} cancelled"),
+            false => result,
+        }
+    }
+
+    fn cancel(&self, tid: &str) {
+        self.jobs.cancel(tid);
+        if let Some(fallback) = &self.fallback {
+            fallback.cancel(tid);
+        }
+    }
+}
+
+struct Authorizer<N: Network> {
+    vm: VM<N, ConsensusMemory<N>>,
+    client: AleoAPIClient<N>,
+}
+
+impl<N: Network> Authorizer<N> {
+    fn authorize(&self, request: &ProveRequest<N>) -> anyhow::Result<(Authorization<N>, Authorization<N>)> {
+        let program_id = ProgramID::<N>::from_str(&request.program_id)?;
+        load_program(&self.vm, &self.client, &program_id)?;
+
+        let rng = &mut rand::thread_rng();
+        let function = Identifier::<N>::from_str(&request.function)?;
+        let authorization =
+            self.vm.authorize(&request.private_key, program_id, function, request.inputs.iter(), rng)?;
+        let execution_id = authorization.to_execution_id()?;
+
+        let ProveRequest { private_key, base_fee, priority_fee, .. } = request;
+        let fee_authorization = match &request.fee_record {
+            Some(record) => self.vm.authorize_fee_private(
+                private_key,
+                record.clone(),
+                *base_fee,
+                *priority_fee,
+                execution_id,
+                rng,
+            )?,
+            None => self.vm.authorize_fee_public(private_key, *base_fee, *priority_fee, execution_id, rng)?,
+        };
+
+        Ok((authorization, fee_authorization))
+    }
+}
+
+// imports are added first, the process refuses a program with missing imports
+pub(crate) fn load_program<N: Network>(
+    vm: &VM<N, ConsensusMemory<N>>,
+    client: &AleoAPIClient<N>,
+    program_id: &ProgramID<N>,
+) -> anyhow::Result<()> {
+    if vm.process().read().contains_program(program_id) {
+        return Ok(());
+    }
+    let program = client.get_program(*program_id)?;
+    for import in program.imports().keys() {
+        load_program(vm, client, import)?;
+    }
+    vm.process().write().add_program(&program)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use axum::{http::StatusCode, routing::post, Json, Router};
+    use serde_json::json;
+
+    use super::ProvingService;
+
+    async fn stand_in(router: Router) -> String {
+        let listener = tokio::net::TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let addr = listener.local_addr().unwrap();
+        tokio::spawn(async move { axum::serve(listener, router).await.unwrap() });
+        format!("http://{}", addr)
+    }
+
+    #[tokio::test]
+    async fn test_proving_service_returns_transaction() {
+        let router = Router::new().route(
+            "/prove",
+            post(|Json(body): Json<serde_json::Value>| async move {
+                Json(json!({ "transaction": { "echo": body["authorization"] } }))
+            }),
+        );
+        let service = ProvingService::new(stand_in(router).await, Duration::from_secs(5)).unwrap();
+
+        let transaction = service.prove(&json!({ "authorization": "auth", "fee_authorization": "fee" })).await.unwrap();
+        assert_eq!(transaction, json!({ "echo": "auth" }));
+    }
+
+    #[tokio::test]
+    async fn test_proving_service_errors() {
+        let router = Router::new()
+            .route("/prove", post(|| async { (StatusCode::SERVICE_UNAVAILABLE, "busy") }))
+            .route("/empty/prove", post(|| async { Json(json!({})) }));
+        let url = stand_in(router).await;
+
+        let service = ProvingService::new(url.clone(), Duration::from_secs(5)).unwrap();
+        let err = service.prove(&json!({})).await.unwrap_err();
+        assert!(err.to_string().contains("503"));
+
+        let service = ProvingService::new(format!("{url}/empty"), Duration::from_secs(5)).unwrap();
+        assert!(service.prove(&json!({})).await.is_err());
+    }
+}
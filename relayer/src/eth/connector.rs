@@ -1,29 +1,54 @@
-use std::sync::Arc;
+use std::{path::Path, sync::Arc};
 
 use ethers::{
-    prelude::SignerMiddleware,
     providers::{Http, Provider},
-    signers::{LocalWallet, Signer},
+    types::H256,
+    utils::keccak256,
 };
-use izar_core::network::eth::EthNetwork;
+use izar_core::{
+    db::{map::DBMap, RocksDB},
+    network::eth::EthNetwork,
+};
+
+use super::wallet::{EthClient, WalletPool};
+
+const REPLACED_PREFIX: &str = "eth_replaced";
 
 #[derive(Clone)]
 pub struct EthOperator<E: EthNetwork> {
-    pub client: Arc<SignerMiddleware<Provider<Http>, LocalWallet>>,
-    private_key: String,
+    pub provider: Provider<Http>,
+    pub wallets: Arc<WalletPool>,
+    // source tx hash to the destination txs a fee bump replaced, oldest first
+    pub(crate) replaced: DBMap<String, Vec<String>>,
+    // topic of the event the logic contract emits for a received payload
+    pub(crate) receive_event: Option<H256>,
     phantom: std::marker::PhantomData<E>,
 }
 
 impl<E: EthNetwork> EthOperator<E> {
-    pub fn new(pk: String, dest: String) -> anyhow::Result<Self> {
-        let wallet = pk.parse::<LocalWallet>()?;
+    pub fn new(pks: Vec<String>, dest: String, db_path: impl AsRef<Path>) -> anyhow::Result<Self> {
         let provider = Provider::<Http>::try_from(dest)?;
+        let wallets = WalletPool::new(&pks, provider.clone(), E::ETH_CHAIN_ID, E::IZAR_CHAIN_ID)?;
+        let replaced = RocksDB::open_map(db_path, REPLACED_PREFIX)?;
+
+        Ok(Self {
+            provider,
+            wallets: Arc::new(wallets),
+            replaced,
+            receive_event: None,
+            phantom: std::marker::PhantomData,
+        })
+    }
 
-        let client = Arc::new(SignerMiddleware::new(provider, wallet.with_chain_id(E::ETH_CHAIN_ID)));
-        Ok(Self { client, private_key: pk, phantom: std::marker::PhantomData })
+    /// Finds executions that landed by `signature`, the event the logic contract emits for a received payload with
+    /// the source chain id and the nonce as its first two indexed arguments. Without it no transfer can be told
+    /// received, and every execution waits.
+    pub fn with_receive_event(mut self, signature: &str) -> Self {
+        self.receive_event = Some(H256(keccak256(signature)));
+        self
     }
 
-    pub fn pk(&self) -> &String {
-        &self.private_key
+    pub fn client(&self) -> Arc<EthClient> {
+        self.wallets.primary()
     }
 }
@@ -1,2 +1,3 @@
 pub mod connector;
 pub mod operator;
+pub mod wallet;
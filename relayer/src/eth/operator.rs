@@ -1,23 +1,35 @@
 use async_trait::async_trait;
 use ethers::{
-    contract::abigen,
-    types::{Address, Bytes, Signature, H256, U256},
+    contract::{abigen, ContractCall, ContractError},
+    providers::Middleware,
+    types::{
+        transaction::eip2718::TypedTransaction, Address, Bytes, Eip1559TransactionRequest, Filter, RecoveryMessage,
+        Signature, TransactionRequest, H256, U256,
+    },
 };
 use izar_core::{
     network::{eth::EthNetwork, IzarNetwork},
-    types::transaction::IzarTransaction,
+    types::{eth::receive_hash, transaction::IzarTransaction},
 };
-use std::str::FromStr;
+use std::{str::FromStr, sync::Arc};
 
-use crate::{relayer::types::IzarPendingTransaction, Operator};
+use crate::{
+    relayer::{
+        profit::Cost,
+        types::{ExecuteError, IzarPendingTransaction, KeyBalance, ReceiveState, TransactionStatus},
+    },
+    Operator,
+};
 
-use super::connector::EthOperator;
+use super::{connector::EthOperator, wallet::EthClient};
 
 abigen!(Bridge, "./src/eth/abi.json", event_derives(serde::Deserialize, serde::Serialize));
+abigen!(L1FeeOracle, r#"[function getL1Fee(bytes) external view returns (uint256)]"#);
 
-#[async_trait]
-impl<I: IzarNetwork, E: EthNetwork> Operator<I> for EthOperator<E> {
-    async fn execute(&self, mut tx: IzarTransaction) -> anyhow::Result<IzarTransaction> {
+type ReceiveCall = ContractCall<EthClient, ()>;
+
+impl<E: EthNetwork> EthOperator<E> {
+    fn receive_call(&self, tx: &IzarTransaction, client: Arc<EthClient>) -> anyhow::Result<ReceiveCall> {
         let sigs = tx
             .certificates
             .iter()
@@ -30,22 +42,434 @@ impl<I: IzarNetwork, E: EthNetwork> Operator<I> for EthOperator<E> {
         let src_addr_bytes = Bytes::from(tx.from_addr.as_bytes().to_vec());
         let payload = Bytes::from(tx.eth_payload()?);
         let (logic_addr, lock_addr) = (E::LOGIC_CONTRACT.parse::<Address>()?, E::LOCK_CONTRACT.parse::<Address>()?);
-        let bridge_call = Bridge::new(logic_addr, self.client.clone());
+        let bridge_call = Bridge::new(logic_addr, client);
 
         // need support eip1159
-        let tx_hash = bridge_call
+        Ok(bridge_call
             .receive_payload(from_chain_id, nonce, src_addr_bytes, lock_addr, payload, sigs, U256::from(E::GAS_LIMIT))
-            .gas(E::GAS_LIMIT)
-            .send()
-            .await?
-            .tx_hash();
+            .gas(E::GAS_LIMIT))
+    }
+}
+
+#[async_trait]
+impl<I: IzarNetwork, E: EthNetwork> Operator<I> for EthOperator<E> {
+    async fn execute(&self, mut tx: IzarTransaction) -> anyhow::Result<IzarTransaction> {
+        let needed = U256::from(E::GAS_LIMIT) * self.provider.get_gas_price().await?;
+        let wallet = self.wallets.pick(needed).await?;
+        let call = self.receive_call(&tx, wallet)?;
+
+        // simulate against the latest block first, a revert onchain still costs gas
+        if let Err(e) = call.call().await {
+            return Err(match revert_reason(&e) {
+                Some(reason) => ExecuteError::Reverted(reason).into(),
+                None => e.into(),
+            });
+        }
+
+        let tx_hash = call.send().await?.tx_hash();
 
         tx.to_chain_tx_hash = Some(E::format_str(tx_hash));
         Ok(tx)
     }
 
+    fn validate(&self, tx: &IzarTransaction) -> anyhow::Result<()> {
+        tx.to_addr.parse::<Address>()?;
+        tx.to_asset_addr.parse::<Address>()?;
+        let hash = receive_hash::<E>(tx)?;
+        for c in tx.certificates.iter() {
+            let signer = c.signer.parse::<Address>()?;
+            Signature::from_str(&c.signature)?
+                .verify(RecoveryMessage::Hash(hash), signer)
+                .map_err(|e| anyhow::anyhow!("certificate of {} does not sign the transfer: {}", c.signer, e))?;
+        }
+        Ok(())
+    }
+
     fn pending(&self, tx: IzarTransaction) -> anyhow::Result<IzarPendingTransaction<I>> {
         let tx_hash = H256::from_str(&tx.to_chain_tx_hash.expect("empty to chain tx hash"))?;
-        Ok(IzarPendingTransaction::eth(tx_hash, self.client.clone(), E::CONFIRMATIONS))
+        let replaced = self.replaced.get(&tx.from_chain_tx_hash)?.unwrap_or_default();
+        let replaced = replaced.iter().map(|h| H256::from_str(h)).collect::<Result<Vec<_>, _>>()?;
+        Ok(IzarPendingTransaction::eth(tx_hash, replaced, self.provider.clone(), E::CONFIRMATIONS))
+    }
+
+    fn settle(&self, tx: &IzarTransaction, _status: &TransactionStatus) -> anyhow::Result<()> {
+        self.replaced.remove(&tx.from_chain_tx_hash)
+    }
+
+    async fn bump(&self, tx: &IzarTransaction) -> anyhow::Result<Option<IzarTransaction>> {
+        let hash = tx.to_chain_tx_hash.as_deref().ok_or_else(|| anyhow::anyhow!("no destination tx hash"))?;
+        let hash = H256::from_str(hash)?;
+        let sent = self.provider.get_transaction(hash).await?.ok_or_else(|| anyhow::anyhow!("{hash:?} is unknown"))?;
+        if sent.block_number.is_some() {
+            tracing::info!("{:?} is already mined, nothing to bump for tx {}", hash, tx.from_chain_tx_hash);
+            return Ok(None);
+        }
+        let wallet = self.wallets.by_address(sent.from).ok_or_else(|| anyhow::anyhow!("{:?} is no key", sent.from))?;
+
+        // same nonce, so only one of them can land
+        let gas_price = self.provider.get_gas_price().await?;
+        let replacement: TypedTransaction = match (sent.max_fee_per_gas, sent.max_priority_fee_per_gas) {
+            (Some(max_fee), Some(tip)) => Eip1559TransactionRequest::new()
+                .max_fee_per_gas(bumped(max_fee).max(gas_price))
+                .max_priority_fee_per_gas(bumped(tip))
+                .from(sent.from)
+                .to(sent.to.unwrap_or_default())
+                .data(sent.input)
+                .value(sent.value)
+                .gas(sent.gas)
+                .nonce(sent.nonce)
+                .into(),
+            _ => TransactionRequest::new()
+                .gas_price(bumped(sent.gas_price.unwrap_or_default()).max(gas_price))
+                .from(sent.from)
+                .to(sent.to.unwrap_or_default())
+                .data(sent.input)
+                .value(sent.value)
+                .gas(sent.gas)
+                .nonce(sent.nonce)
+                .into(),
+        };
+        let new_hash = wallet.send_transaction(replacement, None).await?.tx_hash();
+        tracing::info!("replaced {:?} with {:?} for tx {}", hash, new_hash, tx.from_chain_tx_hash);
+
+        let mut replaced = self.replaced.get(&tx.from_chain_tx_hash)?.unwrap_or_default();
+        replaced.push(E::format_str(hash));
+        self.replaced.insert(tx.from_chain_tx_hash.clone(), replaced)?;
+
+        let mut tx = tx.clone();
+        tx.to_chain_tx_hash = Some(E::format_str(new_hash));
+        Ok(Some(tx))
+    }
+
+    async fn received(&self, tx: &IzarTransaction) -> anyhow::Result<ReceiveState> {
+        // a transfer that can't be checked waits, running it blind could execute it twice
+        let event =
+            self.receive_event.ok_or(anyhow::anyhow!("no receive event to check chain {} with", E::IZAR_CHAIN_ID))?;
+        let filter = Filter::new()
+            .address(E::LOGIC_CONTRACT.parse::<Address>()?)
+            .topic0(event)
+            .topic1(topic(U256::from(tx.from_chain_id)))
+            .topic2(topic(U256::from_dec_str(&tx.nonce)?))
+            .from_block(E::START_HEIGHT);
+        let logs = self.provider.get_logs(&filter).await?;
+        let landed = logs.into_iter().filter(|l| l.removed != Some(true)).find_map(|l| l.transaction_hash);
+        Ok(match landed {
+            Some(hash) => ReceiveState::Received(Some(E::format_str(hash))),
+            None => ReceiveState::NotReceived,
+        })
+    }
+
+    async fn estimate_cost(&self, tx: &IzarTransaction) -> anyhow::Result<Option<Cost>> {
+        let call = self.receive_call(tx, self.client())?;
+        let gas = call.estimate_gas().await?;
+        let mut amount = gas * self.provider.get_gas_price().await?;
+
+        // rollups charge the posting of the calldata to L1 on top of the execution gas
+        if let Some(oracle) = E::L1_FEE_ORACLE {
+            let oracle = L1FeeOracle::new(oracle.parse::<Address>()?, self.client());
+            amount += oracle.get_l1_fee(call.tx.rlp()).call().await?;
+        }
+
+        Ok(Some(Cost::native(E::IZAR_CHAIN_ID, amount.low_u128())))
+    }
+
+    async fn balances(&self) -> anyhow::Result<Vec<KeyBalance>> {
+        self.wallets.balances().await
+    }
+
+    async fn execution_budget(&self) -> anyhow::Result<Option<u128>> {
+        let budget = U256::from(E::GAS_LIMIT) * self.provider.get_gas_price().await?;
+        Ok(Some(budget.low_u128()))
+    }
+}
+
+// The bridge abi declares no custom errors, so a revert is either a plain `Error(string)` or raw data.
+fn revert_reason<M: Middleware>(e: &ContractError<M>) -> Option<String> {
+    if let Some(reason) = e.decode_revert::<String>() {
+        return Some(reason);
+    }
+    e.as_revert().map(|data| format!("unknown revert data: {}", data))
+}
+
+// an indexed value type is its own topic, big endian in 32 bytes
+fn topic(value: U256) -> H256 {
+    let mut topic = [0u8; 32];
+    value.to_big_endian(&mut topic);
+    H256(topic)
+}
+
+// nodes take a replacement paying at least 10% more, on the tip as well as the fee cap
+fn bumped(fee: U256) -> U256 {
+    fee + fee / 8 + 1
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, sync::Arc, time::Duration};
+
+    use axum::http::StatusCode;
+    use ethers::{
+        providers::{Http, Provider},
+        signers::{LocalWallet, Signer},
+        types::{Address, Log, Transaction, TransactionReceipt, H256, U256, U64},
+        utils::keccak256,
+    };
+    use izar_core::{
+        network::{eth::EthNetwork, testnet::IzarTestnet, IzarNetwork},
+        sequencer::SequencerClient,
+        types::{eth::receive_hash, transaction::IzarTransaction},
+    };
+    use izar_testkit::{
+        evm::{MockEvm, RpcError},
+        transfer::TransferBuilder,
+    };
+
+    use crate::{
+        eth::connector::EthOperator,
+        relayer::{
+            ingest::SpeedupAction,
+            store::TxState,
+            types::{ExecuteError, IzarPendingTransaction, ReceiveState, TransactionStatus},
+            IzarRelayer,
+        },
+        Operator,
+    };
+
+    type Sepolia = <IzarTestnet as IzarNetwork>::Sepolia;
+
+    const PK: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+    const RECEIVE_EVENT: &str = "PayloadReceived(uint16,uint256,bytes,address)";
+
+    fn transfer() -> IzarTransaction {
+        let tx = TransferBuilder::new("at1transfer", 2, Sepolia::IZAR_CHAIN_ID)
+            .with_from("", "aleo1from")
+            .with_to(format!("{:?}", Address::from_low_u64_be(2)), format!("{:?}", Address::from_low_u64_be(1)))
+            .with_nonce(7);
+        let wallet = PK.parse::<LocalWallet>().unwrap();
+        let signature = wallet.sign_hash(receive_hash::<Sepolia>(&tx.clone().build()).unwrap()).unwrap();
+        tx.with_certificate(format!("{:?}", wallet.address()), signature).build()
+    }
+
+    #[tokio::test]
+    async fn test_simulate() {
+        let evm = MockEvm::start(Sepolia::ETH_CHAIN_ID as u64, 100).await.unwrap();
+        let db = tempfile::tempdir().unwrap();
+        let op = EthOperator::<Sepolia>::new(vec![PK.to_string()], evm.url(), db.path()).unwrap();
+
+        // every execution is simulated once before it is sent
+        Operator::<IzarTestnet>::execute(&op, transfer()).await.unwrap();
+        assert_eq!((evm.requests("eth_call"), evm.sent().len()), (1, 1));
+
+        // a reverting simulation comes back with its reason and nothing is sent
+        evm.revert_calls(Some("invalid signature"));
+        let err = Operator::<IzarTestnet>::execute(&op, transfer()).await.unwrap_err();
+        assert!(
+            matches!(err.downcast_ref::<ExecuteError>(), Some(ExecuteError::Reverted(r)) if r == "invalid signature")
+        );
+        assert_eq!((evm.requests("eth_call"), evm.sent().len()), (2, 1));
+        evm.revert_calls(None);
+
+        // a node error is no revert, the transfer stays queued for another try
+        evm.fail("eth_call", RpcError::new(-32603, "node down"));
+        let err = Operator::<IzarTestnet>::execute(&op, transfer()).await.unwrap_err();
+        assert!(err.downcast_ref::<ExecuteError>().is_none());
+        assert_eq!(evm.sent().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_execute() {
+        let evm = MockEvm::start(Sepolia::ETH_CHAIN_ID as u64, 100).await.unwrap();
+        let db = tempfile::tempdir().unwrap();
+        let op = EthOperator::<Sepolia>::new(vec![PK.to_string()], evm.url(), db.path()).unwrap();
+        let key = PK.parse::<LocalWallet>().unwrap().address();
+
+        let tx = Operator::<IzarTestnet>::execute(&op, transfer()).await.unwrap();
+        let hash = H256::from_str(tx.to_chain_tx_hash.as_deref().unwrap()).unwrap();
+        assert_eq!(evm.sent(), vec![hash]);
+        let sent = evm.transaction(hash).unwrap();
+        assert_eq!((sent.from, sent.to), (key, Some(Sepolia::LOGIC_CONTRACT.parse().unwrap())));
+        assert_eq!((sent.nonce, sent.gas), (U256::zero(), U256::from(Sepolia::GAS_LIMIT)));
+
+        // the receipt is polled until the execution is mined
+        let provider = Provider::<Http>::try_from(evm.url()).unwrap().interval(Duration::from_millis(10));
+        let pending = IzarPendingTransaction::<IzarTestnet>::eth(hash, vec![], provider, 0);
+        let checking = tokio::spawn(pending.checking());
+        tokio::time::sleep(Duration::from_millis(100)).await;
+        evm.mine(1);
+        let (status, fee) = checking.await.unwrap();
+        assert_eq!(status, TransactionStatus::Success(Sepolia::format_str(hash)));
+        assert!(fee.is_some());
+
+        // a simulation that reverts is never sent
+        evm.revert_calls(Some("nonce already used"));
+        let err = Operator::<IzarTestnet>::execute(&op, transfer()).await.unwrap_err();
+        assert!(
+            matches!(err.downcast_ref::<ExecuteError>(), Some(ExecuteError::Reverted(r)) if r == "nonce already used")
+        );
+        assert_eq!(evm.sent().len(), 1);
+        evm.revert_calls(None);
+
+        // the key was used elsewhere, the nonce is synced again and the execution resent
+        evm.set_nonce(key, U256::from(5));
+        Operator::<IzarTestnet>::execute(&op, transfer()).await.unwrap();
+        let sent = evm.sent();
+        assert_eq!(sent.len(), 2);
+        assert_eq!(evm.transaction(sent[1]).unwrap().nonce, U256::from(5));
+
+        // a failed receipt is a rejection
+        evm.revert_onchain(1);
+        evm.mine(1);
+        let provider = Provider::<Http>::try_from(evm.url()).unwrap().interval(Duration::from_millis(10));
+        let (status, _) = IzarPendingTransaction::<IzarTestnet>::eth(sent[1], vec![], provider, 0).checking().await;
+        assert!(matches!(status, TransactionStatus::Rejected(_)));
+
+        evm.fail("eth_gasPrice", RpcError::new(-32603, "node down"));
+        assert!(Operator::<IzarTestnet>::execute(&op, transfer()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_received() {
+        let evm = MockEvm::start(Sepolia::ETH_CHAIN_ID as u64, Sepolia::START_HEIGHT + 100).await.unwrap();
+        let db = tempfile::tempdir().unwrap();
+        let op = EthOperator::<Sepolia>::new(vec![PK.to_string()], evm.url(), db.path()).unwrap();
+
+        // nothing tells a landed execution apart without the event
+        assert!(Operator::<IzarTestnet>::received(&op, &transfer()).await.is_err());
+
+        let op = op.with_receive_event(RECEIVE_EVENT);
+        let received = Operator::<IzarTestnet>::received(&op, &transfer()).await.unwrap();
+        assert_eq!(received, ReceiveState::NotReceived);
+
+        // the transfer is `transfer()` from chain 2 with nonce 7, only its own event counts
+        let log = |chain: u64, nonce: u64, hash: H256, removed: bool| Log {
+            address: Sepolia::LOGIC_CONTRACT.parse().unwrap(),
+            topics: vec![H256(keccak256(RECEIVE_EVENT)), H256::from_low_u64_be(chain), H256::from_low_u64_be(nonce)],
+            block_number: Some(U64::from(Sepolia::START_HEIGHT + 90)),
+            transaction_hash: Some(hash),
+            removed: Some(removed),
+            ..Default::default()
+        };
+        evm.push_logs([
+            log(2, 8, H256::from_low_u64_be(1), false),
+            log(3, 7, H256::from_low_u64_be(2), false),
+            log(2, 7, H256::from_low_u64_be(3), true),
+        ]);
+        let received = Operator::<IzarTestnet>::received(&op, &transfer()).await.unwrap();
+        assert_eq!(received, ReceiveState::NotReceived);
+
+        let landed = H256::from_low_u64_be(4);
+        evm.push_logs([log(2, 7, landed, false)]);
+        let received = Operator::<IzarTestnet>::received(&op, &transfer()).await.unwrap();
+        assert_eq!(received, ReceiveState::Received(Some(Sepolia::format_str(landed))));
+
+        // a node that can't answer leaves the transfer waiting
+        evm.fail("eth_getLogs", RpcError::new(-32603, "node down"));
+        assert!(Operator::<IzarTestnet>::received(&op, &transfer()).await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_bump() {
+        let evm = MockEvm::start(Sepolia::ETH_CHAIN_ID as u64, 100).await.unwrap();
+        let db = tempfile::tempdir().unwrap();
+        let op = EthOperator::<Sepolia>::new(vec![PK.to_string()], evm.url(), db.path()).unwrap();
+
+        let tx = Operator::<IzarTestnet>::execute(&op, transfer()).await.unwrap();
+        let first = H256::from_str(tx.to_chain_tx_hash.as_deref().unwrap()).unwrap();
+        let sent = evm.transaction(first).unwrap();
+        let bumped = Operator::<IzarTestnet>::bump(&op, &tx).await.unwrap().unwrap();
+        let second = H256::from_str(bumped.to_chain_tx_hash.as_deref().unwrap()).unwrap();
+
+        // the same call at the same nonce, paying what nodes take as a replacement
+        let replacement = evm.transaction(second).unwrap();
+        assert!(evm.transaction(first).is_none());
+        assert_eq!((replacement.nonce, &replacement.input, replacement.gas), (sent.nonce, &sent.input, sent.gas));
+        let fees = |t: &Transaction| [t.max_fee_per_gas, t.max_priority_fee_per_gas, t.gas_price];
+        for (new, old) in fees(&replacement).into_iter().zip(fees(&sent)) {
+            if let (Some(new), Some(old)) = (new, old) {
+                assert!(new * 10 >= old * 11, "{new} is not 10% over {old}");
+            }
+        }
+        assert_eq!(op.replaced.get(&tx.from_chain_tx_hash).unwrap(), Some(vec![Sepolia::format_str(first)]));
+
+        // both replaced executions are watched
+        let third = Operator::<IzarTestnet>::bump(&op, &bumped).await.unwrap().unwrap();
+        let replaced = vec![Sepolia::format_str(first), Sepolia::format_str(second)];
+        assert_eq!(op.replaced.get(&tx.from_chain_tx_hash).unwrap(), Some(replaced));
+
+        // a mined execution is not replaced
+        evm.mine(1);
+        assert!(Operator::<IzarTestnet>::bump(&op, &third).await.unwrap().is_none());
+        assert_eq!(evm.sent().len(), 3);
+    }
+
+    #[tokio::test]
+    async fn test_landed_replacement() {
+        let evm = MockEvm::start(Sepolia::ETH_CHAIN_ID as u64, 100).await.unwrap();
+        let provider = Provider::<Http>::try_from(evm.url()).unwrap().interval(Duration::from_millis(10));
+        let (dropped, replaced, landed) =
+            (H256::from_low_u64_be(3), H256::from_low_u64_be(1), H256::from_low_u64_be(2));
+        evm.push_receipt(TransactionReceipt {
+            transaction_hash: landed,
+            block_number: Some(U64::from(100)),
+            status: Some(U64::one()),
+            gas_used: Some(U256::from(21000)),
+            effective_gas_price: Some(U256::from(2)),
+            ..Default::default()
+        });
+
+        // the execution a bump replaced landed, so the one watched was dropped
+        let pending = IzarPendingTransaction::<IzarTestnet>::eth(dropped, vec![replaced, landed], provider.clone(), 0);
+        let (status, fee) = pending.checking().await;
+        assert_eq!(status, TransactionStatus::Success(Sepolia::format_str(landed)));
+        assert_eq!(fee, Some("42000".to_string()));
+
+        // none of them landed
+        let pending = IzarPendingTransaction::<IzarTestnet>::eth(dropped, vec![replaced], provider, 0);
+        assert!(matches!(pending.checking().await, (TransactionStatus::NotBroadcasted(_), None)));
+    }
+
+    #[tokio::test]
+    async fn test_bump_settle_race() {
+        let evm = MockEvm::start(Sepolia::ETH_CHAIN_ID as u64, 100).await.unwrap();
+        let db = tempfile::tempdir().unwrap();
+        let mut op = EthOperator::<Sepolia>::new(vec![PK.to_string()], evm.url(), db.path()).unwrap();
+        op.provider = op.provider.clone().interval(Duration::from_millis(10));
+        let sequencer = SequencerClient::new(vec!["http://127.0.0.1:1".to_string()], Duration::from_secs(1)).unwrap();
+        let mut izar = IzarRelayer::<IzarTestnet>::new(0, sequencer, db.path());
+        izar.insert_operator(Sepolia::IZAR_CHAIN_ID, Box::new(op.clone()));
+        let izar = Arc::new(izar);
+
+        let hash = transfer().from_chain_tx_hash;
+        izar.store().enqueue(transfer()).unwrap();
+        assert!(izar.store().start(&transfer()).unwrap());
+        let sent = Operator::<IzarTestnet>::execute(&op, transfer()).await.unwrap();
+        let tid = sent.order_key();
+        izar.store().submitted(tid.clone(), sent.clone()).unwrap();
+
+        // the replacement reaches the node but its answer is late
+        evm.delay("eth_sendRawTransaction", Some(Duration::from_millis(500)));
+        let speedup = tokio::spawn({
+            let izar = izar.clone();
+            async move { izar.speedup(transfer()).await }
+        });
+        tokio::time::sleep(Duration::from_millis(200)).await;
+
+        // the checker sees the first execution dropped, it must not settle it while the bump is out
+        izar.check_pending(&tid, sent.clone()).await.unwrap();
+        assert_eq!(izar.store().state(&hash).unwrap(), Some(TxState::Pending));
+        // nor can another speedup bump it
+        assert_eq!(izar.speedup(transfer()).await.unwrap_err().status, StatusCode::CONFLICT);
+
+        let (_, sped_up) = speedup.await.unwrap().unwrap();
+        assert_eq!(sped_up.action, SpeedupAction::Bumped);
+        evm.delay("eth_sendRawTransaction", None);
+        evm.mine(1);
+
+        let pending = izar.store().pending().get(&tid).unwrap().unwrap();
+        assert_eq!(pending.to_chain_tx_hash, sped_up.to_chain_tx_hash);
+        izar.check_pending(&tid, pending).await.unwrap();
+        let finalized = izar.store().finalize().get(&hash).unwrap().unwrap();
+        assert_eq!(finalized.to_chain_tx_hash, sped_up.to_chain_tx_hash);
+        assert!(op.replaced.get(&hash).unwrap().is_none());
     }
 }
@@ -0,0 +1,115 @@
+use std::sync::{
+    atomic::{AtomicUsize, Ordering},
+    Arc,
+};
+
+use ethers::{
+    middleware::NonceManagerMiddleware,
+    prelude::SignerMiddleware,
+    providers::{Http, Middleware, Provider},
+    signers::{LocalWallet, Signer},
+    types::{Address, U256},
+};
+
+use crate::relayer::types::KeyBalance;
+
+/// Every key tracks its own nonces, so executions sent from different keys never wait on each other.
+pub type EthClient = NonceManagerMiddleware<SignerMiddleware<Provider<Http>, LocalWallet>>;
+
+pub struct WalletPool {
+    chain_id: u32,
+    wallets: Vec<Arc<EthClient>>,
+    next: AtomicUsize,
+}
+
+impl WalletPool {
+    pub fn new(pks: &[String], provider: Provider<Http>, eth_chain_id: u32, chain_id: u32) -> anyhow::Result<Self> {
+        if pks.is_empty() {
+            anyhow::bail!("no private key for chain {chain_id}");
+        }
+        let wallets = pks
+            .iter()
+            .map(|pk| {
+                let wallet = pk.parse::<LocalWallet>()?.with_chain_id(eth_chain_id);
+                let address = wallet.address();
+                let signer = SignerMiddleware::new(provider.clone(), wallet);
+                Ok(Arc::new(NonceManagerMiddleware::new(signer, address)))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        Ok(Self { chain_id, wallets, next: AtomicUsize::new(0) })
+    }
+
+    /// Used for reads and simulations that don't depend on the sender.
+    pub fn primary(&self) -> Arc<EthClient> {
+        self.wallets[0].clone()
+    }
+
+    pub fn by_address(&self, address: Address) -> Option<Arc<EthClient>> {
+        self.wallets.iter().find(|w| w.inner().address() == address).cloned()
+    }
+
+    /// Takes the keys in turn, skipping those whose balance can't pay `needed`.
+    pub async fn pick(&self, needed: U256) -> anyhow::Result<Arc<EthClient>> {
+        let start = self.next.fetch_add(1, Ordering::SeqCst);
+        for i in 0..self.wallets.len() {
+            let wallet = &self.wallets[(start + i) % self.wallets.len()];
+            let address = wallet.inner().address();
+            let balance = wallet.get_balance(address, None).await?;
+            if balance >= needed {
+                return Ok(wallet.clone());
+            }
+            tracing::warn!("key {:?} on chain {} underfunded: {} < {}", address, self.chain_id, balance, needed);
+        }
+        anyhow::bail!("no key on chain {} can pay {}", self.chain_id, needed)
+    }
+
+    pub async fn balances(&self) -> anyhow::Result<Vec<KeyBalance>> {
+        let mut balances = Vec::with_capacity(self.wallets.len());
+        for wallet in self.wallets.iter() {
+            let address = wallet.inner().address();
+            let balance = wallet.get_balance(address, None).await?;
+            balances.push(KeyBalance {
+                address: format!("{:?}", address),
+                balance: u128::try_from(balance).unwrap_or(u128::MAX),
+            });
+        }
+        Ok(balances)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use ethers::{
+        providers::{Http, Provider},
+        signers::{LocalWallet, Signer},
+        types::U256,
+    };
+    use izar_testkit::evm::MockEvm;
+
+    use super::WalletPool;
+
+    #[tokio::test]
+    async fn test_pick() {
+        let evm = MockEvm::start(1, 1).await.unwrap();
+        let provider = Provider::<Http>::try_from(evm.url()).unwrap();
+        let pks = (1..=3).map(|i| format!("{i:064x}")).collect::<Vec<_>>();
+        let keys = pks.iter().map(|pk| pk.parse::<LocalWallet>().unwrap().address()).collect::<Vec<_>>();
+        let pool = WalletPool::new(&pks, provider, 1, 2).unwrap();
+        let needed = U256::from(1000);
+
+        // keys take turns
+        for i in 0..6 {
+            assert_eq!(pool.pick(needed).await.unwrap().inner().address(), keys[i % 3]);
+        }
+
+        // an underfunded key is skipped, the turns go on after it
+        evm.set_balance(keys[1], U256::from(999));
+        let picked = [pool.pick(needed).await.unwrap(), pool.pick(needed).await.unwrap()];
+        assert_eq!(picked.map(|w| w.inner().address()), [keys[0], keys[2]]);
+
+        evm.set_balance(keys[0], U256::zero());
+        evm.set_balance(keys[2], U256::zero());
+        assert!(pool.pick(needed).await.unwrap_err().to_string().contains("no key on chain 2"));
+    }
+}
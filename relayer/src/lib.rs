@@ -2,7 +2,10 @@ use std::collections::HashMap;
 
 use async_trait::async_trait;
 use izar_core::{network::IzarNetwork, types::transaction::IzarTransaction};
-use relayer::types::IzarPendingTransaction;
+use relayer::{
+    profit::Cost,
+    types::{IzarPendingTransaction, KeyBalance, ReceiveState, TransactionStatus},
+};
 
 pub mod aleo;
 pub mod eth;
@@ -13,7 +16,38 @@ pub const DB_PATH: &str = ".izar-relayer";
 #[async_trait]
 pub trait Operator<I: IzarNetwork>: Send + Sync {
     async fn execute(&self, tx: IzarTransaction) -> anyhow::Result<IzarTransaction>;
+    /// Checks `tx` is well formed for this chain and its certificates sign it, before it is queued.
+    fn validate(&self, _tx: &IzarTransaction) -> anyhow::Result<()> {
+        Ok(())
+    }
     fn pending(&self, tx: IzarTransaction) -> anyhow::Result<IzarPendingTransaction<I>>;
+    /// Asks the destination chain whether `tx` was already received, regardless of the local store.
+    async fn received(&self, tx: &IzarTransaction) -> anyhow::Result<ReceiveState>;
+    /// Transfers to this chain still waiting in the execute queue, told before each execution on it.
+    fn set_backlog(&self, _queued: usize) {}
+    /// Called once the outcome of an executed `tx` is known, frees whatever the execution was holding.
+    fn settle(&self, _tx: &IzarTransaction, _status: &TransactionStatus) -> anyhow::Result<()> {
+        Ok(())
+    }
+    /// Called when `tx` is withdrawn before its execution finished.
+    fn cancel(&self, _tx: &IzarTransaction) {}
+    /// Replaces the pending execution of `tx` with one paying a higher fee, returns `tx` with the new
+    /// destination hash. `None` when executions on this chain cannot be replaced or this one already landed.
+    async fn bump(&self, _tx: &IzarTransaction) -> anyhow::Result<Option<IzarTransaction>> {
+        Ok(None)
+    }
+    /// What executing `tx` would spend on this chain, `None` when the operator has no cost model.
+    async fn estimate_cost(&self, _tx: &IzarTransaction) -> anyhow::Result<Option<Cost>> {
+        Ok(None)
+    }
+    /// Balance of every key the operator executes with.
+    async fn balances(&self) -> anyhow::Result<Vec<KeyBalance>> {
+        Ok(vec![])
+    }
+    /// Most a single execution can spend, in the unit of `balances`. A chain is paused while no key covers it.
+    async fn execution_budget(&self) -> anyhow::Result<Option<u128>> {
+        Ok(None)
+    }
 }
 
 pub type Operators<I> = HashMap<u32, Box<dyn Operator<I>>>;
@@ -1,9 +1,26 @@
-use std::{str::FromStr, time::Duration};
+use std::{collections::HashMap, str::FromStr, sync::Arc, time::Duration};
 
 use aleo_rust::Network;
 use clap::Parser;
-use izar_core::network::{aleo::AleoNetworkExt, eth::EthNetwork, testnet::IzarTestnet, IzarNetwork};
-use izar_relayer::{aleo::connector::AleoOperator, eth::connector::EthOperator, relayer::IzarRelayer};
+use izar_core::{
+    metrics::relayer::RELAYER_BALANCE,
+    monitor::{deserialize_thresholds, Alerter, BalanceMonitor},
+    network::{aleo::AleoNetworkExt, eth::EthNetwork, testnet::IzarTestnet, IzarNetwork},
+    sequencer::SequencerClient,
+};
+use izar_relayer::{
+    aleo::{connector::AleoOperator, fee::PriorityFeePolicy},
+    eth::connector::EthOperator,
+    relayer::{
+        profit::{Profitability, RoutePolicy, StaticPrices},
+        pull::PullConfig,
+        rest::RestConfig,
+        scheduler::Scheduler,
+        shard::{Shard, ShardConfig},
+        IzarRelayer,
+    },
+    DB_PATH,
+};
 use serde::Deserialize;
 
 #[derive(Debug, Parser)]
@@ -18,8 +35,21 @@ pub struct Cli {
 #[derive(Debug, Deserialize)]
 pub struct RelayerConfig {
     pub api_dest: String,
+    /// sequencers to fail over to when `api_dest` is down
+    #[serde(default)]
+    pub api_dests: Vec<String>,
+    /// seconds before a sequencer request times out, 30 by default
+    pub api_timeout: Option<u64>,
     pub port: u16,
     pub metrics: String,
+    /// directory of the relayer store, `.izar-relayer` by default
+    pub db_path: Option<String>,
+    pub rest: Option<RestConfig>,
+    pub profit: Option<ProfitConfig>,
+    pub scheduler: Option<SchedulerConfig>,
+    pub shard: Option<ShardConfig>,
+    pub pull: Option<PullConfig>,
+    pub monitor: Option<MonitorConfig>,
     // nodes configs
     pub aleo_config: AleoConfig,
     pub sepolia_config: Option<EthConfig>,
@@ -34,26 +64,111 @@ pub struct RelayerConfig {
 #[derive(Debug, Deserialize)]
 pub struct AleoConfig {
     pk: String,
+    #[serde(default)]
+    pks: Vec<String>,
     dest: Option<String>,
     from_height: Option<u32>,
+    provers: Option<usize>,
+    prover_queue: Option<usize>,
+    delegated_prover: Option<DelegatedProverConfig>,
+    priority_fee: Option<PriorityFeePolicy>,
+}
+
+#[derive(Debug, Deserialize)]
+pub struct DelegatedProverConfig {
+    url: String,
+    timeout: Option<u64>,
+    fallback: Option<bool>,
 }
 
 impl AleoConfig {
-    pub fn parse<N: Network>(self) -> AleoOperator<N> {
-        let pk: aleo_rust::PrivateKey<N> = aleo_rust::PrivateKey::<N>::from_str(&self.pk).expect("parse pk");
-        AleoOperator::<N>::new(self.dest, pk, self.from_height).expect("init aleo")
+    pub fn parse<N: Network>(self, db_path: &str) -> AleoOperator<N> {
+        let pks = std::iter::once(&self.pk)
+            .chain(self.pks.iter())
+            .map(|pk| aleo_rust::PrivateKey::<N>::from_str(pk).expect("parse pk"))
+            .collect();
+        let (provers, prover_queue) = (self.provers.unwrap_or(1), self.prover_queue.unwrap_or(16));
+        let op = AleoOperator::<N>::new(self.dest, pks, self.from_height, provers, prover_queue, db_path)
+            .expect("init aleo");
+        let op = match self.delegated_prover {
+            Some(DelegatedProverConfig { url, timeout, fallback }) => {
+                let timeout = Duration::from_secs(timeout.unwrap_or(300));
+                op.with_delegated_prover(url, timeout, fallback.unwrap_or(true)).expect("init delegated prover")
+            }
+            None => op,
+        };
+        let op = match self.priority_fee {
+            Some(policy) => op.with_priority_fee_policy(policy),
+            None => op,
+        };
+        op.initial()
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct MonitorConfig {
+    webhook: Option<String>,
+    interval: Option<u64>,
+    // izar chain id to the lowest balance before alerting, in the smallest unit of the chain
+    #[serde(default, deserialize_with = "deserialize_thresholds")]
+    thresholds: HashMap<u32, u128>,
+}
+
+impl MonitorConfig {
+    pub fn parse(self) -> (BalanceMonitor, Duration) {
+        let monitor = BalanceMonitor::new(RELAYER_BALANCE, Alerter::new(self.webhook), self.thresholds);
+        (monitor, Duration::from_secs(self.interval.unwrap_or(60)))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct ProfitConfig {
+    prices: String,
+    defer_secs: Option<u64>,
+    #[serde(default)]
+    routes: Vec<RoutePolicy>,
+}
+
+impl ProfitConfig {
+    pub fn parse(self) -> Profitability {
+        let prices = StaticPrices::load(&self.prices).expect("load prices");
+        Profitability::new(Arc::new(prices), self.routes, self.defer_secs.unwrap_or(300))
+    }
+}
+
+#[derive(Debug, Deserialize)]
+pub struct SchedulerConfig {
+    aging_secs: Option<u64>,
+    // izar chain id of a source chain to its share of executions, 1 for chains not listed
+    #[serde(default)]
+    weights: HashMap<String, u32>,
+}
+
+impl SchedulerConfig {
+    pub fn parse(self) -> Scheduler {
+        let weights = self
+            .weights
+            .into_iter()
+            .map(|(chain, w)| (chain.parse::<u32>().expect("parse weight chain id"), w))
+            .collect();
+        Scheduler::new(self.aging_secs, weights)
     }
 }
 
 #[derive(Debug, Deserialize)]
 pub struct EthConfig {
     pk: String,
+    #[serde(default)]
+    pks: Vec<String>,
     dest: String,
+    // signature of the event the logic contract emits for a received payload
+    receive_event: String,
 }
 
 impl EthConfig {
-    pub fn parse<E: EthNetwork>(self) -> EthOperator<E> {
-        EthOperator::<E>::new(self.pk, self.dest).expect("eth init")
+    pub fn parse<E: EthNetwork>(self, db_path: &str) -> EthOperator<E> {
+        let pks = std::iter::once(self.pk).chain(self.pks).collect();
+        EthOperator::<E>::new(pks, self.dest, db_path).expect("eth init").with_receive_event(&self.receive_event)
     }
 }
 
@@ -81,51 +196,73 @@ async fn main() {
 async fn init<I: IzarNetwork>(config: RelayerConfig) {
     // init izar operator
     let port = config.port;
-    let api_dest = format!("{}/api/v1/BridgeTx", config.api_dest);
-    let mut operators = IzarRelayer::<I>::new(port, api_dest);
+    let db_path = config.db_path.unwrap_or(DB_PATH.to_string());
+    let urls = std::iter::once(config.api_dest).chain(config.api_dests).collect();
+    let timeout = Duration::from_secs(config.api_timeout.unwrap_or(30));
+    let sequencer = SequencerClient::new(urls, timeout).expect("init sequencer client");
+    let mut operators = IzarRelayer::<I>::new(port, sequencer, &db_path);
+    operators.set_rest(config.rest.unwrap_or_default()).expect("init rest");
+    if let Some(profit) = config.profit {
+        operators.set_profitability(profit.parse());
+    }
+    if let Some(scheduler) = config.scheduler {
+        operators.set_scheduler(scheduler.parse());
+    }
+    if let Some(shard) = config.shard {
+        let shard = Shard::new(shard).expect("init shard");
+        tracing::info!("relayer {} shares transfers with its set", shard.me());
+        operators.set_shard(shard);
+    }
+    if let Some(pull) = config.pull {
+        operators.set_pull(pull).expect("init pull");
+    }
+    if let Some(monitor) = config.monitor {
+        let (monitor, interval) = monitor.parse();
+        operators.set_monitor(monitor, interval);
+    }
 
     // init aleo operator
-    let aleo_op = config.aleo_config.parse::<I::Aleo>();
+    let aleo_op = config.aleo_config.parse::<I::Aleo>(&db_path);
     operators.insert_operator(I::Aleo::IZAR_CHAIN_ID, Box::new(aleo_op));
 
     // init sepolia operator
     if let Some(config) = config.sepolia_config {
-        operators.insert_operator(I::Sepolia::IZAR_CHAIN_ID, Box::new(config.parse::<I::Sepolia>()));
+        operators.insert_operator(I::Sepolia::IZAR_CHAIN_ID, Box::new(config.parse::<I::Sepolia>(&db_path)));
     }
 
     // init linea operator
     if let Some(config) = config.linea_config {
-        operators.insert_operator(I::Linea::IZAR_CHAIN_ID, Box::new(config.parse::<I::Linea>()));
+        operators.insert_operator(I::Linea::IZAR_CHAIN_ID, Box::new(config.parse::<I::Linea>(&db_path)));
     }
 
     // init goerli operator
     if let Some(config) = config.goerli_config {
-        operators.insert_operator(I::Goerli::IZAR_CHAIN_ID, Box::new(config.parse::<I::Goerli>()));
+        operators.insert_operator(I::Goerli::IZAR_CHAIN_ID, Box::new(config.parse::<I::Goerli>(&db_path)));
     }
 
     // init zksync operator
     if let Some(config) = config.zksync_config {
-        operators.insert_operator(I::Zksync::IZAR_CHAIN_ID, Box::new(config.parse::<I::Zksync>()));
+        operators.insert_operator(I::Zksync::IZAR_CHAIN_ID, Box::new(config.parse::<I::Zksync>(&db_path)));
     }
 
     // init scroll operator
     if let Some(config) = config.scroll_config {
-        operators.insert_operator(I::Scroll::IZAR_CHAIN_ID, Box::new(config.parse::<I::Scroll>()));
+        operators.insert_operator(I::Scroll::IZAR_CHAIN_ID, Box::new(config.parse::<I::Scroll>(&db_path)));
     }
 
     // init optimism operator
     if let Some(config) = config.optimism_config {
-        operators.insert_operator(I::Optimism::IZAR_CHAIN_ID, Box::new(config.parse::<I::Optimism>()));
+        operators.insert_operator(I::Optimism::IZAR_CHAIN_ID, Box::new(config.parse::<I::Optimism>(&db_path)));
     }
 
     // init arbitrum operator
     if let Some(config) = config.arbitrum_config {
-        operators.insert_operator(I::Arbitrum::IZAR_CHAIN_ID, Box::new(config.parse::<I::Arbitrum>()));
+        operators.insert_operator(I::Arbitrum::IZAR_CHAIN_ID, Box::new(config.parse::<I::Arbitrum>(&db_path)));
     }
 
     // init taiko operator
     if let Some(config) = config.taiko_config {
-        operators.insert_operator(I::Taiko::IZAR_CHAIN_ID, Box::new(config.parse::<I::Taiko>()));
+        operators.insert_operator(I::Taiko::IZAR_CHAIN_ID, Box::new(config.parse::<I::Taiko>(&db_path)));
     }
 
     // init operators
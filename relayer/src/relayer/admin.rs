@@ -0,0 +1,132 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{rejection::JsonRejection, Path, State},
+    Json,
+};
+use izar_core::network::IzarNetwork;
+use serde::Deserialize;
+use utoipa::ToSchema;
+
+use super::{
+    ingest::ApiError,
+    query::TxView,
+    store::{Located, TxState},
+    types::TransactionStatus,
+    IzarRelayer,
+};
+
+#[derive(Debug, Deserialize, ToSchema)]
+pub struct FinalizeRequest {
+    /// destination chain tx hash that settled the transfer
+    pub to_chain_tx_hash: String,
+}
+
+impl<I: IzarNetwork> IzarRelayer<I> {
+    fn locate_waiting(&self, hash: &String, action: &str) -> Result<Located, ApiError> {
+        let located =
+            self.store().locate(hash).map_err(ApiError::internal)?.ok_or_else(|| ApiError::not_found(hash))?;
+        match located.state {
+            TxState::Executing | TxState::Finalized => {
+                Err(ApiError::conflict(format!("tx {hash} is {:?}, it cannot be {action}", located.state)))
+            }
+            _ => Ok(located),
+        }
+    }
+
+    fn view(&self, hash: String) -> Result<TxView, ApiError> {
+        TxView::load(self, hash)
+    }
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/tx/{hash}/requeue",
+    params(("hash" = String, Path, description = "source chain tx hash")),
+    responses(
+        (status = 200, description = "the transfer is back in the execute queue", body = TxView),
+        (status = 404, description = "unknown transfer", body = ApiError),
+        (status = 409, description = "only deferred and flagged transfers can be requeued", body = ApiError),
+    )
+)]
+pub async fn requeue<I: IzarNetwork>(
+    State(izar): State<Arc<IzarRelayer<I>>>,
+    Path(hash): Path<String>,
+) -> Result<Json<TxView>, ApiError> {
+    {
+        let _guard = izar.writes.lock().unwrap_or_else(|e| e.into_inner());
+        let located = izar.locate_waiting(&hash, "requeued")?;
+        let tx = match (located.state, located.tx) {
+            (TxState::Deferred | TxState::Flagged, Some(tx)) => tx,
+            (state, _) => return Err(ApiError::conflict(format!("tx {hash} is {state:?}, it cannot be requeued"))),
+        };
+        izar.store().requeue(tx).map_err(ApiError::internal)?;
+    }
+    tracing::warn!("admin requeued tx {hash}");
+    Ok(Json(izar.view(hash)?))
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/tx/{hash}/cancel",
+    params(("hash" = String, Path, description = "source chain tx hash")),
+    responses(
+        (status = 200, description = "the transfer was removed, it is accepted again when resubmitted"),
+        (status = 404, description = "unknown transfer", body = ApiError),
+        (status = 409, description = "finalized transfers cannot be cancelled", body = ApiError),
+    )
+)]
+pub async fn cancel<I: IzarNetwork>(
+    State(izar): State<Arc<IzarRelayer<I>>>,
+    Path(hash): Path<String>,
+) -> Result<(), ApiError> {
+    let located = {
+        let _guard = izar.writes.lock().unwrap_or_else(|e| e.into_inner());
+        let located =
+            izar.store().locate(&hash).map_err(ApiError::internal)?.ok_or_else(|| ApiError::not_found(&hash))?;
+        if located.state == TxState::Finalized {
+            return Err(ApiError::conflict(format!("tx {hash} is Finalized, it cannot be cancelled")));
+        }
+        izar.store().withdraw(&hash, &located).map_err(ApiError::internal)?;
+        located
+    };
+    // an executing transfer has its proof dropped, the executor leaves it alone once the execution returns. A pending
+    // one may still land, the operator only stops tracking it
+    if let (TxState::Executing | TxState::Pending, Some(tx)) = (located.state, located.tx.as_ref()) {
+        if let Some(op) = izar.operators.get(&tx.to_chain_id) {
+            op.cancel(tx);
+        }
+    }
+    tracing::warn!("admin cancelled tx {hash} while {:?}", located.state);
+    Ok(())
+}
+
+#[utoipa::path(
+    post,
+    path = "/admin/tx/{hash}/finalize",
+    params(("hash" = String, Path, description = "source chain tx hash")),
+    request_body = FinalizeRequest,
+    responses(
+        (status = 200, description = "the transfer is finalized and the sequencer told so", body = TxView),
+        (status = 404, description = "unknown transfer", body = ApiError),
+        (status = 409, description = "executing and finalized transfers cannot be finalized", body = ApiError),
+    )
+)]
+pub async fn finalize<I: IzarNetwork>(
+    State(izar): State<Arc<IzarRelayer<I>>>,
+    Path(hash): Path<String>,
+    req: Result<Json<FinalizeRequest>, JsonRejection>,
+) -> Result<Json<TxView>, ApiError> {
+    let Json(req) = req.map_err(|e| ApiError::malformed(e.body_text()))?;
+    {
+        let _guard = izar.writes.lock().unwrap_or_else(|e| e.into_inner());
+        let located = izar.locate_waiting(&hash, "finalized")?;
+        let mut tx = located.tx.clone().ok_or_else(|| ApiError::conflict(format!("tx {hash} has no stored body")))?;
+        tx.to_chain_tx_hash = Some(req.to_chain_tx_hash.clone());
+        let status = TransactionStatus::Success(req.to_chain_tx_hash.clone());
+        izar.patch_result_with(hash.clone(), status, |batch| izar.store().force_finish_append(&located, tx, batch))
+            .map_err(ApiError::internal)?;
+    }
+    tracing::warn!("admin finalized tx {hash} as {}", req.to_chain_tx_hash);
+    Ok(Json(izar.view(hash)?))
+}
@@ -0,0 +1,259 @@
+use std::{
+    collections::HashMap,
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+};
+
+use axum::{
+    body::Body,
+    extract::{ConnectInfo, Request, State},
+    http::{HeaderMap, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use hmac::{Hmac, Mac};
+use serde::Deserialize;
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+
+pub const KEY_HEADER: &str = "x-izar-key";
+pub const TIMESTAMP_HEADER: &str = "x-izar-timestamp";
+pub const SIGNATURE_HEADER: &str = "x-izar-signature";
+
+const MAX_BODY: usize = 1 << 20;
+
+/// Callers are named, so the logs tell which of them pushed a request.
+#[derive(Clone, Default, Deserialize)]
+pub struct AuthConfig {
+    /// caller name to the token it sends as `Authorization: Bearer <token>`
+    #[serde(default)]
+    pub tokens: HashMap<String, String>,
+    /// caller name to the secret it signs requests with, the name goes in `x-izar-key`
+    #[serde(default)]
+    pub hmac_keys: HashMap<String, String>,
+    /// how far `x-izar-timestamp` may be from the relayer clock, in seconds
+    pub max_skew: Option<u64>,
+    /// callers allowed on the `/admin` routes
+    #[serde(default)]
+    pub admins: Vec<String>,
+    /// accept every caller when no tokens or hmac keys are set, the relayer refuses to start without them otherwise
+    #[serde(default)]
+    pub insecure: bool,
+}
+
+impl AuthConfig {
+    pub fn check(&self) -> anyhow::Result<()> {
+        if self.tokens.is_empty() && self.hmac_keys.is_empty() && !self.insecure {
+            anyhow::bail!("rest.auth has no tokens or hmac keys, set `insecure = true` to accept every caller");
+        }
+        Ok(())
+    }
+}
+
+// the relayer logs its config on start, only caller names are shown
+impl std::fmt::Debug for AuthConfig {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("AuthConfig")
+            .field("tokens", &self.tokens.keys().collect::<Vec<_>>())
+            .field("hmac_keys", &self.hmac_keys.keys().collect::<Vec<_>>())
+            .field("max_skew", &self.max_skew)
+            .field("admins", &self.admins)
+            .field("insecure", &self.insecure)
+            .finish()
+    }
+}
+
+pub struct Auth {
+    config: AuthConfig,
+    // hmac key and signature to the timestamp they came with, a signature is only accepted once
+    seen: Mutex<HashMap<(String, Vec<u8>), u64>>,
+}
+
+impl Auth {
+    pub fn new(config: AuthConfig) -> Self {
+        Self { config, seen: Mutex::new(HashMap::new()) }
+    }
+
+    pub fn is_open(&self) -> bool {
+        self.config.tokens.is_empty() && self.config.hmac_keys.is_empty()
+    }
+
+    /// Only the callers named in `admins`, an insecure server has none.
+    pub fn is_admin(&self, caller: &str) -> bool {
+        !self.is_open() && self.config.admins.iter().any(|a| a == caller)
+    }
+
+    /// Returns the caller name, or why the request is refused.
+    /// A signature covers `{timestamp}\n{method}\n{path}\n` followed by the raw body, as lowercase hex, and is
+    /// refused when it comes again within `max_skew`.
+    pub fn verify(
+        &self,
+        method: &str,
+        path: &str,
+        headers: &HeaderMap,
+        body: &[u8],
+        now: u64,
+    ) -> Result<String, String> {
+        if self.is_open() {
+            return Ok("anonymous".to_string());
+        }
+
+        if let Some(auth) = header(headers, axum::http::header::AUTHORIZATION.as_str()) {
+            let token = auth.strip_prefix("Bearer ").ok_or("unsupported authorization scheme")?;
+            return self
+                .config
+                .tokens
+                .iter()
+                .find(|(_, t)| t.as_bytes().ct_eq(token.as_bytes()).into())
+                .map(|(name, _)| name.clone())
+                .ok_or("unknown token".to_string());
+        }
+
+        let key = header(headers, KEY_HEADER).ok_or("missing credentials")?;
+        let secret = self.config.hmac_keys.get(key).ok_or(format!("unknown key {key}"))?;
+        let timestamp = header(headers, TIMESTAMP_HEADER).ok_or("missing timestamp")?;
+        let signature = header(headers, SIGNATURE_HEADER).ok_or("missing signature")?;
+
+        let ts = timestamp.parse::<u64>().map_err(|_| "invalid timestamp")?;
+        let max_skew = self.config.max_skew.unwrap_or(300);
+        if ts.abs_diff(now) > max_skew {
+            return Err(format!("timestamp {ts} too far from {now}"));
+        }
+        let signature = hex::decode(signature).map_err(|_| "invalid signature encoding")?;
+        let mut mac = Hmac::<Sha256>::new_from_slice(secret.as_bytes()).map_err(|e| e.to_string())?;
+        mac.update(format!("{timestamp}\n{method}\n{path}\n").as_bytes());
+        mac.update(body);
+        mac.verify_slice(&signature).map_err(|_| "bad signature")?;
+
+        let mut seen = self.seen.lock().unwrap_or_else(|e| e.into_inner());
+        // a signature older than the window cannot come back, its timestamp would be refused
+        seen.retain(|_, ts| ts.abs_diff(now) <= max_skew);
+        if seen.insert((key.to_string(), signature), ts).is_some() {
+            return Err("signature replayed".to_string());
+        }
+        Ok(key.to_string())
+    }
+}
+
+fn header<'a>(headers: &'a HeaderMap, name: &str) -> Option<&'a str> {
+    headers.get(name).and_then(|v| v.to_str().ok())
+}
+
+pub async fn authenticate(
+    State(auth): State<Arc<Auth>>,
+    ConnectInfo(remote): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let (parts, body) = request.into_parts();
+    let (method, path) = (parts.method.to_string(), parts.uri.path().to_string());
+    let body = match axum::body::to_bytes(body, MAX_BODY).await {
+        Ok(body) => body,
+        Err(e) => {
+            tracing::warn!(target: "izar_relayer::auth::rejected", %remote, %method, %path, "unreadable body: {}", e);
+            return StatusCode::BAD_REQUEST.into_response();
+        }
+    };
+
+    let now = std::time::SystemTime::now().duration_since(std::time::UNIX_EPOCH).unwrap_or_default().as_secs();
+    match auth.verify(&method, &path, &parts.headers, &body, now) {
+        Ok(caller) if path.starts_with("/admin/") && !auth.is_admin(&caller) => {
+            tracing::warn!(target: "izar_relayer::auth::rejected", %remote, %method, %path, %caller, "not an admin");
+            StatusCode::FORBIDDEN.into_response()
+        }
+        Ok(caller) => {
+            tracing::info!(target: "izar_relayer::auth::accepted", %remote, %method, %path, %caller);
+            next.run(Request::from_parts(parts, Body::from(body))).await
+        }
+        Err(reason) => {
+            tracing::warn!(target: "izar_relayer::auth::rejected", %remote, %method, %path, %reason);
+            StatusCode::UNAUTHORIZED.into_response()
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use axum::http::{HeaderMap, HeaderValue};
+    use hmac::{Hmac, Mac};
+    use sha2::Sha256;
+
+    use super::{Auth, AuthConfig, KEY_HEADER, SIGNATURE_HEADER, TIMESTAMP_HEADER};
+
+    fn auth() -> Auth {
+        Auth::new(AuthConfig {
+            tokens: HashMap::from([("sequencer".to_string(), "token".to_string())]),
+            hmac_keys: HashMap::from([("signer".to_string(), "secret".to_string())]),
+            max_skew: Some(60),
+            admins: vec!["signer".to_string()],
+            insecure: false,
+        })
+    }
+
+    fn signed(ts: u64, body: &[u8]) -> HeaderMap {
+        let mut mac = Hmac::<Sha256>::new_from_slice(b"secret").unwrap();
+        mac.update(format!("{ts}\nPOST\n/exec\n").as_bytes());
+        mac.update(body);
+        let mut headers = HeaderMap::new();
+        headers.insert(KEY_HEADER, HeaderValue::from_static("signer"));
+        headers.insert(TIMESTAMP_HEADER, HeaderValue::from_str(&ts.to_string()).unwrap());
+        headers.insert(SIGNATURE_HEADER, HeaderValue::from_str(&hex::encode(mac.finalize().into_bytes())).unwrap());
+        headers
+    }
+
+    #[test]
+    fn test_bearer_token() {
+        let mut headers = HeaderMap::new();
+        headers.insert("authorization", HeaderValue::from_static("Bearer token"));
+        assert_eq!(auth().verify("POST", "/exec", &headers, b"", 0), Ok("sequencer".to_string()));
+
+        headers.insert("authorization", HeaderValue::from_static("Bearer nope"));
+        assert!(auth().verify("POST", "/exec", &headers, b"", 0).is_err());
+        assert!(auth().verify("POST", "/exec", &HeaderMap::new(), b"", 0).is_err());
+    }
+
+    #[test]
+    fn test_hmac_signature() {
+        let headers = signed(1000, b"{}");
+        assert_eq!(auth().verify("POST", "/exec", &headers, b"{}", 1030), Ok("signer".to_string()));
+        // tampered body, other route, stale timestamp
+        assert!(auth().verify("POST", "/exec", &headers, b"{ }", 1030).is_err());
+        assert!(auth().verify("POST", "/speedup", &headers, b"{}", 1030).is_err());
+        assert!(auth().verify("POST", "/exec", &headers, b"{}", 1100).is_err());
+    }
+
+    #[test]
+    fn test_hmac_replay() {
+        let auth = auth();
+        let headers = signed(1000, b"{}");
+        assert!(auth.verify("POST", "/exec", &headers, b"{}", 1000).is_ok());
+        assert_eq!(auth.verify("POST", "/exec", &headers, b"{}", 1030), Err("signature replayed".to_string()));
+        // a new timestamp signs anew
+        assert!(auth.verify("POST", "/exec", &signed(1001, b"{}"), b"{}", 1030).is_ok());
+        // out of the window the timestamp alone refuses it, the cache forgets it
+        assert!(auth.verify("POST", "/exec", &headers, b"{}", 1100).is_err());
+        assert!(auth.seen.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_open_without_credentials() {
+        assert!(AuthConfig::default().check().is_err());
+        let config = AuthConfig { insecure: true, ..Default::default() };
+        assert!(config.check().is_ok());
+        let auth = Auth::new(config);
+        assert!(auth.verify("POST", "/exec", &HeaderMap::new(), b"", 0).is_ok());
+        assert!(!auth.is_admin("anonymous"));
+        // anonymous callers never reach the admin routes, not even when named
+        let auth =
+            Auth::new(AuthConfig { insecure: true, admins: vec!["anonymous".to_string()], ..Default::default() });
+        assert!(!auth.is_admin("anonymous"));
+    }
+
+    #[test]
+    fn test_admins() {
+        assert!(auth().is_admin("signer"));
+        assert!(!auth().is_admin("sequencer"));
+    }
+}
@@ -1,93 +1,298 @@
 use anyhow::anyhow;
-use izar_core::network::IzarNetwork;
-use std::sync::Arc;
+use izar_core::{network::IzarNetwork, types::transaction::IzarTransaction};
+use std::{
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex},
+};
 
-use crate::relayer::types::{PatchRequest, TransactionStatus};
+use crate::relayer::{
+    profit::Verdict,
+    store::{now, Deferred, TxState},
+    types::{ExecuteError, ReceiveState, TransactionStatus},
+};
 
 use super::IzarRelayer;
 
 impl<I: IzarNetwork> IzarRelayer<I> {
     pub async fn execute_handler(self_: Arc<IzarRelayer<I>>) -> anyhow::Result<()> {
         loop {
-            let tx = match self_.store().execute().iter().next() {
-                Some((_, tx)) => tx.into_owned(),
-                None => {
-                    tracing::warn!("no execute transaction, sleep 15s");
-                    std::thread::sleep(std::time::Duration::from_secs(15));
-                    continue;
+            match self_.execute_once().await {
+                Ok(true) => {}
+                Ok(false) => {
+                    tracing::warn!("no executable transaction, sleep 15s");
+                    tokio::time::sleep(std::time::Duration::from_secs(15)).await;
                 }
-            };
-            let tid = tx.order_key();
-            self_.store().execute().remove(&tid)?;
+                // the store failed, whatever was taken is recovered by the next step
+                Err(e) => {
+                    tracing::error!("execute step failed: {:?}", e);
+                    tokio::time::sleep(std::time::Duration::from_secs(1)).await;
+                }
+            }
+        }
+    }
 
-            // check is already finalize
-            if self_.store().finalize().get(&tx.from_chain_tx_hash)?.is_some() {
-                tracing::warn!("tx already finalized: {}", tx.from_chain_tx_hash);
-                self_.store().execute().remove(&tid)?;
-                continue;
+    /// Takes the next executable transfer through to pending or a final state, false when there is none.
+    pub(crate) async fn execute_once(&self) -> anyhow::Result<bool> {
+        if let Err(e) = self.requeue_deferred() {
+            tracing::error!("failed to requeue deferred txs: {:?}", e);
+        }
+        {
+            // nothing executes between two steps, so a transfer still executing was cut short by a crash or an error
+            let _guard = self.writes.lock().unwrap_or_else(|e| e.into_inner());
+            for hash in self.store().recover()? {
+                tracing::warn!("tx {hash} was cut short while executing, queued again");
             }
+        }
 
-            // execute tx
-            tracing::info!("executing {:?}", tx);
-            let op = self_.operators.get(&tx.to_chain_id);
-            let fut = async move {
-                let op = op.ok_or(anyhow!("no operator for chain id: {}", tx.to_chain_id))?;
-                let pending = op.execute(tx).await?;
-                Ok::<_, anyhow::Error>(pending)
-            };
+        // txs to a paused chain keep their place until it is funded again, those of other relayers until
+        // their owner had its time
+        let now = now();
+        let (backlog, picked) = self.store().queued(|queued| {
+            let mut backlog = HashMap::new();
+            let queued = queued.inspect(|tx| *backlog.entry(tx.to_chain_id).or_insert(0usize) += 1);
+            let queued = queued.filter(|tx| !self.is_paused(tx.to_chain_id));
+            let queued = queued.filter(|tx| self.is_turn(tx, now));
+            let picked = self.scheduler.pick(queued, now);
+            (backlog, picked)
+        });
+        let Some(picked) = picked else {
+            return Ok(false);
+        };
+        let tx = {
+            // an admin action may have moved it since it was picked
+            let _guard = self.writes.lock().unwrap_or_else(|e| e.into_inner());
+            match self.store().take(&picked.key)? {
+                Some(tx) => tx,
+                None => return Ok(true),
+            }
+        };
+        let tid = tx.order_key();
+        let previous = self.shard.as_ref().and_then(|s| s.previous(&tx.from_chain_tx_hash));
+        if let Some(previous) = previous {
+            // the destination check below keeps a transfer its owner executed meanwhile from running twice
+            tracing::warn!("taking over tx {} from {}", tx.from_chain_tx_hash, previous);
+        }
 
-            match fut.await {
-                Ok(pending) => self_.store().pending().insert(tid, pending)?,
-                Err(e) => tracing::error!("failed to execute tx: {:?}", e),
+        // check is already finalize
+        if self.store().finalize().get(&tx.from_chain_tx_hash)?.is_some() {
+            tracing::warn!("tx already finalized: {}", tx.from_chain_tx_hash);
+            self.store().forget(&tx.from_chain_tx_hash)?;
+            return Ok(true);
+        }
+
+        // execute tx
+        tracing::info!("executing {:?}", tx);
+        let from_chain_tx_hash = tx.from_chain_tx_hash.clone();
+        let op = match self.operators.get(&tx.to_chain_id) {
+            Some(op) => op,
+            None => {
+                tracing::error!("failed to execute tx: no operator for chain id: {}", tx.to_chain_id);
+                self.store().forget(&from_chain_tx_hash)?;
+                return Ok(true);
+            }
+        };
+
+        // the ones behind it, the operator may price its fee on them
+        op.set_backlog(backlog.get(&tx.to_chain_id).map_or(0, |queued| queued - 1));
+
+        // the local finalize store is gone with the db, so ask the destination chain as well
+        match op.received(&tx).await {
+            Ok(ReceiveState::Received(to_chain_tx_hash)) => {
+                tracing::warn!("tx {from_chain_tx_hash} already received on chain {}", tx.to_chain_id);
+                self.settle_received(tx, to_chain_tx_hash, previous.is_some())?;
+                return Ok(true);
+            }
+            Ok(ReceiveState::NotReceived) => {}
+            // an earlier execution may have landed, sending another one blind could only fail or be reported wrong
+            Err(e) => {
+                tracing::warn!("failed to check tx {from_chain_tx_hash} on destination, deferred: {:?}", e);
+                self.store().defer(Deferred { until: now() + self.profit.defer_secs(), tx })?;
+                return Ok(true);
+            }
+        }
+
+        match self.profit.check(op.as_ref(), &tx).await {
+            Verdict::Execute => {}
+            Verdict::Defer(reason) => {
+                tracing::warn!("tx {from_chain_tx_hash} deferred: {}", reason);
+                let until = now() + self.profit.defer_secs();
+                self.store().defer(Deferred { until, tx })?;
+                return Ok(true);
+            }
+            Verdict::Flag(reason) => {
+                tracing::error!("tx {from_chain_tx_hash} flagged unprofitable: {}", reason);
+                self.store().flag(tx)?;
+                return Ok(true);
+            }
+        }
+
+        // only a transfer that executes uses up the turn of its chain
+        self.scheduler.charge(tx.from_chain_id);
+        self.scheduler.record_wait(&picked, now());
+        let result = op.execute(tx.clone()).await;
+        // the revert may be on an attempt that landed after the check above, the destination tells it by now
+        let recheck = match result.as_ref().err().and_then(|e| e.downcast_ref::<ExecuteError>()) {
+            Some(ExecuteError::Reverted(_)) => Some(op.received(&tx).await),
+            None => None,
+        };
+        // an admin may have cancelled it meanwhile, it must not come back
+        let _guard = self.writes.lock().unwrap_or_else(|e| e.into_inner());
+        if self.store().state(&from_chain_tx_hash)? != Some(TxState::Executing) {
+            tracing::warn!("tx {from_chain_tx_hash} was cancelled while executing");
+            return Ok(true);
+        }
+        match result {
+            Ok(pending) => self.store().submitted(tid, pending)?,
+            Err(e) => match (e.downcast_ref::<ExecuteError>(), recheck) {
+                (Some(ExecuteError::Reverted(reason)), Some(Ok(ReceiveState::Received(to_chain_tx_hash)))) => {
+                    tracing::warn!("tx {from_chain_tx_hash} reverted on an earlier execution that landed: {}", reason);
+                    self.settle_received(tx, to_chain_tx_hash, previous.is_some())?;
+                }
+                // reporting the revert could be wrong while the destination does not tell
+                (Some(ExecuteError::Reverted(reason)), Some(Err(check))) => {
+                    tracing::warn!(
+                        "tx {from_chain_tx_hash} reverted: {}, failed to check it, deferred: {:?}",
+                        reason,
+                        check
+                    );
+                    self.store().defer(Deferred { until: now() + self.profit.defer_secs(), tx })?;
+                }
+                (Some(ExecuteError::Reverted(reason)), _) => {
+                    tracing::error!("tx {from_chain_tx_hash} reverted in simulation: {}", reason);
+                    let status = TransactionStatus::Reverted(reason.clone());
+                    let hash = from_chain_tx_hash.clone();
+                    self.patch_result_with(hash, status, |batch| {
+                        self.store().forget_append(&from_chain_tx_hash, batch)
+                    })?;
+                }
+                // the execution may have been sent before the error, the destination check tells on the next try
+                (None, _) => {
+                    tracing::error!("failed to execute tx {from_chain_tx_hash}, deferred: {:?}", e);
+                    self.store().defer(Deferred { until: now() + self.profit.defer_secs(), tx })?;
+                }
+            },
+        }
+        Ok(true)
+    }
+
+    // a hash the queued transfer carries is from an attempt that did not land, only the chain's counts
+    fn settle_received(
+        &self,
+        mut tx: IzarTransaction,
+        to_chain_tx_hash: Option<String>,
+        takeover: bool,
+    ) -> anyhow::Result<()> {
+        let from_chain_tx_hash = tx.from_chain_tx_hash.clone();
+        tx.to_chain_tx_hash = to_chain_tx_hash;
+        match tx.to_chain_tx_hash.clone() {
+            // whoever executed it reports it, a takeover has nothing to tell
+            _ if takeover => self.store().finish(tx),
+            Some(hash) => {
+                let status = TransactionStatus::Success(hash);
+                self.patch_result_with(from_chain_tx_hash, status, |batch| self.store().finish_append(tx, batch))
+            }
+            // a success without the execution that landed would be a wrong report, and finalizing it unreported
+            // leaves it pending at the sequencer, so it waits for the destination to tell
+            None => {
+                tracing::error!("tx {from_chain_tx_hash} was received without its destination tx, deferred");
+                self.store().defer(Deferred { until: now() + self.profit.defer_secs(), tx })
             }
         }
     }
 
     pub fn pending_checker(self_: Arc<IzarRelayer<I>>) {
+        // pending txs stay in the store until they are settled, these ones are being checked already
+        let checking = Arc::new(Mutex::new(HashSet::<Vec<u8>>::new()));
         loop {
-            let tx = match self_.store().pending().iter().next() {
-                Some((_, tx)) => tx.into_owned(),
+            let next = {
+                let checking = checking.lock().unwrap_or_else(|e| e.into_inner());
+                let mut pending = self_.store().pending().iter();
+                pending.find(|(k, _)| !checking.contains(k.as_ref())).map(|(k, tx)| (k.into_owned(), tx.into_owned()))
+            };
+            let (tid, tx) = match next {
+                Some(next) => next,
                 None => {
                     tracing::warn!("no pending transaction, sleep 60s");
                     std::thread::sleep(std::time::Duration::from_secs(60));
                     continue;
                 }
             };
-            let tid = tx.order_key();
-            let from_chain_tx_hash = tx.from_chain_tx_hash.clone();
-            self_.store().pending().remove(&tid).unwrap(); // TODO: handle error
+            checking.lock().unwrap_or_else(|e| e.into_inner()).insert(tid.clone());
 
             let izar = self_.clone();
+            let checking = checking.clone();
             let fut = async move {
-                let op = izar
-                    .operators
-                    .get(&tx.to_chain_id)
-                    .ok_or(anyhow!("no operator for chain id: {}", tx.to_chain_id))?;
-                let status = op.pending(tx.clone())?.checking().await;
-                match status {
-                    TransactionStatus::Success(ref tx_hash) => {
-                        tracing::info!("tx {from_chain_tx_hash} finalized: {}", tx_hash);
-                        izar.store().finalize().insert(from_chain_tx_hash, tx.clone())?;
-                    }
-                    TransactionStatus::NotBroadcasted(ref e) => {
-                        tracing::error!("tx {from_chain_tx_hash} not broadcasted: {}, re-add executing pipeline", e);
-                    }
-                    TransactionStatus::Rejected(ref reason) => {
-                        tracing::error!("tx {from_chain_tx_hash} rejected: {}", reason)
-                    }
+                let from_chain_tx_hash = tx.from_chain_tx_hash.clone();
+                if let Err(e) = izar.check_pending(&tid, tx).await {
+                    tracing::error!("tx {from_chain_tx_hash} check failed: {:?}", e);
                 }
-                izar.patch_result(tx.from_chain_tx_hash.clone(), status).await?;
-                Ok::<_, anyhow::Error>(())
+                checking.lock().unwrap_or_else(|e| e.into_inner()).remove(&tid);
             };
 
-            tokio::spawn(fut); // TODO: handle error? resource control?
+            tokio::spawn(fut); // TODO: resource control?
         }
     }
 
-    async fn patch_result(&self, from_tx_hash: String, status: TransactionStatus) -> anyhow::Result<()> {
-        let req = PatchRequest::new(from_tx_hash, status);
-        let resp = self.client.patch(self.sequencer()).json(&req).send().await?;
-        tracing::info!("patch result: {:?}", resp);
+    pub(crate) async fn check_pending(&self, tid: &Vec<u8>, tx: IzarTransaction) -> anyhow::Result<()> {
+        let from_chain_tx_hash = tx.from_chain_tx_hash.clone();
+        let op = self.operators.get(&tx.to_chain_id).ok_or(anyhow!("no operator for chain id: {}", tx.to_chain_id))?;
+        let (status, fee) = op.pending(tx.clone())?.checking().await;
+
+        // a speedup may have replaced the execution meanwhile, or an admin taken it out
+        let _guard = self.writes.lock().unwrap_or_else(|e| e.into_inner());
+        // what was seen of an execution being replaced may be stale already, the next round tells
+        if self.is_bumping(&from_chain_tx_hash) {
+            tracing::info!("tx {from_chain_tx_hash} is being bumped, checked again later");
+            return Ok(());
+        }
+        match self.store().pending().get(tid)? {
+            Some(stored) if stored.to_chain_tx_hash == tx.to_chain_tx_hash => {}
+            Some(stored) => {
+                tracing::info!("tx {from_chain_tx_hash} replaced by {:?} while checked", stored.to_chain_tx_hash);
+                return Ok(());
+            }
+            None => {
+                tracing::warn!("tx {from_chain_tx_hash} left the pending store while checked");
+                return Ok(());
+            }
+        }
+
+        let mut tx = tx;
+        if let Err(e) = op.settle(&tx, &status) {
+            tracing::error!("tx {from_chain_tx_hash} settle failed: {:?}", e);
+        }
+        let hash = from_chain_tx_hash.clone();
+        match status {
+            TransactionStatus::Success(ref tx_hash) => {
+                tracing::info!("tx {from_chain_tx_hash} finalized: {}", tx_hash);
+                // a replaced execution may be the one that landed
+                tx.to_chain_tx_hash = Some(tx_hash.clone());
+                self.patch_result_with(hash, status.clone(), |batch| self.store().settle_append(tx, fee, batch))
+            }
+            TransactionStatus::NotBroadcasted(ref e) => {
+                tracing::error!("tx {from_chain_tx_hash} not broadcasted: {}, re-add executing pipeline", e);
+                self.patch_result_with(hash, status.clone(), |batch| {
+                    self.store().forget_append(&from_chain_tx_hash, batch)
+                })
+            }
+            TransactionStatus::Rejected(ref reason) | TransactionStatus::Reverted(ref reason) => {
+                tracing::error!("tx {from_chain_tx_hash} rejected: {}", reason);
+                self.patch_result_with(hash, status.clone(), |batch| {
+                    self.store().forget_append(&from_chain_tx_hash, batch)
+                })
+            }
+        }
+    }
+
+    // deferred txs keep their order key, so they go back to where they were in the queue
+    fn requeue_deferred(&self) -> anyhow::Result<()> {
+        let now = now();
+        let _guard = self.writes.lock().unwrap_or_else(|e| e.into_inner());
+        for (_, deferred) in self.store().deferred().get_all()? {
+            if deferred.until <= now {
+                self.store().requeue(deferred.tx)?;
+            }
+        }
         Ok(())
     }
 }
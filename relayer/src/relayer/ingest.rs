@@ -0,0 +1,352 @@
+use std::{collections::HashSet, sync::Mutex};
+
+use axum::{
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    Json,
+};
+use izar_core::{
+    eths,
+    network::{aleo::AleoNetworkExt, IzarNetwork},
+    types::transaction::{IzarTransaction, Priority},
+};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::{store::TxState, IzarRelayer};
+
+/// Error body of every REST endpoint, `code` is stable and `message` is for humans.
+#[derive(Debug, Serialize, ToSchema)]
+pub struct ApiError {
+    #[serde(skip)]
+    pub status: StatusCode,
+    #[schema(value_type = String)]
+    pub code: &'static str,
+    pub message: String,
+}
+
+impl ApiError {
+    pub fn new(status: StatusCode, code: &'static str, message: impl ToString) -> Self {
+        Self { status, code, message: message.to_string() }
+    }
+
+    pub fn malformed(message: impl ToString) -> Self {
+        Self::new(StatusCode::BAD_REQUEST, "malformed_request", message)
+    }
+
+    pub fn invalid(message: impl ToString) -> Self {
+        Self::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_transaction", message)
+    }
+
+    pub fn unsupported_route(message: impl ToString) -> Self {
+        Self::new(StatusCode::UNPROCESSABLE_ENTITY, "unsupported_route", message)
+    }
+
+    pub fn invalid_certificates(message: impl ToString) -> Self {
+        Self::new(StatusCode::UNPROCESSABLE_ENTITY, "invalid_certificates", message)
+    }
+
+    pub fn not_found(hash: &str) -> Self {
+        Self::new(StatusCode::NOT_FOUND, "not_found", format!("no transfer with source tx hash {hash}"))
+    }
+
+    pub fn conflict(message: impl ToString) -> Self {
+        Self::new(StatusCode::CONFLICT, "conflict", message)
+    }
+
+    pub fn internal(e: anyhow::Error) -> Self {
+        tracing::error!("rest request failed: {:?}", e);
+        Self::new(StatusCode::INTERNAL_SERVER_ERROR, "internal", e)
+    }
+}
+
+impl IntoResponse for ApiError {
+    fn into_response(self) -> Response {
+        (self.status, Json(self)).into_response()
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Ingested {
+    pub from_chain_tx_hash: String,
+    pub state: TxState,
+    pub to_chain_tx_hash: Option<String>,
+    /// the transfer was known already, nothing was queued
+    pub duplicate: bool,
+}
+
+/// What a speedup request did to the transfer.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum SpeedupAction {
+    /// unknown before, queued with high priority
+    Queued,
+    /// moved ahead in the execute queue
+    Reprioritized,
+    /// a deferred transfer queued again with high priority
+    Requeued,
+    /// the pending execution was replaced by one paying a higher fee
+    Bumped,
+    /// nothing left to speed up
+    None,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SpedUp {
+    pub from_chain_tx_hash: String,
+    pub state: TxState,
+    pub to_chain_tx_hash: Option<String>,
+    pub action: SpeedupAction,
+}
+
+/// Holds the bump of a transfer until dropped.
+struct Bump<'a> {
+    bumping: &'a Mutex<HashSet<String>>,
+    hash: String,
+}
+
+impl Drop for Bump<'_> {
+    fn drop(&mut self) {
+        self.bumping.lock().unwrap_or_else(|e| e.into_inner()).remove(&self.hash);
+    }
+}
+
+impl<I: IzarNetwork> IzarRelayer<I> {
+    /// Whether a speedup is replacing the pending execution of `hash`.
+    pub(crate) fn is_bumping(&self, hash: &str) -> bool {
+        self.bumping.lock().unwrap_or_else(|e| e.into_inner()).contains(hash)
+    }
+
+    // none while another speedup bumps it
+    fn start_bump(&self, hash: &str) -> Option<Bump<'_>> {
+        let started = self.bumping.lock().unwrap_or_else(|e| e.into_inner()).insert(hash.to_string());
+        started.then(|| Bump { bumping: &self.bumping, hash: hash.to_string() })
+    }
+
+    /// Queues `tx` unless a transfer with the same source tx hash is already anywhere in the store.
+    pub fn ingest(&self, tx: IzarTransaction) -> Result<(StatusCode, Ingested), ApiError> {
+        self.validate(&tx)?;
+
+        // the state lookup and the insert have to be one step for concurrent submissions of a transfer
+        let _guard = self.writes.lock().unwrap_or_else(|e| e.into_inner());
+        let hash = tx.from_chain_tx_hash.clone();
+        if let Some(state) = self.store().state(&hash).map_err(ApiError::internal)? {
+            tracing::info!("tx {hash} already known: {:?}", state);
+            let to_chain_tx_hash = match state {
+                TxState::Finalized => self.store().finalize().get(&hash).map_err(ApiError::internal)?,
+                _ => None,
+            }
+            .and_then(|tx| tx.to_chain_tx_hash);
+            let ingested = Ingested { from_chain_tx_hash: hash, state, to_chain_tx_hash, duplicate: true };
+            return Ok((StatusCode::OK, ingested));
+        }
+
+        self.store().enqueue(tx).map_err(ApiError::internal)?;
+        let ingested =
+            Ingested { from_chain_tx_hash: hash, state: TxState::Queued, to_chain_tx_hash: None, duplicate: false };
+        Ok((StatusCode::ACCEPTED, ingested))
+    }
+
+    fn validate(&self, tx: &IzarTransaction) -> Result<(), ApiError> {
+        let fields = [&tx.from_chain_tx_hash, &tx.from_addr, &tx.from_asset_addr, &tx.to_addr, &tx.to_asset_addr];
+        if fields.iter().any(|f| f.is_empty()) {
+            return Err(ApiError::invalid("source tx hash, addresses and assets are required"));
+        }
+        if tx.to_chain_tx_hash.is_some() {
+            return Err(ApiError::invalid("destination tx hash is set before execution"));
+        }
+        tx.fee.parse::<u128>().map_err(|e| ApiError::invalid(format!("fee {}: {}", tx.fee, e)))?;
+
+        let is_izar_chain = |chain_id: u32| matches!(chain_id, eths!()) || chain_id == I::Aleo::IZAR_CHAIN_ID;
+        if !is_izar_chain(tx.from_chain_id) {
+            return Err(ApiError::unsupported_route(format!("unknown source chain {}", tx.from_chain_id)));
+        }
+        if tx.from_chain_id == tx.to_chain_id {
+            return Err(ApiError::unsupported_route(format!("source and destination are both {}", tx.to_chain_id)));
+        }
+        let op = self
+            .operators
+            .get(&tx.to_chain_id)
+            .ok_or_else(|| ApiError::unsupported_route(format!("no operator for chain {}", tx.to_chain_id)))?;
+
+        if tx.certificates.is_empty() {
+            return Err(ApiError::invalid_certificates("transfer carries no certificates"));
+        }
+        let mut signers = HashSet::new();
+        for c in tx.certificates.iter() {
+            if !signers.insert(&c.signer) {
+                return Err(ApiError::invalid_certificates(format!("{} signed more than once", c.signer)));
+            }
+        }
+        op.validate(tx).map_err(ApiError::invalid_certificates)
+    }
+
+    /// Moves `tx` ahead wherever it is, a pending execution gets its fee bumped.
+    pub async fn speedup(&self, mut tx: IzarTransaction) -> Result<(StatusCode, SpedUp), ApiError> {
+        tx.priority = Priority::High;
+        let hash = tx.from_chain_tx_hash.clone();
+        let sped_up = |state, to_chain_tx_hash, action| SpedUp {
+            from_chain_tx_hash: hash.clone(),
+            state,
+            to_chain_tx_hash,
+            action,
+        };
+
+        let located = {
+            let _guard = self.writes.lock().unwrap_or_else(|e| e.into_inner());
+            let located = self.store().locate(&hash).map_err(ApiError::internal)?;
+            match located {
+                Some(located) => match (located.state, located.tx.clone()) {
+                    (TxState::Queued, Some(mut queued)) => {
+                        if queued.priority != Priority::High {
+                            queued.priority = Priority::High;
+                            self.store().reorder(&located, queued).map_err(ApiError::internal)?;
+                        }
+                        return Ok((StatusCode::OK, sped_up(TxState::Queued, None, SpeedupAction::Reprioritized)));
+                    }
+                    (TxState::Deferred, Some(mut deferred)) => {
+                        deferred.priority = Priority::High;
+                        self.store().requeue(deferred).map_err(ApiError::internal)?;
+                        return Ok((StatusCode::OK, sped_up(TxState::Queued, None, SpeedupAction::Requeued)));
+                    }
+                    (TxState::Finalized, tx) => {
+                        let to_chain_tx_hash = tx.and_then(|tx| tx.to_chain_tx_hash);
+                        return Ok((
+                            StatusCode::OK,
+                            sped_up(TxState::Finalized, to_chain_tx_hash, SpeedupAction::None),
+                        ));
+                    }
+                    (TxState::Pending, Some(_)) => {
+                        let bump = self.start_bump(&hash).ok_or_else(|| {
+                            ApiError::conflict(format!("tx {hash} is being sped up by another request"))
+                        })?;
+                        Some((located, bump))
+                    }
+                    (state, _) => {
+                        return Err(ApiError::conflict(format!("tx {hash} is {state:?}, it cannot be sped up")))
+                    }
+                },
+                None => None,
+            }
+        };
+
+        let Some((located, _bump)) = located else {
+            let (status, ingested) = self.ingest(tx)?;
+            let action = if ingested.duplicate { SpeedupAction::None } else { SpeedupAction::Queued };
+            return Ok((status, sped_up(ingested.state, ingested.to_chain_tx_hash, action)));
+        };
+
+        let pending = located.tx.clone().ok_or_else(|| ApiError::conflict(format!("tx {hash} has no stored body")))?;
+        let op = self
+            .operators
+            .get(&pending.to_chain_id)
+            .ok_or_else(|| ApiError::unsupported_route(format!("no operator for chain {}", pending.to_chain_id)))?;
+        let bumped = op.bump(&pending).await.map_err(ApiError::internal)?.ok_or_else(|| {
+            ApiError::new(
+                StatusCode::CONFLICT,
+                "not_bumpable",
+                format!("the execution {:?} of tx {hash} cannot be replaced", pending.to_chain_tx_hash),
+            )
+        })?;
+
+        let _guard = self.writes.lock().unwrap_or_else(|e| e.into_inner());
+        // the checker may have settled the old execution while the replacement was sent
+        if self.store().pending().get(&located.key).map_err(ApiError::internal)? != Some(pending) {
+            tracing::warn!("tx {hash} settled while bumped to {:?}", bumped.to_chain_tx_hash);
+            return Err(ApiError::conflict(format!("tx {hash} settled while its fee was bumped")));
+        }
+        let to_chain_tx_hash = bumped.to_chain_tx_hash.clone();
+        self.store().replace_pending(&located, bumped).map_err(ApiError::internal)?;
+        Ok((StatusCode::OK, sped_up(TxState::Pending, to_chain_tx_hash, SpeedupAction::Bumped)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use axum::http::StatusCode;
+    use izar_core::{
+        network::{eth::EthNetwork, testnet::IzarTestnet, IzarNetwork},
+        sequencer::SequencerClient,
+        types::transaction::IzarTransaction,
+    };
+    use izar_testkit::transfer::TransferBuilder;
+
+    use crate::{
+        relayer::{
+            store::TxState,
+            types::{IzarPendingTransaction, ReceiveState},
+            IzarRelayer,
+        },
+        Operator,
+    };
+
+    type Sepolia = <IzarTestnet as IzarNetwork>::Sepolia;
+    const SEPOLIA: u32 = Sepolia::IZAR_CHAIN_ID;
+
+    // accepts every certificate but the forger's
+    struct Validating;
+
+    #[async_trait]
+    impl Operator<IzarTestnet> for Validating {
+        async fn execute(&self, tx: IzarTransaction) -> anyhow::Result<IzarTransaction> {
+            Ok(tx)
+        }
+
+        fn validate(&self, tx: &IzarTransaction) -> anyhow::Result<()> {
+            match tx.certificates.iter().any(|c| c.signer == "forger") {
+                true => anyhow::bail!("bad signature of forger"),
+                false => Ok(()),
+            }
+        }
+
+        fn pending(&self, _tx: IzarTransaction) -> anyhow::Result<IzarPendingTransaction<IzarTestnet>> {
+            anyhow::bail!("never executed")
+        }
+
+        async fn received(&self, _tx: &IzarTransaction) -> anyhow::Result<ReceiveState> {
+            Ok(ReceiveState::NotReceived)
+        }
+    }
+
+    fn transfer(hash: &str) -> TransferBuilder {
+        TransferBuilder::new(hash, 2, SEPOLIA).with_from("aleo1asset", "aleo1from").with_to("0xasset", "0xto")
+    }
+
+    #[test]
+    fn test_ingest() {
+        let db = tempfile::tempdir().unwrap();
+        let sequencer = SequencerClient::new(vec!["http://127.0.0.1:1".to_string()], Duration::from_secs(1)).unwrap();
+        let mut izar = IzarRelayer::<IzarTestnet>::new(0, sequencer, db.path());
+        izar.insert_operator(SEPOLIA, Box::new(Validating));
+
+        let code = |tx: IzarTransaction| izar.ingest(tx).map(|_| "ok").unwrap_or_else(|e| e.code);
+        assert_eq!(code(transfer("t1").build()), "invalid_certificates");
+        assert_eq!(code(transfer("t1").with_certificate("forger", "s").build()), "invalid_certificates");
+        let twice = transfer("t1").with_certificate("v1", "s1").with_certificate("v1", "s2").build();
+        assert_eq!(code(twice), "invalid_certificates");
+        assert_eq!(
+            code(TransferBuilder::new("t1", 2, SEPOLIA).with_certificate("v1", "s").build()),
+            "invalid_transaction"
+        );
+        let mut looped = transfer("t1").with_certificate("v1", "s").build();
+        looped.to_chain_id = 2;
+        assert_eq!(code(looped), "unsupported_route");
+        let mut unserved = transfer("t1").with_certificate("v1", "s").build();
+        unserved.to_chain_id = 3;
+        assert_eq!(code(unserved), "unsupported_route");
+        let mut executed = transfer("t1").with_certificate("v1", "s").build();
+        executed.to_chain_tx_hash = Some("0x1".to_string());
+        assert_eq!(code(executed), "invalid_transaction");
+        assert_eq!(izar.store().state(&"t1".to_string()).unwrap(), None);
+
+        let tx = transfer("t1").with_certificate("v1", "s").build();
+        let (status, ingested) = izar.ingest(tx.clone()).unwrap();
+        assert_eq!((status, ingested.state, ingested.duplicate), (StatusCode::ACCEPTED, TxState::Queued, false));
+        let (status, ingested) = izar.ingest(tx).unwrap();
+        assert_eq!((status, ingested.state, ingested.duplicate), (StatusCode::OK, TxState::Queued, true));
+        assert_eq!(izar.store().execute().get_all().unwrap().len(), 1);
+    }
+}
@@ -1,30 +1,87 @@
+pub mod admin;
+pub mod auth;
 pub mod execute;
+pub mod ingest;
+pub mod monitor;
+pub mod outbox;
+pub mod profit;
+pub mod pull;
+pub mod query;
 pub mod rest;
+pub mod scheduler;
+pub mod shard;
+#[cfg(test)]
+mod sim;
 pub mod store;
 pub mod types;
 
-use izar_core::network::IzarNetwork;
-use std::{collections::HashMap, sync::Arc};
+use izar_core::{
+    metrics::relayer::RELAYER_BALANCE,
+    monitor::{Alerter, BalanceMonitor},
+    network::IzarNetwork,
+    sequencer::SequencerClient,
+};
+use std::{
+    collections::{HashMap, HashSet},
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex, RwLock},
+    time::Duration,
+};
 
 use crate::{Operator, Operators};
 
-use self::store::RelayerStore;
+use self::{
+    outbox::Outbox,
+    profit::Profitability,
+    pull::{PullConfig, Puller},
+    rest::RestConfig,
+    scheduler::Scheduler,
+    shard::Shard,
+    store::{RelayerStore, WriteHook},
+};
 
 pub struct IzarRelayer<I: IzarNetwork> {
     port: u16,
-    sequencer: String,
+    db_path: PathBuf,
+    rest: RestConfig,
+    sequencer: SequencerClient,
     store: RelayerStore,
+    outbox: Outbox,
     operators: Operators<I>,
+    profit: Profitability,
+    scheduler: Scheduler,
+    shard: Option<Shard>,
+    puller: Option<Puller>,
+    monitor: BalanceMonitor,
+    monitor_interval: Duration,
+    paused: RwLock<HashSet<u32>>,
+    // serializes REST writes to the store with each other and with the executor taking work
+    writes: Mutex<()>,
+    // transfers whose pending execution a speedup is replacing, the checker leaves them be meanwhile
+    bumping: Mutex<HashSet<String>>,
     client: reqwest::Client,
 }
 
 impl<I: IzarNetwork> IzarRelayer<I> {
-    pub fn new(port: u16, sequencer: String) -> Self {
+    pub fn new(port: u16, sequencer: SequencerClient, db_path: impl AsRef<Path>) -> Self {
+        let db_path = db_path.as_ref().to_path_buf();
         Self {
             port,
+            rest: RestConfig::default(),
             sequencer,
-            store: RelayerStore::build().expect("Failed to open relayer store"),
+            store: RelayerStore::build(&db_path).expect("Failed to open relayer store"),
+            outbox: Outbox::new(&db_path).expect("Failed to open patch outbox"),
+            db_path,
             operators: HashMap::new(),
+            profit: Profitability::default(),
+            scheduler: Scheduler::default(),
+            shard: None,
+            puller: None,
+            monitor: BalanceMonitor::new(RELAYER_BALANCE, Alerter::new(None), HashMap::new()),
+            monitor_interval: Duration::from_secs(60),
+            paused: RwLock::new(HashSet::new()),
+            writes: Mutex::new(()),
+            bumping: Mutex::new(HashSet::new()),
             client: reqwest::Client::new(),
         }
     }
@@ -33,11 +90,45 @@ impl<I: IzarNetwork> IzarRelayer<I> {
         self.operators.insert(chain_id, operator);
     }
 
+    pub fn set_rest(&mut self, rest: RestConfig) -> anyhow::Result<()> {
+        rest.auth.check()?;
+        self.rest = rest;
+        Ok(())
+    }
+
+    pub fn set_profitability(&mut self, profit: Profitability) {
+        self.profit = profit;
+    }
+
+    pub fn set_scheduler(&mut self, scheduler: Scheduler) {
+        self.scheduler = scheduler;
+    }
+
+    pub fn set_shard(&mut self, shard: Shard) {
+        self.shard = Some(shard);
+    }
+
+    pub fn set_pull(&mut self, config: PullConfig) -> anyhow::Result<()> {
+        self.puller = Some(Puller::new(config, &self.db_path)?);
+        Ok(())
+    }
+
+    pub fn set_monitor(&mut self, monitor: BalanceMonitor, interval: Duration) {
+        self.monitor = monitor;
+        self.monitor_interval = interval;
+    }
+
+    /// Hooks `hook` around every write to the store and the patch outbox.
+    pub fn set_write_hook(&mut self, hook: Arc<dyn WriteHook>) {
+        self.store.set_hook(hook.clone());
+        self.outbox.set_hook(hook);
+    }
+
     pub fn store(&self) -> &RelayerStore {
         &self.store
     }
 
-    pub fn sequencer(&self) -> &str {
+    pub fn sequencer(&self) -> &SequencerClient {
         &self.sequencer
     }
 
@@ -56,6 +147,18 @@ impl<I: IzarNetwork> IzarRelayer<I> {
             }
         });
 
+        // start balance monitor
+        let monitor = izar.clone();
+        tokio::spawn(async move { IzarRelayer::balance_monitor(monitor).await });
+
+        // start delivering status patches
+        let sender = izar.clone();
+        tokio::spawn(async move { IzarRelayer::outbox_sender(sender).await });
+
+        // start pulling from the sequencer
+        let puller = izar.clone();
+        tokio::spawn(async move { IzarRelayer::pull_loop(puller).await });
+
         // start pending checker
         let checker = izar.clone();
         tokio::spawn(async move { IzarRelayer::pending_checker(checker) });
@@ -0,0 +1,49 @@
+use izar_core::network::IzarNetwork;
+use std::sync::Arc;
+
+use crate::Operator;
+
+use super::IzarRelayer;
+
+impl<I: IzarNetwork> IzarRelayer<I> {
+    pub async fn balance_monitor(self_: Arc<IzarRelayer<I>>) {
+        loop {
+            for (chain_id, op) in self_.operators.iter() {
+                if let Err(e) = self_.check_balances(*chain_id, op.as_ref()).await {
+                    tracing::error!("failed to check balances on chain {}: {:?}", chain_id, e);
+                }
+            }
+            tokio::time::sleep(self_.monitor_interval).await;
+        }
+    }
+
+    pub fn is_paused(&self, chain_id: u32) -> bool {
+        self.paused.read().map(|p| p.contains(&chain_id)).unwrap_or(false)
+    }
+
+    async fn check_balances(&self, chain_id: u32, op: &dyn Operator<I>) -> anyhow::Result<()> {
+        let balances = op.balances().await?;
+        for balance in balances.iter() {
+            self.monitor.observe(chain_id, &balance.address, balance.balance).await;
+        }
+
+        let budget = match op.execution_budget().await? {
+            Some(budget) => budget,
+            None => return Ok(()),
+        };
+        let funded = balances.iter().any(|b| b.balance >= budget);
+        let changed = match self.paused.write() {
+            Ok(mut paused) if funded => paused.remove(&chain_id),
+            Ok(mut paused) => paused.insert(chain_id),
+            Err(_) => anyhow::bail!("paused chains lock poisoned"),
+        };
+        if changed {
+            let text = match funded {
+                true => format!("executions to chain {chain_id} resumed"),
+                false => format!("executions to chain {chain_id} paused, no key can pay {budget}"),
+            };
+            self.monitor.alerter().alert(&text).await;
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,258 @@
+use std::{
+    collections::HashSet,
+    path::Path,
+    sync::{
+        atomic::{AtomicU64, Ordering},
+        Arc,
+    },
+    time::Duration,
+};
+
+use izar_core::{
+    db::{map::DBMap, RocksDB},
+    metrics::relayer::RELAYER_PATCH_OUTBOX,
+    network::IzarNetwork,
+};
+use rocksdb::WriteBatch;
+use serde::{Deserialize, Serialize};
+use tokio::sync::Notify;
+
+use super::{
+    store::{now, NoHook, WriteHook},
+    types::{PatchRequest, TransactionStatus},
+    IzarRelayer,
+};
+
+const OUTBOX_PREFIX: &str = "patch_outbox";
+const REJECTED_PREFIX: &str = "patch_rejected";
+const BASE_BACKOFF: u64 = 5;
+const MAX_BACKOFF: u64 = 600;
+
+/// A status update waiting for the sequencer to answer 2xx.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct OutboxEntry {
+    pub request: PatchRequest,
+    pub attempts: u32,
+    /// unix seconds of the next attempt
+    pub next_at: u64,
+}
+
+/// A patch the sequencer refused for good, kept for an operator to look at.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RejectedPatch {
+    pub request: PatchRequest,
+    pub attempts: u32,
+    pub status: u16,
+    pub reason: String,
+    pub rejected_at: u64,
+}
+
+/// Status PATCHes to the sequencer, kept until delivered. Updates of one transfer go out in the order they were
+/// made, a failing one holds back the later ones of the same transfer only.
+pub struct Outbox {
+    entries: DBMap<Vec<u8>, OutboxEntry>,
+    rejected: DBMap<Vec<u8>, RejectedPatch>,
+    seq: AtomicU64,
+    wake: Notify,
+    hook: Arc<dyn WriteHook>,
+}
+
+impl Outbox {
+    pub fn new(db_path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let entries: DBMap<Vec<u8>, OutboxEntry> = RocksDB::open_map(&db_path, OUTBOX_PREFIX)?;
+        let rejected = RocksDB::open_map(&db_path, REJECTED_PREFIX)?;
+        // keys are big endian sequence numbers, so the last one is the highest
+        let last = entries.keys().last().map(|k| u64::from_be_bytes(k.as_slice().try_into().unwrap_or_default()));
+        let seq = AtomicU64::new(last.map_or(0, |l| l + 1));
+        Ok(Self { entries, rejected, seq, wake: Notify::new(), hook: Arc::new(NoHook) })
+    }
+
+    pub fn push(&self, from_chain_tx_hash: String, status: TransactionStatus) -> anyhow::Result<()> {
+        self.push_with(from_chain_tx_hash, status, |_| Ok(()))
+    }
+
+    /// Queues the patch in one write with `f`, so a transfer never settles in the store without its patch.
+    pub fn push_with(
+        &self,
+        from_chain_tx_hash: String,
+        status: TransactionStatus,
+        f: impl FnOnce(&mut WriteBatch) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        let request = PatchRequest::new(from_chain_tx_hash, status);
+        let key = self.seq.fetch_add(1, Ordering::SeqCst).to_be_bytes().to_vec();
+        self.write(|batch| {
+            f(batch)?;
+            self.entries.write_append(key, OutboxEntry { request, attempts: 0, next_at: 0 }, batch)
+        })?;
+        self.wake.notify_one();
+        Ok(())
+    }
+
+    /// Drops the entry at `key` once the sequencer took it.
+    pub fn delivered(&self, key: &Vec<u8>) -> anyhow::Result<()> {
+        self.write(|batch| self.entries.delete_append(key, batch))
+    }
+
+    /// Keeps the entry at `key` for another attempt after its backoff.
+    pub fn retry(&self, key: Vec<u8>, mut entry: OutboxEntry) -> anyhow::Result<()> {
+        entry.attempts += 1;
+        entry.next_at = now() + backoff(entry.attempts);
+        self.write(|batch| self.entries.write_append(key, entry, batch))
+    }
+
+    pub fn entries(&self) -> &DBMap<Vec<u8>, OutboxEntry> {
+        &self.entries
+    }
+
+    /// Moves the entry at `key` out of the outbox for good, later patches of its transfer are not held back by it.
+    pub fn reject(&self, key: Vec<u8>, entry: OutboxEntry, status: u16, reason: String) -> anyhow::Result<()> {
+        self.write(|batch| {
+            self.entries.delete_append(&key, batch)?;
+            let OutboxEntry { request, attempts, .. } = entry;
+            let rejected = RejectedPatch { request, attempts: attempts + 1, status, reason, rejected_at: now() };
+            self.rejected.write_append(key.clone(), rejected, batch)
+        })
+    }
+
+    pub fn rejected(&self) -> &DBMap<Vec<u8>, RejectedPatch> {
+        &self.rejected
+    }
+
+    pub(crate) fn set_hook(&mut self, hook: Arc<dyn WriteHook>) {
+        self.hook = hook;
+    }
+
+    fn write(&self, f: impl FnOnce(&mut WriteBatch) -> anyhow::Result<()>) -> anyhow::Result<()> {
+        self.hook.before("outbox")?;
+        RocksDB::atomic_batch(self.entries.inner(), f)?;
+        self.hook.after("outbox");
+        Ok(())
+    }
+}
+
+fn backoff(attempts: u32) -> u64 {
+    BASE_BACKOFF.saturating_mul(1 << attempts.min(16)).min(MAX_BACKOFF)
+}
+
+impl<I: IzarNetwork> IzarRelayer<I> {
+    /// Queues the final status of a transfer along with `f` settling it in the store.
+    pub(crate) fn patch_result_with(
+        &self,
+        from_tx_hash: String,
+        status: TransactionStatus,
+        f: impl FnOnce(&mut WriteBatch) -> anyhow::Result<()>,
+    ) -> anyhow::Result<()> {
+        tracing::info!("queue patch of tx {from_tx_hash}: {:?}", status);
+        self.outbox.push_with(from_tx_hash, status, f)
+    }
+
+    pub async fn outbox_sender(self_: Arc<IzarRelayer<I>>) {
+        loop {
+            let backlog = match self_.deliver_outbox().await {
+                Ok(backlog) => backlog,
+                Err(e) => {
+                    tracing::error!("failed to deliver patches: {:?}", e);
+                    self_.outbox.entries.iter().count()
+                }
+            };
+            metrics::gauge!(RELAYER_PATCH_OUTBOX).set(backlog as f64);
+
+            // new patches wake the sender, retries are picked up by the timeout
+            let _ = tokio::time::timeout(Duration::from_secs(BASE_BACKOFF), self_.outbox.wake.notified()).await;
+        }
+    }
+
+    /// Sends every due patch once, returns how many are left.
+    pub(crate) async fn deliver_outbox(&self) -> anyhow::Result<usize> {
+        let entries = self.outbox.entries.iter().map(|(k, e)| (k.into_owned(), e.into_owned())).collect::<Vec<_>>();
+        let mut held = HashSet::new();
+        let mut left = 0;
+        for (key, entry) in entries {
+            let hash = entry.request.from_chain_tx_hash.clone();
+            if held.contains(&hash) || entry.next_at > now() {
+                held.insert(hash);
+                left += 1;
+                continue;
+            }
+
+            match self.sequencer.patch_status(&entry.request).await {
+                Ok(()) => {
+                    tracing::info!("patched tx {hash}: {:?}", entry.request.status);
+                    self.outbox.delivered(&key)?;
+                    continue;
+                }
+                // the request itself is refused, sending it again changes nothing
+                Err(e) if e.is_permanent() => {
+                    tracing::error!("patch of tx {hash} rejected: {e}");
+                    self.outbox.reject(key, entry, e.status().unwrap_or_default(), e.to_string())?;
+                    continue;
+                }
+                Err(e) => tracing::warn!("patch of tx {hash} failed, attempt {}: {}", entry.attempts + 1, e),
+            }
+            self.outbox.retry(key, entry)?;
+            held.insert(hash);
+            left += 1;
+        }
+        Ok(left)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use ethers::signers::LocalWallet;
+    use izar_core::{network::testnet::IzarTestnet, sequencer::SequencerClient};
+    use izar_mock_sequencer::{MockConfig, MockSequencer};
+    use izar_testkit::transfer::TransferBuilder;
+
+    use super::backoff;
+    use crate::relayer::{types::TransactionStatus, IzarRelayer};
+
+    #[test]
+    fn test_backoff() {
+        assert_eq!(backoff(1), 10);
+        assert_eq!(backoff(3), 40);
+        assert_eq!(backoff(7), 600);
+        assert_eq!(backoff(u32::MAX), 600);
+    }
+
+    #[tokio::test]
+    async fn test_deliver() {
+        let seq = MockSequencer::new(MockConfig { threshold: Some(1), ..Default::default() });
+        let url = format!("http://{}", seq.clone().spawn(([127, 0, 0, 1], 0).into()).await.unwrap());
+        let db = tempfile::tempdir().unwrap();
+        let sequencer = SequencerClient::new(vec![url], Duration::from_secs(5)).unwrap();
+        let izar = IzarRelayer::<IzarTestnet>::new(0, sequencer, db.path());
+        let key = LocalWallet::new(&mut rand::thread_rng());
+        for hash in ["t1", "t2"] {
+            let tx = TransferBuilder::new(hash, 2, 1).with_certificate("voter1", "sign1").build();
+            izar.sequencer().submit_certificates(&tx, &key).await.unwrap();
+        }
+
+        let first = TransactionStatus::NotBroadcasted("dropped".to_string());
+        let second = TransactionStatus::Success("0x1".to_string());
+        izar.outbox.push("t1".to_string(), first.clone()).unwrap();
+        izar.outbox.push("t1".to_string(), second.clone()).unwrap();
+        izar.outbox.push("t2".to_string(), second.clone()).unwrap();
+        izar.outbox.push("unknown".to_string(), second.clone()).unwrap();
+
+        // the failed patch of t1 holds back its later one only, the refused one is not retried
+        seq.fail("patch", 1);
+        assert_eq!(izar.deliver_outbox().await.unwrap(), 2);
+        let rejected = izar.outbox.rejected().values().map(|r| r.into_owned()).collect::<Vec<_>>();
+        assert_eq!(rejected.len(), 1);
+        assert_eq!((rejected[0].request.from_chain_tx_hash.as_str(), rejected[0].status), ("unknown", 404));
+
+        // due again, t1 goes out in the order its patches were made
+        for (key, mut entry) in izar.outbox.entries().get_all().unwrap() {
+            assert_eq!(entry.request.from_chain_tx_hash, "t1");
+            entry.next_at = 0;
+            izar.outbox.entries().insert(key, entry).unwrap();
+        }
+        assert_eq!(izar.deliver_outbox().await.unwrap(), 0);
+        let patches = seq.snapshot().patches.into_iter().map(|p| (p.from_chain_tx_hash, p.status)).collect::<Vec<_>>();
+        let expected = [("t2", second.clone()), ("t1", first), ("t1", second)];
+        assert_eq!(patches, expected.map(|(hash, status)| (hash.to_string(), status)));
+    }
+}
@@ -0,0 +1,200 @@
+use std::{collections::HashMap, sync::Arc};
+
+use async_trait::async_trait;
+use izar_core::{network::IzarNetwork, types::transaction::IzarTransaction};
+use serde::Deserialize;
+
+use crate::Operator;
+
+/// What an execution is expected to spend on the destination chain, in the smallest unit of `asset`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Cost {
+    pub asset: String,
+    pub amount: u128,
+}
+
+impl Cost {
+    pub fn native(chain_id: u32, amount: u128) -> Self {
+        Self { asset: native_asset(chain_id), amount }
+    }
+}
+
+// assets are keyed as `{izar chain id}:{token address}`, the gas currency of a chain is `{izar chain id}:native`
+pub fn native_asset(chain_id: u32) -> String {
+    format!("{chain_id}:native")
+}
+
+pub fn fee_asset(tx: &IzarTransaction) -> String {
+    format!("{}:{}", tx.from_chain_id, tx.from_asset_addr.to_lowercase())
+}
+
+/// Converts amounts of any fee or gas currency into a common value.
+#[async_trait]
+pub trait PriceSource: Send + Sync {
+    async fn value(&self, asset: &str, amount: u128) -> anyhow::Result<f64>;
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct AssetPrice {
+    pub price: f64,
+    pub decimals: u32,
+}
+
+/// Prices read once from a toml file, `[assets."1:native"] price = 3000.0 decimals = 18`.
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct StaticPrices {
+    assets: HashMap<String, AssetPrice>,
+}
+
+impl StaticPrices {
+    pub fn load(path: &str) -> anyhow::Result<Self> {
+        let prices: StaticPrices = toml::from_str(&std::fs::read_to_string(path)?)?;
+        Ok(Self { assets: prices.assets.into_iter().map(|(k, v)| (k.to_lowercase(), v)).collect() })
+    }
+}
+
+#[async_trait]
+impl PriceSource for StaticPrices {
+    async fn value(&self, asset: &str, amount: u128) -> anyhow::Result<f64> {
+        let AssetPrice { price, decimals } =
+            self.assets.get(&asset.to_lowercase()).ok_or(anyhow::anyhow!("no price for {asset}"))?;
+        Ok(amount as f64 / 10f64.powi(*decimals as i32) * price)
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProfitAction {
+    /// Executes regardless of the fee.
+    #[default]
+    Execute,
+    /// Keeps the transfer aside and checks it again later.
+    Defer,
+    /// Keeps the transfer aside until an operator looks at it.
+    Flag,
+}
+
+/// Applies to transfers from `from` to `to`, a missing side matches any chain.
+#[derive(Debug, Clone, Deserialize)]
+pub struct RoutePolicy {
+    pub from: Option<u32>,
+    pub to: Option<u32>,
+    pub action: ProfitAction,
+    /// Fee value over cost value the transfer has to reach.
+    #[serde(default = "default_min_ratio")]
+    pub min_ratio: f64,
+}
+
+fn default_min_ratio() -> f64 {
+    1.0
+}
+
+impl RoutePolicy {
+    fn matches(&self, tx: &IzarTransaction) -> bool {
+        self.from.map_or(true, |from| from == tx.from_chain_id) && self.to.map_or(true, |to| to == tx.to_chain_id)
+    }
+
+    fn judge(&self, fee: f64, cost: f64) -> Verdict {
+        if fee >= cost * self.min_ratio {
+            return Verdict::Execute;
+        }
+        let reason = format!("fee worth {fee:.6} does not cover {:.2}x of cost worth {cost:.6}", self.min_ratio);
+        match self.action {
+            ProfitAction::Execute => Verdict::Execute,
+            ProfitAction::Defer => Verdict::Defer(reason),
+            ProfitAction::Flag => Verdict::Flag(reason),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum Verdict {
+    Execute,
+    Defer(String),
+    Flag(String),
+}
+
+#[derive(Clone)]
+pub struct Profitability {
+    prices: Arc<dyn PriceSource>,
+    routes: Vec<RoutePolicy>,
+    defer_secs: u64,
+}
+
+impl Default for Profitability {
+    fn default() -> Self {
+        Self::new(Arc::new(StaticPrices::default()), vec![], 300)
+    }
+}
+
+impl Profitability {
+    pub fn new(prices: Arc<dyn PriceSource>, routes: Vec<RoutePolicy>, defer_secs: u64) -> Self {
+        Self { prices, routes, defer_secs }
+    }
+
+    pub fn defer_secs(&self) -> u64 {
+        self.defer_secs
+    }
+
+    /// The first matching route decides. Anything that can't be priced is executed, as it was before the check.
+    pub async fn check<I: IzarNetwork>(&self, op: &dyn Operator<I>, tx: &IzarTransaction) -> Verdict {
+        let policy = match self.routes.iter().find(|r| r.matches(tx)) {
+            Some(policy) if policy.action != ProfitAction::Execute => policy,
+            _ => return Verdict::Execute,
+        };
+
+        match self.values(op, tx).await {
+            Ok(Some((fee, cost))) => policy.judge(fee, cost),
+            Ok(None) => Verdict::Execute,
+            Err(e) => {
+                tracing::warn!("failed to price tx {}, executing: {:?}", tx.from_chain_tx_hash, e);
+                Verdict::Execute
+            }
+        }
+    }
+
+    async fn values<I: IzarNetwork>(
+        &self,
+        op: &dyn Operator<I>,
+        tx: &IzarTransaction,
+    ) -> anyhow::Result<Option<(f64, f64)>> {
+        let Cost { asset, amount } = match op.estimate_cost(tx).await? {
+            Some(cost) => cost,
+            None => return Ok(None),
+        };
+        let fee = self.prices.value(&fee_asset(tx), tx.fee.parse()?).await?;
+        let cost = self.prices.value(&asset, amount).await?;
+        Ok(Some((fee, cost)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::{PriceSource, ProfitAction, RoutePolicy, StaticPrices, Verdict};
+
+    #[tokio::test]
+    async fn test_static_prices() {
+        let prices: StaticPrices = toml::from_str(
+            r#"
+            [assets."1:native"]
+            price = 2000.0
+            decimals = 18
+            "#,
+        )
+        .unwrap();
+
+        let value = prices.value("1:native", 5 * 10u128.pow(17)).await.unwrap();
+        assert!((value - 1000.0).abs() < 1e-9);
+        assert!(prices.value("2:native", 1).await.is_err());
+    }
+
+    #[test]
+    fn test_route_judge() {
+        let policy = RoutePolicy { from: None, to: Some(2), action: ProfitAction::Defer, min_ratio: 1.5 };
+        assert_eq!(policy.judge(3.0, 2.0), Verdict::Execute);
+        assert!(matches!(policy.judge(2.9, 2.0), Verdict::Defer(_)));
+
+        let policy = RoutePolicy { action: ProfitAction::Flag, ..policy };
+        assert!(matches!(policy.judge(1.0, 2.0), Verdict::Flag(_)));
+    }
+}
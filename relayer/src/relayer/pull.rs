@@ -0,0 +1,214 @@
+use std::{path::Path, sync::Arc, time::Duration};
+
+use izar_core::{
+    db::{map::DBMap, RocksDB},
+    network::IzarNetwork,
+    sequencer::ReadyTx,
+};
+use serde::Deserialize;
+
+use super::IzarRelayer;
+
+const CURSOR_PREFIX: &str = "pull_cursor";
+const CURSOR_KEY: &str = "last_id";
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct PullConfig {
+    /// seconds between polls once caught up, 10 by default
+    pub interval: Option<u64>,
+    /// transfers asked for per poll, 100 by default
+    pub limit: Option<u64>,
+}
+
+/// Polls the sequencer for ready transfers a failed or missed push left behind.
+pub struct Puller {
+    interval: Duration,
+    limit: u64,
+    cursor: DBMap<String, u64>,
+}
+
+impl Puller {
+    pub fn new(config: PullConfig, db_path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Ok(Self {
+            interval: Duration::from_secs(config.interval.unwrap_or(10)),
+            limit: config.limit.unwrap_or(100).max(1),
+            cursor: RocksDB::open_map(db_path, CURSOR_PREFIX)?,
+        })
+    }
+
+    /// Id of the last pulled transfer, 0 before the first pull.
+    pub fn cursor(&self) -> anyhow::Result<u64> {
+        Ok(self.cursor.get(&CURSOR_KEY.to_string())?.unwrap_or_default())
+    }
+
+    /// Pulls again from after `id`, for a sequencer whose ids started over.
+    pub fn rewind(&self, id: u64) -> anyhow::Result<()> {
+        self.cursor.insert(CURSOR_KEY.to_string(), id)
+    }
+}
+
+impl<I: IzarNetwork> IzarRelayer<I> {
+    pub async fn pull_loop(self_: Arc<IzarRelayer<I>>) {
+        let Some(puller) = self_.puller.as_ref() else {
+            return;
+        };
+        loop {
+            match self_.pull_once(puller).await {
+                // a full page means more are waiting, catch up without sleeping
+                Ok(pulled) if pulled == puller.limit => continue,
+                Ok(_) => {}
+                Err(e) => tracing::error!("failed to pull ready txs: {:?}", e),
+            }
+            tokio::time::sleep(puller.interval).await;
+        }
+    }
+
+    /// Queues one page of ready transfers the way `/exec` does, returns how many of them were past the cursor.
+    pub(crate) async fn pull_once(&self, puller: &Puller) -> anyhow::Result<u64> {
+        let after = puller.cursor()?;
+        let mut ready = self.sequencer.ready(after, puller.limit).await?;
+        ready.sort_by_key(|r| r.id);
+        // a sequencer answering what was pulled already must not keep the loop from sleeping
+        ready.retain(|r| r.id > after);
+
+        let pulled = ready.len() as u64;
+        let mut last = after;
+        for ReadyTx { id, tx } in ready {
+            let hash = tx.from_chain_tx_hash.clone();
+            match self.ingest(tx) {
+                Ok((_, ingested)) if !ingested.duplicate => tracing::info!("pulled tx {hash} queued"),
+                Ok(_) => {}
+                // the store failed, this one has to be pulled again
+                Err(e) if e.status.is_server_error() => {
+                    puller.cursor.insert(CURSOR_KEY.to_string(), last)?;
+                    anyhow::bail!("pulled tx {hash} not queued: {}", e.message);
+                }
+                // pushed or pulled, an invalid transfer stays invalid
+                Err(e) => tracing::warn!("pulled tx {hash} rejected, {}: {}", e.code, e.message),
+            }
+            last = id;
+        }
+        if last > after {
+            puller.cursor.insert(CURSOR_KEY.to_string(), last)?;
+            tracing::info!("pulled {pulled} ready txs up to id {last}");
+        }
+        Ok(pulled)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use axum::{routing::get, Json, Router};
+    use ethers::signers::LocalWallet;
+    use izar_core::{
+        network::{eth::EthNetwork, testnet::IzarTestnet, IzarNetwork},
+        sequencer::{ReadyTx, SequencerClient},
+        types::transaction::IzarTransaction,
+    };
+    use izar_mock_sequencer::{MockConfig, MockSequencer};
+    use izar_testkit::transfer::TransferBuilder;
+
+    use super::PullConfig;
+    use crate::{
+        relayer::{
+            types::{IzarPendingTransaction, ReceiveState},
+            IzarRelayer,
+        },
+        Operator,
+    };
+
+    type Sepolia = <IzarTestnet as IzarNetwork>::Sepolia;
+    const SEPOLIA: u32 = Sepolia::IZAR_CHAIN_ID;
+
+    // takes whatever is pulled, nothing executes here
+    struct Idle;
+
+    #[async_trait]
+    impl Operator<IzarTestnet> for Idle {
+        async fn execute(&self, tx: IzarTransaction) -> anyhow::Result<IzarTransaction> {
+            Ok(tx)
+        }
+
+        fn pending(&self, _tx: IzarTransaction) -> anyhow::Result<IzarPendingTransaction<IzarTestnet>> {
+            anyhow::bail!("never executed")
+        }
+
+        async fn received(&self, _tx: &IzarTransaction) -> anyhow::Result<ReceiveState> {
+            Ok(ReceiveState::NotReceived)
+        }
+    }
+
+    fn transfer(hash: &str) -> IzarTransaction {
+        TransferBuilder::new(hash, 2, SEPOLIA)
+            .with_from("aleo1asset", "aleo1from")
+            .with_to("0xasset", "0xto")
+            .with_certificate("voter1", "sign1")
+            .build()
+    }
+
+    fn relayer(url: String, db: &tempfile::TempDir) -> IzarRelayer<IzarTestnet> {
+        let sequencer = SequencerClient::new(vec![url], Duration::from_secs(5)).unwrap();
+        let mut izar = IzarRelayer::new(0, sequencer, db.path());
+        izar.insert_operator(SEPOLIA, Box::new(Idle));
+        izar.set_pull(PullConfig { interval: Some(0), limit: Some(2) }).unwrap();
+        izar
+    }
+
+    #[tokio::test]
+    async fn test_pull_pages() {
+        let seq = MockSequencer::new(MockConfig { threshold: Some(1), ..Default::default() });
+        let url = format!("http://{}", seq.clone().spawn(([127, 0, 0, 1], 0).into()).await.unwrap());
+        let db = tempfile::tempdir().unwrap();
+        let izar = relayer(url, &db);
+        let puller = izar.puller.as_ref().unwrap();
+
+        let key = LocalWallet::new(&mut rand::thread_rng());
+        for i in 0..5 {
+            izar.sequencer().submit_certificates(&transfer(&format!("t{i}")), &key).await.unwrap();
+        }
+
+        // an unavailable sequencer leaves the cursor where it was
+        seq.fail("ready", 1);
+        assert!(izar.pull_once(puller).await.is_err());
+        assert_eq!(puller.cursor().unwrap(), 0);
+
+        let mut pages = vec![];
+        loop {
+            let pulled = izar.pull_once(puller).await.unwrap();
+            pages.push(pulled);
+            if pulled < 2 {
+                break;
+            }
+        }
+        assert_eq!(pages, [2, 2, 1]);
+        assert_eq!(puller.cursor().unwrap(), 5);
+        assert_eq!(izar.store().index().get_all().unwrap().len(), 5);
+        assert_eq!(izar.pull_once(puller).await.unwrap(), 0);
+
+        // pulled again after a rewind, they are known already
+        puller.rewind(3).unwrap();
+        assert_eq!(izar.pull_once(puller).await.unwrap(), 2);
+        assert_eq!(izar.store().index().get_all().unwrap().len(), 5);
+    }
+
+    #[tokio::test]
+    async fn test_stale_page() {
+        // a sequencer that answers the same full page whatever the cursor
+        let page = (1..=2).map(|id| ReadyTx { id, tx: transfer(&format!("t{id}")) }).collect::<Vec<_>>();
+        let router = Router::new().route("/api/v1/BridgeTx/ready", get(move || async move { Json(page.clone()) }));
+        let listener = tokio::net::TcpListener::bind(("127.0.0.1", 0)).await.unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        tokio::spawn(async move { axum::serve(listener, router).await });
+        let db = tempfile::tempdir().unwrap();
+        let izar = relayer(url, &db);
+        let puller = izar.puller.as_ref().unwrap();
+
+        assert_eq!(izar.pull_once(puller).await.unwrap(), 2);
+        // nothing past the cursor is not a full page, the loop sleeps
+        assert_eq!(izar.pull_once(puller).await.unwrap(), 0);
+        assert_eq!(puller.cursor().unwrap(), 2);
+    }
+}
@@ -0,0 +1,155 @@
+use std::sync::Arc;
+
+use axum::{
+    extract::{
+        rejection::{PathRejection, QueryRejection},
+        Path, Query, State,
+    },
+    Json,
+};
+use izar_core::{network::IzarNetwork, types::transaction::IzarTransaction};
+use serde::{Deserialize, Serialize};
+use utoipa::{IntoParams, ToSchema};
+
+use super::{
+    ingest::ApiError,
+    store::{Located, TxState},
+    IzarRelayer,
+};
+
+const DEFAULT_LIMIT: usize = 50;
+const MAX_LIMIT: usize = 500;
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct TxView {
+    pub from_chain_tx_hash: String,
+    pub state: TxState,
+    pub to_chain_tx_hash: Option<String>,
+    /// fee the execution paid on the destination chain, in its smallest unit, once finalized
+    pub to_chain_fee: Option<String>,
+    /// unix seconds of the last state change, unknown for finalized transfers
+    pub updated_at: Option<u64>,
+    /// the stored transfer, missing only when the store lost its body
+    pub tx: Option<IzarTransaction>,
+}
+
+impl TxView {
+    pub fn new(hash: String, located: Located, to_chain_fee: Option<String>) -> Self {
+        let to_chain_tx_hash = located.tx.as_ref().and_then(|tx| tx.to_chain_tx_hash.clone());
+        Self {
+            from_chain_tx_hash: hash,
+            state: located.state,
+            to_chain_tx_hash,
+            to_chain_fee,
+            updated_at: located.updated_at,
+            tx: located.tx,
+        }
+    }
+
+    pub(crate) fn load<I: IzarNetwork>(izar: &IzarRelayer<I>, hash: String) -> Result<Self, ApiError> {
+        let located =
+            izar.store().locate(&hash).map_err(ApiError::internal)?.ok_or_else(|| ApiError::not_found(&hash))?;
+        let fee = match located.state {
+            TxState::Finalized => izar.store().to_chain_fee(&hash).map_err(ApiError::internal)?,
+            _ => None,
+        };
+        Ok(Self::new(hash, located, fee))
+    }
+}
+
+#[derive(Debug, Clone, Copy, Deserialize, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum Queue {
+    Execute,
+    Pending,
+    Finalize,
+    Deferred,
+    Flagged,
+}
+
+#[derive(Debug, Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ListQuery {
+    pub from_chain: Option<u32>,
+    pub to_chain: Option<u32>,
+    /// entries to skip, 0 by default
+    pub offset: Option<usize>,
+    /// entries to return, 50 by default and 500 at most
+    pub limit: Option<usize>,
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct QueueEntry {
+    pub tx: IzarTransaction,
+    /// unix seconds a deferred transfer waits for
+    pub not_before: Option<u64>,
+}
+
+impl QueueEntry {
+    fn new(tx: IzarTransaction) -> Self {
+        Self { tx, not_before: None }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct Page {
+    pub items: Vec<QueueEntry>,
+    /// offset of the next page, none after the last one
+    pub next_offset: Option<usize>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/tx/{hash}",
+    params(("hash" = String, Path, description = "source chain tx hash")),
+    responses(
+        (status = 200, description = "current state of the transfer", body = TxView),
+        (status = 404, description = "unknown transfer", body = ApiError),
+    )
+)]
+pub async fn get_tx<I: IzarNetwork>(
+    State(izar): State<Arc<IzarRelayer<I>>>,
+    Path(hash): Path<String>,
+) -> Result<Json<TxView>, ApiError> {
+    Ok(Json(TxView::load(&izar, hash)?))
+}
+
+#[utoipa::path(
+    get,
+    path = "/queues/{queue}",
+    params(("queue" = Queue, Path, description = "store to list"), ListQuery),
+    responses(
+        (status = 200, description = "transfers in store order, the execute queue in execution order", body = Page),
+        (status = 400, description = "unknown queue or bad query", body = ApiError),
+    )
+)]
+pub async fn list_queue<I: IzarNetwork>(
+    State(izar): State<Arc<IzarRelayer<I>>>,
+    queue: Result<Path<Queue>, PathRejection>,
+    query: Result<Query<ListQuery>, QueryRejection>,
+) -> Result<Json<Page>, ApiError> {
+    let Path(queue) = queue.map_err(|e| ApiError::malformed(e.body_text()))?;
+    let Query(query) = query.map_err(|e| ApiError::malformed(e.body_text()))?;
+    let store = izar.store();
+    let entries: Box<dyn Iterator<Item = QueueEntry>> = match queue {
+        Queue::Execute => Box::new(store.execute().values().map(|tx| QueueEntry::new(tx.into_owned()))),
+        Queue::Pending => Box::new(store.pending().values().map(|tx| QueueEntry::new(tx.into_owned()))),
+        Queue::Finalize => Box::new(store.finalize().values().map(|tx| QueueEntry::new(tx.into_owned()))),
+        Queue::Flagged => Box::new(store.flagged().values().map(|tx| QueueEntry::new(tx.into_owned()))),
+        Queue::Deferred => {
+            Box::new(store.deferred().values().map(|d| QueueEntry { not_before: Some(d.until), tx: d.into_owned().tx }))
+        }
+    };
+
+    let (offset, limit) = (query.offset.unwrap_or(0), query.limit.unwrap_or(DEFAULT_LIMIT).min(MAX_LIMIT));
+    let mut items = entries
+        .filter(|e| query.from_chain.map_or(true, |c| e.tx.from_chain_id == c))
+        .filter(|e| query.to_chain.map_or(true, |c| e.tx.to_chain_id == c))
+        .skip(offset)
+        .take(limit + 1)
+        .collect::<Vec<_>>();
+    let next_offset = (items.len() > limit).then_some(offset + limit);
+    items.truncate(limit);
+
+    Ok(Json(Page { items, next_offset }))
+}
@@ -1,84 +1,238 @@
-use std::{net::SocketAddr, sync::Arc};
+use std::{
+    fs::File,
+    io::BufReader,
+    net::{IpAddr, Ipv4Addr, SocketAddr},
+    sync::Arc,
+};
 
-use axum::{extract::State, http::StatusCode, response::IntoResponse, routing::post, Json, Router};
+use axum::{
+    extract::{rejection::JsonRejection, State},
+    http::{HeaderName, HeaderValue},
+    middleware,
+    response::IntoResponse,
+    routing::{get, post},
+    Json, Router,
+};
+use axum_server::tls_rustls::RustlsConfig;
 use izar_core::{
     network::IzarNetwork,
-    types::transaction::{IzarTransaction, Priority},
+    types::{
+        cert::Certificate,
+        transaction::{IzarTransaction, Priority},
+    },
 };
+use serde::Deserialize;
 use tower_http::{
-    cors::{Any, CorsLayer},
+    cors::CorsLayer,
     trace::{self, TraceLayer},
 };
 use tracing::Level;
+use utoipa::OpenApi;
+
+use super::{
+    admin::{self, FinalizeRequest},
+    auth::{self, Auth, AuthConfig},
+    ingest::{ApiError, Ingested, SpedUp, SpeedupAction},
+    query::{self, Page, Queue, QueueEntry, TxView},
+    scheduler::{self, SchedulerView, WaitStats},
+    store::TxState,
+    IzarRelayer,
+};
 
-use super::{store::RelayerStore, IzarRelayer};
+#[derive(OpenApi)]
+#[openapi(
+    info(title = "izar relayer"),
+    paths(
+        execute,
+        speedup,
+        query::get_tx,
+        query::list_queue,
+        scheduler::scheduler,
+        admin::requeue,
+        admin::cancel,
+        admin::finalize
+    ),
+    components(schemas(
+        IzarTransaction,
+        Certificate,
+        Priority,
+        Ingested,
+        ApiError,
+        TxState,
+        TxView,
+        Queue,
+        QueueEntry,
+        Page,
+        FinalizeRequest,
+        SpedUp,
+        SpeedupAction,
+        SchedulerView,
+        WaitStats
+    ))
+)]
+pub struct ApiDoc;
+
+#[derive(Debug, Clone, Default, Deserialize)]
+pub struct RestConfig {
+    /// address to listen on, all interfaces by default
+    pub bind: Option<IpAddr>,
+    #[serde(default)]
+    pub auth: AuthConfig,
+    pub tls: Option<TlsConfig>,
+    /// origins browsers may call from, none by default
+    #[serde(default)]
+    pub cors: Vec<String>,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct TlsConfig {
+    pub cert: String,
+    pub key: String,
+    /// pem bundle of CAs client certificates must chain to, clients are not asked for one without it
+    pub client_ca: Option<String>,
+}
+
+impl TlsConfig {
+    fn rustls(&self) -> anyhow::Result<RustlsConfig> {
+        let certs = read_pem(&self.cert)?
+            .into_iter()
+            .filter_map(|item| match item {
+                rustls_pemfile::Item::X509Certificate(der) => Some(rustls::Certificate(der)),
+                _ => None,
+            })
+            .collect::<Vec<_>>();
+        let key = read_pem(&self.key)?
+            .into_iter()
+            .find_map(|item| match item {
+                rustls_pemfile::Item::PKCS8Key(der)
+                | rustls_pemfile::Item::RSAKey(der)
+                | rustls_pemfile::Item::ECKey(der) => Some(rustls::PrivateKey(der)),
+                _ => None,
+            })
+            .ok_or_else(|| anyhow::anyhow!("no private key in {}", self.key))?;
+
+        let builder = rustls::ServerConfig::builder().with_safe_defaults();
+        let builder = match &self.client_ca {
+            Some(client_ca) => {
+                let mut roots = rustls::RootCertStore::empty();
+                for item in read_pem(client_ca)? {
+                    if let rustls_pemfile::Item::X509Certificate(der) = item {
+                        roots.add(&rustls::Certificate(der))?;
+                    }
+                }
+                builder.with_client_cert_verifier(rustls::server::AllowAnyAuthenticatedClient::new(roots).boxed())
+            }
+            None => builder.with_no_client_auth(),
+        };
+        let mut config = builder.with_single_cert(certs, key)?;
+        config.alpn_protocols = vec![b"h2".to_vec(), b"http/1.1".to_vec()];
+        Ok(RustlsConfig::from_config(Arc::new(config)))
+    }
+}
+
+fn read_pem(path: &str) -> anyhow::Result<Vec<rustls_pemfile::Item>> {
+    let mut reader = BufReader::new(File::open(path)?);
+    Ok(rustls_pemfile::read_all(&mut reader)?)
+}
 
 impl<I: IzarNetwork> IzarRelayer<I> {
     pub async fn serve(self_: Arc<IzarRelayer<I>>) -> anyhow::Result<()> {
+        let config = self_.rest.clone();
+        config.auth.check()?;
+        let auth = Arc::new(Auth::new(config.auth));
+        if auth.is_open() {
+            tracing::warn!("rest server is insecure, every caller is accepted");
+        }
+
+        let origins = config.cors.iter().map(|o| o.parse::<HeaderValue>()).collect::<Result<Vec<_>, _>>()?;
         let cors = CorsLayer::new()
-            .allow_origin(Any)
+            .allow_origin(origins)
             .allow_methods([axum::http::Method::GET, axum::http::Method::POST, axum::http::Method::OPTIONS])
-            .allow_headers([axum::http::header::CONTENT_TYPE]);
+            .allow_headers([
+                axum::http::header::CONTENT_TYPE,
+                axum::http::header::AUTHORIZATION,
+                HeaderName::from_static(auth::KEY_HEADER),
+                HeaderName::from_static(auth::TIMESTAMP_HEADER),
+                HeaderName::from_static(auth::SIGNATURE_HEADER),
+            ]);
 
-        let store = self_.store().clone();
         let router = Router::new()
-            .route("/exec", post(execute))
-            .route("/speedup", post(speedup))
-            .with_state(store)
+            .route("/exec", post(execute::<I>))
+            .route("/speedup", post(speedup::<I>))
+            .route("/tx/:hash", get(query::get_tx::<I>))
+            .route("/queues/:queue", get(query::list_queue::<I>))
+            .route("/scheduler", get(scheduler::scheduler::<I>))
+            .route("/admin/tx/:hash/requeue", post(admin::requeue::<I>))
+            .route("/admin/tx/:hash/cancel", post(admin::cancel::<I>))
+            .route("/admin/tx/:hash/finalize", post(admin::finalize::<I>))
+            .route_layer(middleware::from_fn_with_state(auth, auth::authenticate))
+            .route("/openapi.json", get(|| async { Json(ApiDoc::openapi()) }))
+            .with_state(self_.clone())
             .layer(cors)
             .layer(
                 TraceLayer::new_for_http()
                     .make_span_with(trace::DefaultMakeSpan::new().level(Level::INFO))
                     .on_response(trace::DefaultOnResponse::new().level(Level::INFO)),
             );
+        let service = router.into_make_service_with_connect_info::<SocketAddr>();
 
-        let addr = SocketAddr::from(([0, 0, 0, 0], self_.port));
-        tracing::info!("rest server listening on {}", addr);
-        let listener = tokio::net::TcpListener::bind(addr).await?;
-        axum::serve(listener, router.into_make_service()).await?;
+        let addr = SocketAddr::new(config.bind.unwrap_or(IpAddr::V4(Ipv4Addr::UNSPECIFIED)), self_.port);
+        match config.tls {
+            Some(tls) => {
+                tracing::info!(
+                    "rest server listening on {} with tls, client certificates: {}",
+                    addr,
+                    tls.client_ca.is_some()
+                );
+                axum_server::bind_rustls(addr, tls.rustls()?).serve(service).await?;
+            }
+            None => {
+                tracing::info!("rest server listening on {}", addr);
+                let listener = tokio::net::TcpListener::bind(addr).await?;
+                axum::serve(listener, service).await?;
+            }
+        }
         Ok(())
     }
 }
 
-async fn execute(State(store): State<RelayerStore>, Json(tx): Json<IzarTransaction>) -> impl IntoResponse {
-    // check if already finalized
-    match store.finalize().get(&tx.from_chain_tx_hash) {
-        Ok(Some(tx)) => {
-            tracing::info!("tx already finalized: {:?}", tx);
-            (StatusCode::OK, tx.to_chain_tx_hash.unwrap()).into_response()
-        }
-        Err(e) => {
-            tracing::error!("failed to get tx from db: {}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
-        }
-        Ok(None) => {
-            if let Err(e) = store.execute().insert(tx.order_key(), tx.clone()) {
-                tracing::error!("failed to insert tx to db: {}", e);
-                return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
-            }
-            (StatusCode::ACCEPTED, format!("already added to queue: {tx:?}")).into_response()
-        }
-    }
+#[utoipa::path(
+    post,
+    path = "/exec",
+    request_body = IzarTransaction,
+    responses(
+        (status = 202, description = "queued", body = Ingested),
+        (status = 200, description = "already known, `state` tells where it is", body = Ingested),
+        (status = 400, description = "body is not a transfer", body = ApiError),
+        (status = 422, description = "invalid transfer, route or certificates", body = ApiError),
+    )
+)]
+async fn execute<I: IzarNetwork>(
+    State(izar): State<Arc<IzarRelayer<I>>>,
+    tx: Result<Json<IzarTransaction>, JsonRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Json(tx) = tx.map_err(|e| ApiError::malformed(e.body_text()))?;
+    let (status, ingested) = izar.ingest(tx)?;
+    Ok((status, Json(ingested)))
 }
 
-async fn speedup(State(store): State<RelayerStore>, Json(mut tx): Json<IzarTransaction>) -> impl IntoResponse {
-    tx.priority = Priority::High;
-    // check if already finalized
-    match store.finalize().get(&tx.from_chain_tx_hash) {
-        Ok(Some(tx)) => {
-            tracing::info!("tx already finalized: {:?}", tx);
-            (StatusCode::OK, tx.to_chain_tx_hash.unwrap()).into_response()
-        }
-        Err(e) => {
-            tracing::error!("failed to get tx from db: {}", e);
-            (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response()
-        }
-        Ok(None) => {
-            if let Err(e) = store.execute().insert(tx.order_key(), tx.clone()) {
-                tracing::error!("failed to insert tx to db: {}", e);
-                return (StatusCode::INTERNAL_SERVER_ERROR, e.to_string()).into_response();
-            }
-            (StatusCode::ACCEPTED, format!("already added to queue: {tx:?}")).into_response()
-        }
-    }
+#[utoipa::path(
+    post,
+    path = "/speedup",
+    request_body = IzarTransaction,
+    responses(
+        (status = 202, description = "unknown before, queued with high priority", body = SpedUp),
+        (status = 200, description = "known already, `action` tells what was done", body = SpedUp),
+        (status = 400, description = "body is not a transfer", body = ApiError),
+        (status = 409, description = "executing, flagged, being bumped, or not replaceable", body = ApiError),
+        (status = 422, description = "invalid transfer, route or certificates", body = ApiError),
+    )
+)]
+async fn speedup<I: IzarNetwork>(
+    State(izar): State<Arc<IzarRelayer<I>>>,
+    tx: Result<Json<IzarTransaction>, JsonRejection>,
+) -> Result<impl IntoResponse, ApiError> {
+    let Json(tx) = tx.map_err(|e| ApiError::malformed(e.body_text()))?;
+    let (status, sped_up) = izar.speedup(tx).await?;
+    Ok((status, Json(sped_up)))
 }
@@ -0,0 +1,257 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    sync::{Arc, Mutex},
+};
+
+use axum::{extract::State, Json};
+use izar_core::{metrics::relayer::RELAYER_QUEUE_WAIT, network::IzarNetwork, types::transaction::Priority};
+use serde::Serialize;
+use utoipa::ToSchema;
+
+use super::{store::Queued, IzarRelayer};
+
+const CLASSES: [Priority; 3] = [Priority::High, Priority::Medium, Priority::Low];
+// waits kept per priority class for the percentiles
+const WAIT_WINDOW: usize = 1024;
+
+fn class(priority: Priority) -> usize {
+    match priority {
+        Priority::High => 0,
+        Priority::Medium => 1,
+        Priority::Low => 2,
+    }
+}
+
+/// Picks the next transfer to execute.
+///
+/// Source chains share the executor by weight, a chain with weight 3 gets three turns for every turn of a chain with
+/// weight 1. Within a chain transfers go by priority, and with `aging_secs` set a waiting transfer climbs one
+/// priority class for every `aging_secs` it waited.
+pub struct Scheduler {
+    aging_secs: Option<u64>,
+    weights: HashMap<u32, u32>,
+    state: Mutex<SchedulerState>,
+}
+
+#[derive(Default)]
+struct SchedulerState {
+    // virtual time of the last turn, a chain coming back from idle starts there instead of spending old credit
+    vtime: f64,
+    pass: HashMap<u32, f64>,
+    waits: [VecDeque<u64>; 3],
+}
+
+impl SchedulerState {
+    // virtual time the next turn of `chain_id` starts at
+    fn start(&self, chain_id: u32) -> f64 {
+        self.pass.get(&chain_id).copied().unwrap_or(0.0).max(self.vtime)
+    }
+}
+
+impl Default for Scheduler {
+    fn default() -> Self {
+        Self::new(None, HashMap::new())
+    }
+}
+
+impl Scheduler {
+    pub fn new(aging_secs: Option<u64>, weights: HashMap<u32, u32>) -> Self {
+        Self { aging_secs: aging_secs.filter(|a| *a > 0), weights, state: Mutex::new(SchedulerState::default()) }
+    }
+
+    pub fn weight(&self, chain_id: u32) -> u32 {
+        self.weights.get(&chain_id).copied().unwrap_or(1).max(1)
+    }
+
+    /// Priority class `tx` competes in at `now`, 0 is the highest.
+    pub fn effective_class(&self, tx: &Queued, now: u64) -> usize {
+        let climbed = self.aging_secs.map_or(0, |aging| now.saturating_sub(tx.timestamp) / aging);
+        class(tx.priority).saturating_sub(climbed.min(CLASSES.len() as u64) as usize)
+    }
+
+    /// Returns the most urgent transfer of the source chain whose turn is next among `candidates`. The turn is only
+    /// used up by `charge`, a transfer that does not execute leaves it to the chain.
+    pub fn pick<'a>(&self, candidates: impl Iterator<Item = &'a Queued>, now: u64) -> Option<Queued> {
+        let rank = |tx: &Queued| (self.effective_class(tx, now), tx.timestamp, tx.key.clone());
+        let mut heads: HashMap<u32, &Queued> = HashMap::new();
+        for tx in candidates {
+            match heads.get(&tx.from_chain_id) {
+                Some(head) if rank(head) <= rank(tx) => {}
+                _ => {
+                    heads.insert(tx.from_chain_id, tx);
+                }
+            }
+        }
+
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let (_, tx) = heads.into_iter().min_by(|(a, ta), (b, tb)| {
+            state.start(*a).total_cmp(&state.start(*b)).then_with(|| rank(ta).cmp(&rank(tb)))
+        })?;
+        Some(tx.clone())
+    }
+
+    /// Uses up the turn of `chain_id` once the transfer `pick` chose for it executes.
+    pub fn charge(&self, chain_id: u32) {
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let turn = state.start(chain_id);
+        state.vtime = turn;
+        state.pass.insert(chain_id, turn + 1.0 / self.weight(chain_id) as f64);
+    }
+
+    /// Records how long `tx` waited before it was taken for execution.
+    pub fn record_wait(&self, tx: &Queued, now: u64) {
+        let wait = now.saturating_sub(tx.timestamp);
+        let label = format!("{:?}", tx.priority).to_lowercase();
+        metrics::histogram!(RELAYER_QUEUE_WAIT, "priority" => label).record(wait as f64);
+
+        let mut state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        let waits = &mut state.waits[class(tx.priority)];
+        if waits.len() == WAIT_WINDOW {
+            waits.pop_front();
+        }
+        waits.push_back(wait);
+    }
+
+    /// Wait percentiles of the last executions of each priority class.
+    pub fn waits(&self) -> Vec<WaitStats> {
+        let state = self.state.lock().unwrap_or_else(|e| e.into_inner());
+        CLASSES.iter().map(|p| WaitStats::new(*p, state.waits[class(*p)].iter().copied().collect())).collect()
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct WaitStats {
+    pub priority: Priority,
+    /// executions the percentiles cover, the last 1024 at most
+    pub samples: usize,
+    /// seconds from the source transfer to its execution, none without samples
+    pub p50: Option<u64>,
+    pub p90: Option<u64>,
+    pub p99: Option<u64>,
+}
+
+impl WaitStats {
+    fn new(priority: Priority, mut waits: Vec<u64>) -> Self {
+        waits.sort_unstable();
+        let percentile = |p: usize| match waits.len() {
+            0 => None,
+            n => Some(waits[((n * p).div_ceil(100)).clamp(1, n) - 1]),
+        };
+        Self { priority, samples: waits.len(), p50: percentile(50), p90: percentile(90), p99: percentile(99) }
+    }
+}
+
+#[derive(Debug, Serialize, ToSchema)]
+pub struct SchedulerView {
+    pub aging_secs: Option<u64>,
+    /// source chain weights, chains not listed have weight 1
+    pub weights: HashMap<u32, u32>,
+    pub waits: Vec<WaitStats>,
+}
+
+#[utoipa::path(
+    get,
+    path = "/scheduler",
+    responses((status = 200, description = "scheduler settings and queue wait percentiles", body = SchedulerView))
+)]
+pub async fn scheduler<I: IzarNetwork>(State(izar): State<Arc<IzarRelayer<I>>>) -> Json<SchedulerView> {
+    let scheduler = &izar.scheduler;
+    Json(SchedulerView {
+        aging_secs: scheduler.aging_secs,
+        weights: scheduler.weights.clone(),
+        waits: scheduler.waits(),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::collections::HashMap;
+
+    use izar_core::types::transaction::Priority;
+    use izar_testkit::transfer::TransferBuilder;
+
+    use super::Scheduler;
+    use crate::relayer::store::Queued;
+
+    fn tx(hash: &str, from_chain_id: u32, priority: Priority, timestamp: u64) -> Queued {
+        Queued::new(&TransferBuilder::new(hash, from_chain_id, 1).with_priority(priority, timestamp).build(), 0)
+    }
+
+    // picks and executes until the queue is empty, like the executor does
+    fn drain(scheduler: &Scheduler, mut queue: Vec<Queued>, now: u64) -> Vec<String> {
+        let mut order = vec![];
+        while let Some(next) = scheduler.pick(queue.iter(), now) {
+            scheduler.charge(next.from_chain_id);
+            queue.retain(|t| t.from_chain_tx_hash != next.from_chain_tx_hash);
+            order.push(next.from_chain_tx_hash);
+        }
+        order
+    }
+
+    #[test]
+    fn test_priority_aging() {
+        let queue = vec![tx("low", 2, Priority::Low, 100), tx("high", 2, Priority::High, 900)];
+        assert_eq!(drain(&Scheduler::default(), queue.clone(), 1000), ["high", "low"]);
+
+        // 900s at 300s a class lifts the low one to high, and it is older
+        let aging = Scheduler::new(Some(300), HashMap::new());
+        assert_eq!(aging.effective_class(&queue[0], 1000), 0);
+        assert_eq!(aging.effective_class(&queue[1], 1000), 0);
+        assert_eq!(drain(&aging, queue, 1000), ["low", "high"]);
+    }
+
+    #[test]
+    fn test_weighted_fair_share() {
+        let scheduler = Scheduler::new(None, HashMap::from([(3, 3)]));
+        let mut queue = (0..8).map(|i| tx(&format!("a{i}"), 2, Priority::High, i)).collect::<Vec<_>>();
+        queue.extend((0..8).map(|i| tx(&format!("b{i}"), 3, Priority::Low, i)));
+
+        // high priority on chain 2 does not starve chain 3, which gets three turns for each of chain 2
+        let order = drain(&scheduler, queue, 100);
+        let first = order[..8].iter().filter(|h| h.starts_with('b')).count();
+        assert_eq!(first, 6);
+        assert_eq!(order.len(), 16);
+    }
+
+    #[test]
+    fn test_idle_chain_banks_no_credit() {
+        let scheduler = Scheduler::default();
+        let busy = (0..10).map(|i| tx(&format!("a{i}"), 2, Priority::Low, i)).collect::<Vec<_>>();
+        for _ in 0..10 {
+            let next = scheduler.pick(busy.iter(), 100).unwrap();
+            scheduler.charge(next.from_chain_id);
+        }
+
+        // the chain that was idle alternates with the busy one instead of taking ten turns in a row
+        let mut queue = busy;
+        queue.extend((0..4).map(|i| tx(&format!("b{i}"), 3, Priority::Low, 50 + i)));
+        let order = drain(&scheduler, queue, 100);
+        assert!(order[..4].iter().any(|h| h.starts_with('a')));
+        assert!(order[..4].iter().any(|h| h.starts_with('b')));
+    }
+
+    #[test]
+    fn test_turn_kept_until_charged() {
+        let scheduler = Scheduler::default();
+        let queue = vec![tx("a", 2, Priority::Low, 1), tx("b", 3, Priority::Low, 5)];
+        assert_eq!(scheduler.pick(queue.iter(), 100).unwrap().from_chain_tx_hash, "a");
+
+        // "a" was deferred, chain 2 still has the turn and its next transfer gets it
+        let queue = vec![tx("a2", 2, Priority::Low, 3), tx("b", 3, Priority::Low, 5)];
+        assert_eq!(scheduler.pick(queue.iter(), 100).unwrap().from_chain_tx_hash, "a2");
+        scheduler.charge(2);
+        assert_eq!(scheduler.pick(queue.iter(), 100).unwrap().from_chain_tx_hash, "b");
+    }
+
+    #[test]
+    fn test_wait_percentiles() {
+        let scheduler = Scheduler::default();
+        for wait in 1..=100 {
+            scheduler.record_wait(&tx("t", 2, Priority::Medium, 1000 - wait), 1000);
+        }
+        let waits = scheduler.waits();
+        assert_eq!(waits[0].samples, 0);
+        assert_eq!(waits[0].p50, None);
+        assert_eq!((waits[1].samples, waits[1].p50, waits[1].p90, waits[1].p99), (100, Some(50), Some(90), Some(99)));
+    }
+}
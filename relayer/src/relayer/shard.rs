@@ -0,0 +1,200 @@
+use izar_core::network::IzarNetwork;
+use serde::Deserialize;
+use sha2::{Digest, Sha256};
+
+use super::{store::Queued, IzarRelayer};
+
+/// Relayers sharing one sequencer, each transfer is owned by one of them.
+#[derive(Debug, Clone, Deserialize)]
+pub struct ShardConfig {
+    /// names of every relayer in the set, the same list in the same order on all of them
+    pub members: Vec<String>,
+    /// position of this relayer in `members`
+    pub index: usize,
+    /// seconds a transfer waits for its owner before the next member in line takes it, 300 by default
+    pub takeover_secs: Option<u64>,
+}
+
+/// Rendezvous hashing over the member names, so adding or removing a member only moves the transfers it owned.
+#[derive(Debug, Clone)]
+pub struct Shard {
+    members: Vec<String>,
+    index: usize,
+    takeover_secs: u64,
+}
+
+impl Shard {
+    pub fn new(config: ShardConfig) -> anyhow::Result<Self> {
+        if config.index >= config.members.len() {
+            anyhow::bail!("shard index {} out of {} members", config.index, config.members.len());
+        }
+        let takeover_secs = config.takeover_secs.unwrap_or(300).max(1);
+        Ok(Self { members: config.members, index: config.index, takeover_secs })
+    }
+
+    pub fn me(&self) -> &str {
+        &self.members[self.index]
+    }
+
+    /// Members in the order they take a transfer, the owner first.
+    pub fn line(&self, hash: &str) -> Vec<usize> {
+        let mut line = (0..self.members.len()).collect::<Vec<_>>();
+        line.sort_by_cached_key(|i| {
+            std::cmp::Reverse(
+                Sha256::new().chain_update(&self.members[*i]).chain_update([0]).chain_update(hash).finalize(),
+            )
+        });
+        line
+    }
+
+    /// Place of this relayer in the line of `hash`, 0 for the owner.
+    pub fn rank(&self, hash: &str) -> usize {
+        self.line(hash).iter().position(|i| *i == self.index).unwrap_or_default()
+    }
+
+    /// Whether this relayer may execute `hash` queued at `queued_at`, everyone ahead of it had its turn by `now`.
+    pub fn is_turn(&self, hash: &str, queued_at: u64, now: u64) -> bool {
+        now.saturating_sub(queued_at) >= self.rank(hash) as u64 * self.takeover_secs
+    }
+
+    /// Member whose turn it was before this relayer's, none for the owner.
+    pub fn previous(&self, hash: &str) -> Option<&str> {
+        let line = self.line(hash);
+        let rank = line.iter().position(|i| *i == self.index)?;
+        rank.checked_sub(1).map(|r| self.members[line[r]].as_str())
+    }
+}
+
+impl<I: IzarNetwork> IzarRelayer<I> {
+    /// Whether `tx` is this relayer's to execute at `now`, always without a shard.
+    pub(crate) fn is_turn(&self, tx: &Queued, now: u64) -> bool {
+        // the turns count from when this relayer first queued the transfer
+        self.shard.as_ref().map_or(true, |shard| shard.is_turn(&tx.from_chain_tx_hash, tx.queued_at, now))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::Duration;
+
+    use async_trait::async_trait;
+    use izar_core::{network::testnet::IzarTestnet, sequencer::SequencerClient, types::transaction::IzarTransaction};
+    use izar_testkit::transfer::TransferBuilder;
+
+    use super::{Shard, ShardConfig};
+    use crate::{
+        relayer::{
+            store::TxState,
+            types::{IzarPendingTransaction, ReceiveState},
+            IzarRelayer,
+        },
+        Operator,
+    };
+
+    // a destination where every transfer landed already
+    struct Landed;
+
+    #[async_trait]
+    impl Operator<IzarTestnet> for Landed {
+        async fn execute(&self, _tx: IzarTransaction) -> anyhow::Result<IzarTransaction> {
+            anyhow::bail!("executed a received transfer")
+        }
+
+        fn pending(&self, _tx: IzarTransaction) -> anyhow::Result<IzarPendingTransaction<IzarTestnet>> {
+            anyhow::bail!("never executed")
+        }
+
+        async fn received(&self, _tx: &IzarTransaction) -> anyhow::Result<ReceiveState> {
+            Ok(ReceiveState::Received(Some("0xlanded".to_string())))
+        }
+    }
+
+    fn shards(n: usize) -> Vec<Shard> {
+        let members = (0..n).map(|i| format!("relayer-{i}")).collect::<Vec<_>>();
+        (0..n)
+            .map(|index| Shard::new(ShardConfig { members: members.clone(), index, takeover_secs: Some(60) }).unwrap())
+            .collect()
+    }
+
+    #[test]
+    fn test_one_owner() {
+        let shards = shards(3);
+        let mut owned = [0; 3];
+        for i in 0..300 {
+            let hash = format!("0x{i:064x}");
+            let owners = shards.iter().filter(|s| s.is_turn(&hash, 1000, 1000)).map(|s| s.index).collect::<Vec<_>>();
+            assert_eq!(owners.len(), 1);
+            owned[owners[0]] += 1;
+            // every member agrees on the line
+            assert!(shards.iter().all(|s| s.line(&hash) == shards[0].line(&hash)));
+        }
+        assert!(owned.iter().all(|o| *o > 50), "{owned:?}");
+    }
+
+    #[test]
+    fn test_takeover() {
+        let shards = shards(3);
+        let hash = "0xabc";
+        let line = shards[0].line(hash);
+        let (owner, second, third) = (&shards[line[0]], &shards[line[1]], &shards[line[2]]);
+        assert_eq!(owner.previous(hash), None);
+        assert_eq!(second.previous(hash), Some(owner.me()));
+
+        assert!(owner.is_turn(hash, 1000, 1000));
+        assert!(!second.is_turn(hash, 1000, 1059));
+        assert!(second.is_turn(hash, 1000, 1060));
+        assert!(!third.is_turn(hash, 1000, 1060));
+        assert!(third.is_turn(hash, 1000, 1120));
+    }
+
+    #[test]
+    fn test_member_removed() {
+        let all = shards(3);
+        let members = vec!["relayer-0".to_string(), "relayer-1".to_string()];
+        let fewer = (0..2)
+            .map(|index| Shard::new(ShardConfig { members: members.clone(), index, takeover_secs: None }).unwrap())
+            .collect::<Vec<_>>();
+        // transfers of the remaining members keep their owner
+        for i in 0..100 {
+            let hash = format!("{i}");
+            let owner = all[0].line(&hash)[0];
+            if owner < 2 {
+                assert_eq!(fewer[0].line(&hash)[0], owner);
+            }
+        }
+        assert!(Shard::new(ShardConfig { members, index: 2, takeover_secs: None }).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_takeover_received() {
+        let hash = "t1".to_string();
+        let members = vec!["relayer-0".to_string(), "relayer-1".to_string()];
+        let line = shards(2)[0].line(&hash);
+        let relayer = |db: &tempfile::TempDir, index: usize| {
+            let sequencer =
+                SequencerClient::new(vec!["http://127.0.0.1:1".to_string()], Duration::from_secs(1)).unwrap();
+            let mut izar = IzarRelayer::<IzarTestnet>::new(0, sequencer, db.path());
+            izar.insert_operator(1, Box::new(Landed));
+            let takeover_secs = Some(1);
+            izar.set_shard(Shard::new(ShardConfig { members: members.clone(), index, takeover_secs }).unwrap());
+            izar
+        };
+        let (owner_db, second_db) = (tempfile::tempdir().unwrap(), tempfile::tempdir().unwrap());
+        let (owner, second) = (relayer(&owner_db, line[0]), relayer(&second_db, line[1]));
+        for izar in [&owner, &second] {
+            izar.store().enqueue(TransferBuilder::new(&hash, 2, 1).build()).unwrap();
+        }
+
+        // the owner reports what it finds landed
+        assert!(owner.execute_once().await.unwrap());
+        assert_eq!(owner.store().state(&hash).unwrap(), Some(TxState::Finalized));
+        assert!(owner.outbox.entries().iter().next().is_some());
+
+        // the next in line drops it without a report once its turn came
+        tokio::time::sleep(Duration::from_millis(1100)).await;
+        assert!(second.execute_once().await.unwrap());
+        let finalized = second.store().finalize().get(&hash).unwrap().unwrap();
+        assert_eq!(finalized.to_chain_tx_hash.as_deref(), Some("0xlanded"));
+        assert!(second.outbox.entries().iter().next().is_none());
+    }
+}
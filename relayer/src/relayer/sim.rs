@@ -0,0 +1,455 @@
+//! Crash and fault injection for the relayer state machine. Store and outbox writes, seen through a write hook, and
+//! operator calls are numbered steps: a run either crashes the relayer at one chosen step or fails and crashes steps
+//! drawn from a seed, restarts it from the store wherever it crashed, and lets it settle. Every transfer has to end up
+//! finalized with the execution that landed, or reported failed when none did, with nothing left behind. The
+//! destination shows a landing one read late, and names the execution of one transfer only on a later read.
+
+use std::{
+    cell::RefCell,
+    collections::{HashMap, HashSet},
+    sync::{Arc, Mutex, MutexGuard, Once},
+    time::Duration,
+};
+
+use async_trait::async_trait;
+use ethers::{
+    providers::{Http, Provider},
+    signers::LocalWallet,
+    types::{Address, Transaction, TransactionReceipt, H256, U64},
+};
+use izar_core::{
+    network::{eth::EthNetwork, testnet::IzarTestnet, IzarNetwork},
+    sequencer::SequencerClient,
+    types::transaction::IzarTransaction,
+};
+use izar_mock_sequencer::{MockConfig, MockSequencer};
+use izar_testkit::{evm::MockEvm, transfer::TransferBuilder};
+
+use crate::Operator;
+
+use super::{
+    profit::{Profitability, StaticPrices},
+    pull::PullConfig,
+    store::WriteHook,
+    types::{ExecuteError, IzarPendingTransaction, ReceiveState, TransactionStatus},
+    IzarRelayer,
+};
+
+type Sepolia = <IzarTestnet as IzarNetwork>::Sepolia;
+type Relayer = Arc<IzarRelayer<IzarTestnet>>;
+
+const TRANSFERS: usize = 3;
+// steps one execute stage takes at most, a transfer deferred over and over must not hold up the round
+const EXECUTE_STEPS: usize = 20;
+const SETTLE_ROUNDS: usize = 10;
+
+enum Outcome {
+    Pass,
+    Fail,
+    Crash,
+}
+
+/// Panic payload of an injected crash, the relayer is rebuilt from the store after one.
+struct Crashed(&'static str);
+
+struct Plan {
+    step: u64,
+    crash_at: Option<u64>,
+    crashed: bool,
+    // xorshift state failures are drawn from, none for a single crash
+    rng: Option<u64>,
+    fail_one_in: u64,
+}
+
+impl Plan {
+    fn crash_at(step: u64) -> Self {
+        Self { step: 0, crash_at: Some(step), crashed: false, rng: None, fail_one_in: 0 }
+    }
+
+    fn seeded(seed: u64, fail_one_in: u64) -> Self {
+        Self { step: 0, crash_at: None, crashed: false, rng: Some(seed.max(1)), fail_one_in: fail_one_in.max(1) }
+    }
+
+    fn next(&mut self) -> Outcome {
+        self.step += 1;
+        if self.crash_at == Some(self.step) {
+            self.crashed = true;
+            return Outcome::Crash;
+        }
+        let Some(rng) = self.rng.as_mut() else {
+            return Outcome::Pass;
+        };
+        *rng ^= *rng << 13;
+        *rng ^= *rng >> 7;
+        *rng ^= *rng << 17;
+        match *rng % (2 * self.fail_one_in) {
+            0 => {
+                self.crashed = true;
+                Outcome::Crash
+            }
+            1 => Outcome::Fail,
+            _ => Outcome::Pass,
+        }
+    }
+}
+
+thread_local! {
+    // the simulation runs on one thread, tests elsewhere never see a plan
+    static PLAN: RefCell<Option<Plan>> = const { RefCell::new(None) };
+}
+
+fn next_step() -> Outcome {
+    PLAN.with(|plan| plan.borrow_mut().as_mut().map_or(Outcome::Pass, Plan::next))
+}
+
+fn arm(plan: Plan) {
+    PLAN.with(|p| *p.borrow_mut() = Some(plan));
+}
+
+/// Stops injecting, returns whether the plan crashed the relayer.
+fn disarm() -> bool {
+    PLAN.with(|p| p.borrow_mut().take().is_some_and(|plan| plan.crashed))
+}
+
+/// A step that can fail, before a write or a call.
+fn fault(site: &'static str) -> anyhow::Result<()> {
+    match next_step() {
+        Outcome::Pass => Ok(()),
+        Outcome::Fail => anyhow::bail!("injected {site} failure"),
+        Outcome::Crash => std::panic::panic_any(Crashed(site)),
+    }
+}
+
+/// A step right after a write, the relayer can only crash there.
+fn crash_point(site: &'static str) {
+    if let Outcome::Crash = next_step() {
+        std::panic::panic_any(Crashed(site))
+    }
+}
+
+struct Faults;
+
+impl WriteHook for Faults {
+    fn before(&self, site: &'static str) -> anyhow::Result<()> {
+        fault(site)
+    }
+
+    fn after(&self, site: &'static str) {
+        crash_point(site)
+    }
+}
+
+// injected crashes are expected, any other panic still reports
+fn quiet_crashes() {
+    static HOOK: Once = Once::new();
+    HOOK.call_once(|| {
+        let default = std::panic::take_hook();
+        std::panic::set_hook(Box::new(move |info| {
+            if info.payload().downcast_ref::<Crashed>().is_none() {
+                default(info)
+            }
+        }));
+    });
+}
+
+#[derive(Default)]
+struct Chain {
+    executions: u64,
+    // source tx hash to the execution that landed
+    landed: HashMap<String, H256>,
+    // executions sent for a transfer that had landed already
+    repeated: u64,
+    // landed transfers the next read still misses
+    stale: HashSet<String>,
+    stale_reads: u64,
+    // transfers the next landed read answers without the execution that landed them
+    unnamed: HashSet<String>,
+    unnamed_answers: HashSet<String>,
+}
+
+/// Executes on a destination where a transfer lands at most once, as the logic contract sees to.
+struct SimOperator {
+    chain: Arc<Mutex<Chain>>,
+    evm: MockEvm,
+    provider: Provider<Http>,
+}
+
+impl SimOperator {
+    fn chain(&self) -> MutexGuard<'_, Chain> {
+        self.chain.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+#[async_trait]
+impl Operator<IzarTestnet> for SimOperator {
+    async fn execute(&self, mut tx: IzarTransaction) -> anyhow::Result<IzarTransaction> {
+        fault("execute")?;
+        let hash = {
+            let mut chain = self.chain();
+            if chain.landed.contains_key(&tx.from_chain_tx_hash) {
+                chain.repeated += 1;
+                return Err(ExecuteError::Reverted("nonce already used".to_string()).into());
+            }
+            chain.executions += 1;
+            H256::from_low_u64_be(chain.executions)
+        };
+
+        // a rejected execution is mined with a failed receipt and leaves the transfer unreceived
+        let rejected = fault("chain").is_err();
+        self.evm.push_transaction(Transaction { hash, block_number: Some(U64::one()), ..Default::default() });
+        self.evm.push_receipt(TransactionReceipt {
+            transaction_hash: hash,
+            block_number: Some(U64::one()),
+            status: Some(U64::from(u64::from(!rejected))),
+            ..Default::default()
+        });
+        if !rejected {
+            let mut chain = self.chain();
+            chain.landed.insert(tx.from_chain_tx_hash.clone(), hash);
+            chain.stale.insert(tx.from_chain_tx_hash.clone());
+        }
+
+        // sent, but the answer was lost
+        fault("sent")?;
+        tx.to_chain_tx_hash = Some(Sepolia::format_str(hash));
+        Ok(tx)
+    }
+
+    fn pending(&self, tx: IzarTransaction) -> anyhow::Result<IzarPendingTransaction<IzarTestnet>> {
+        fault("pending")?;
+        let hash = tx.to_chain_tx_hash.as_deref().ok_or(anyhow::anyhow!("tx was not executed"))?.parse()?;
+        Ok(IzarPendingTransaction::eth(hash, vec![], self.provider.clone(), 0))
+    }
+
+    async fn received(&self, tx: &IzarTransaction) -> anyhow::Result<ReceiveState> {
+        fault("received")?;
+        let mut chain = self.chain();
+        let hash = &tx.from_chain_tx_hash;
+        if chain.stale.remove(hash) {
+            chain.stale_reads += 1;
+            return Ok(ReceiveState::NotReceived);
+        }
+        Ok(match chain.landed.get(hash).copied() {
+            Some(_) if chain.unnamed.remove(hash) => {
+                chain.unnamed_answers.insert(hash.clone());
+                ReceiveState::Received(None)
+            }
+            Some(landed) => ReceiveState::Received(Some(Sepolia::format_str(landed))),
+            None => ReceiveState::NotReceived,
+        })
+    }
+}
+
+fn transfer(hash: &str) -> IzarTransaction {
+    TransferBuilder::new(hash, 2, Sepolia::IZAR_CHAIN_ID)
+        .with_from("aleo1asset", "aleo1from")
+        .with_to(format!("{:?}", Address::from_low_u64_be(2)), format!("{:?}", Address::from_low_u64_be(1)))
+        .with_nonce(7)
+        .with_certificate("voter1", "sign1")
+        .build()
+}
+
+#[derive(Debug, Clone, Copy)]
+enum Stage {
+    Pull,
+    Execute,
+    Check,
+    Deliver,
+}
+
+// one pass of the loop behind `stage`, its failures are left to the next round as the loop would
+async fn run_stage(izar: Relayer, stage: Stage) -> anyhow::Result<()> {
+    match stage {
+        Stage::Pull => {
+            izar.pull_once(izar.puller.as_ref().unwrap()).await?;
+        }
+        Stage::Execute => {
+            for _ in 0..EXECUTE_STEPS {
+                if !izar.execute_once().await? {
+                    break;
+                }
+            }
+        }
+        Stage::Check => {
+            for (tid, tx) in izar.store().pending().get_all()? {
+                izar.check_pending(&tid, tx).await?;
+            }
+        }
+        Stage::Deliver => {
+            // retries are due right away
+            for (key, mut entry) in izar.outbox.entries().get_all()? {
+                entry.next_at = 0;
+                izar.outbox.entries().insert(key, entry)?;
+            }
+            izar.deliver_outbox().await?;
+        }
+    }
+    Ok(())
+}
+
+/// Transfers made ready on a fresh sequencer for a relayer with an empty store.
+struct Sim {
+    label: String,
+    db: tempfile::TempDir,
+    seq: Arc<MockSequencer>,
+    seq_url: String,
+    evm: MockEvm,
+    chain: Arc<Mutex<Chain>>,
+    hashes: Vec<String>,
+}
+
+impl Sim {
+    async fn start(label: String) -> Self {
+        let seq = MockSequencer::new(MockConfig { threshold: Some(1), ..Default::default() });
+        let seq_url = format!("http://{}", seq.clone().spawn(([127, 0, 0, 1], 0).into()).await.unwrap());
+        let evm = MockEvm::start(Sepolia::ETH_CHAIN_ID as u64, 1).await.unwrap();
+        let hashes = (0..TRANSFERS).map(|i| format!("at1{label}x{i}")).collect::<Vec<_>>();
+        let db = tempfile::tempdir().unwrap();
+        let chain = Chain { unnamed: HashSet::from([hashes[1].clone()]), ..Default::default() };
+        let sim = Self { label, db, seq, seq_url, evm, chain: Arc::new(Mutex::new(chain)), hashes };
+
+        let izar = sim.relayer();
+
+        let key = LocalWallet::new(&mut rand::thread_rng());
+        for hash in sim.hashes.iter() {
+            izar.sequencer().submit_certificates(&transfer(hash), &key).await.unwrap();
+        }
+        sim
+    }
+
+    /// A relayer started on the store as it is, the way a restarted process finds it.
+    fn relayer(&self) -> Relayer {
+        let sequencer = SequencerClient::new(vec![self.seq_url.clone()], Duration::from_secs(5)).unwrap();
+        let mut izar = IzarRelayer::new(0, sequencer, self.db.path());
+        let provider = Provider::<Http>::try_from(self.evm.url()).unwrap().interval(Duration::from_millis(10));
+        let op = SimOperator { chain: self.chain.clone(), evm: self.evm.clone(), provider };
+        izar.insert_operator(Sepolia::IZAR_CHAIN_ID, Box::new(op));
+        izar.set_write_hook(Arc::new(Faults));
+        // deferred transfers are due again right away
+        izar.set_profitability(Profitability::new(Arc::new(StaticPrices::default()), vec![], 0));
+        izar.set_pull(PullConfig { interval: Some(0), limit: Some(2) }).unwrap();
+        Arc::new(izar)
+    }
+
+    /// Every loop once, a relayer that crashed is restarted for the next one.
+    async fn round(&self, izar: &mut Option<Relayer>) {
+        for stage in [Stage::Pull, Stage::Execute, Stage::Check, Stage::Deliver] {
+            let relayer = izar.get_or_insert_with(|| self.relayer()).clone();
+            match tokio::spawn(run_stage(relayer, stage)).await {
+                Ok(_) => {}
+                Err(e) if e.is_panic() => {
+                    let panic = e.into_panic();
+                    let crashed = panic.downcast_ref::<Crashed>().map(|c| c.0);
+                    match crashed {
+                        Some(site) => tracing::info!("{}: relayer crashed at {site} in {stage:?}", self.label),
+                        None => std::panic::resume_unwind(panic),
+                    }
+                    *izar = None;
+                }
+                Err(e) => panic!("{}: {stage:?} stage cancelled: {e}", self.label),
+            }
+        }
+    }
+
+    fn quiet(&self, izar: &Relayer) -> bool {
+        let store = izar.store();
+        let pulled = izar.puller.as_ref().unwrap().cursor().unwrap() == self.seq.snapshot().ready;
+        let empty = store.index().iter().next().is_none() && store.executing().iter().next().is_none();
+        pulled && empty && izar.outbox.entries().iter().next().is_none()
+    }
+
+    /// Rounds until nothing is left to pull, execute, check or patch.
+    async fn settle(&self, izar: &mut Option<Relayer>) {
+        for _ in 0..SETTLE_ROUNDS {
+            self.round(izar).await;
+            if izar.as_ref().is_some_and(|izar| self.quiet(izar)) {
+                return;
+            }
+        }
+        let index = izar.as_ref().map(|izar| izar.store().index().get_all().unwrap());
+        panic!("{}: relayer did not settle, index {:?}", self.label, index);
+    }
+
+    /// Each transfer landed once and was finalized and reported with that execution, or none landed and its
+    /// failure was reported. A transfer the destination named no execution for waits until it does.
+    fn check(&self, izar: &Relayer, all_land: bool) {
+        let label = &self.label;
+        let chain = self.chain.lock().unwrap();
+        // only a read that missed the landing sends it again, the revert that follows is settled as received
+        assert!(chain.repeated <= chain.stale_reads, "{label}: executed again after landing");
+        let state = self.seq.snapshot();
+        for hash in self.hashes.iter() {
+            let finalized = izar.store().finalize().get(hash).unwrap();
+            let status = state.txs[hash].status.clone();
+            match chain.landed.get(hash) {
+                Some(landed) => {
+                    let landed = Sepolia::format_str(*landed);
+                    assert_eq!(finalized.and_then(|tx| tx.to_chain_tx_hash), Some(landed.clone()), "{label}: {hash}");
+                    assert_eq!(status, Some(TransactionStatus::Success(landed)), "{label}: {hash}");
+                }
+                None => {
+                    assert!(!all_land, "{label}: {hash} never landed");
+                    assert!(finalized.is_none(), "{label}: {hash} finalized without landing");
+                    assert!(
+                        matches!(
+                            status,
+                            Some(
+                                TransactionStatus::Rejected(_)
+                                    | TransactionStatus::Reverted(_)
+                                    | TransactionStatus::NotBroadcasted(_)
+                            )
+                        ),
+                        "{label}: {hash} reported {status:?}"
+                    );
+                }
+            }
+        }
+    }
+}
+
+#[tokio::test]
+async fn test_crash_and_faults() {
+    quiet_crashes();
+
+    // a crash at each step in turn, until a run is over before reaching it
+    let mut step = 1;
+    let (mut stale_reads, mut repeated, mut unnamed) = (0, 0, 0);
+    loop {
+        let sim = Sim::start(format!("crash{step}")).await;
+        arm(Plan::crash_at(step));
+        let mut izar = None;
+        sim.settle(&mut izar).await;
+        let crashed = disarm();
+        sim.check(izar.as_ref().unwrap(), true);
+        {
+            let chain = sim.chain.lock().unwrap();
+            stale_reads += chain.stale_reads;
+            repeated += chain.repeated;
+            unnamed += chain.unnamed_answers.len();
+        }
+        if !crashed {
+            break;
+        }
+        step += 1;
+        assert!(step < 1000, "runs never end");
+    }
+    assert!(step > 10 * TRANSFERS as u64, "only {step} steps, the hooks are not reached");
+    // a crash between landing and recording it sends the execution again on a stale read
+    assert!(stale_reads > 0 && repeated > 0, "no stale read raced an execution");
+    assert!(unnamed > 0, "no transfer was received without its execution");
+
+    // failures and crashes drawn from a seed, along with an unavailable sequencer, then a settle without them
+    for seed in 1..=16 {
+        let sim = Sim::start(format!("seed{seed}")).await;
+        sim.seq.fail("ready", 1);
+        sim.seq.fail("patch", 2);
+        arm(Plan::seeded(seed, 5));
+        let mut izar = None;
+        for _ in 0..6 {
+            sim.round(&mut izar).await;
+        }
+        disarm();
+        sim.settle(&mut izar).await;
+        sim.check(izar.as_ref().unwrap(), false);
+    }
+}
@@ -1,28 +1,192 @@
+use std::{
+    collections::{btree_map, BTreeMap},
+    path::Path,
+    sync::{Arc, Mutex, MutexGuard},
+};
+
 use izar_core::{
     db::{map::DBMap, RocksDB},
-    types::transaction::IzarTransaction,
+    types::transaction::{IzarTransaction, Priority},
 };
-
-use crate::DB_PATH;
+use rocksdb::WriteBatch;
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
 
 const EXECUTE_PREFIX: &str = "execute";
 const FINALIZE_PREFIX: &str = "finalize";
 const PENDING_PREFIX: &str = "pending";
+const DEFERRED_PREFIX: &str = "deferred";
+const FLAGGED_PREFIX: &str = "flagged";
+const INDEX_PREFIX: &str = "index";
+// not `executing`, a prefix that starts with another one runs into its map when that is iterated
+const INFLIGHT_PREFIX: &str = "inflight";
+const LEGACY_EXECUTING_PREFIX: &str = "executing";
+const TO_CHAIN_FEE_PREFIX: &str = "to_chain_fee";
+const QUEUED_AT_PREFIX: &str = "queued_at";
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Deferred {
+    /// unix seconds after which the transfer goes back to the execute queue
+    pub until: u64,
+    pub tx: IzarTransaction,
+}
+
+/// Where a transfer is, by its source tx hash. Finalized transfers leave the index for the finalize store,
+/// failed ones leave it altogether so they can be submitted again.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TxState {
+    Queued,
+    Executing,
+    Pending,
+    Deferred,
+    Flagged,
+    Finalized,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct TxIndex {
+    pub state: TxState,
+    /// key of the transfer in the execute or pending store
+    pub key: Vec<u8>,
+    pub updated_at: u64,
+}
+
+/// A transfer found through the index or the finalize store.
+#[derive(Debug, Clone)]
+pub struct Located {
+    pub state: TxState,
+    pub key: Vec<u8>,
+    pub tx: Option<IzarTransaction>,
+    pub updated_at: Option<u64>,
+}
+
+/// What picking the next transfer needs of a queued one.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Queued {
+    /// key of the transfer in the execute store
+    pub key: Vec<u8>,
+    pub from_chain_tx_hash: String,
+    pub from_chain_id: u32,
+    pub to_chain_id: u32,
+    pub priority: Priority,
+    pub timestamp: u64,
+    /// unix seconds this relayer first queued the transfer, moving it around the queue keeps it
+    pub queued_at: u64,
+}
+
+impl Queued {
+    pub fn new(tx: &IzarTransaction, queued_at: u64) -> Self {
+        Self {
+            key: tx.order_key(),
+            from_chain_tx_hash: tx.from_chain_tx_hash.clone(),
+            from_chain_id: tx.from_chain_id,
+            to_chain_id: tx.to_chain_id,
+            priority: tx.priority,
+            timestamp: tx.timestamp,
+            queued_at,
+        }
+    }
+}
+
+/// Runs around every write to the store and the patch outbox, the state machine tests inject faults through it.
+pub trait WriteHook: Send + Sync {
+    /// Before a write to `site`, an error fails the write.
+    fn before(&self, _site: &'static str) -> anyhow::Result<()> {
+        Ok(())
+    }
+    /// Once a write to `site` landed.
+    fn after(&self, _site: &'static str) {}
+}
+
+pub struct NoHook;
+
+impl WriteHook for NoHook {}
 
 #[derive(Clone)]
 pub struct RelayerStore {
     execute: DBMap<Vec<u8>, IzarTransaction>,
     pending: DBMap<Vec<u8>, IzarTransaction>,
     finalize: DBMap<String, IzarTransaction>,
+    deferred: DBMap<String, Deferred>,
+    flagged: DBMap<String, IzarTransaction>,
+    index: DBMap<String, TxIndex>,
+    // transfers the executor took, until they leave `Executing`
+    executing: DBMap<String, IzarTransaction>,
+    // fee each finalized execution paid on its destination chain, in the chain's smallest unit
+    to_chain_fees: DBMap<String, String>,
+    queued_at: DBMap<String, u64>,
+    // the execute store in memory, so picking the next transfer reads nothing. Written after the store, an entry
+    // whose transfer left the queue without it is dropped once the executor finds it gone
+    queue: Arc<Mutex<BTreeMap<Vec<u8>, Queued>>>,
+    hook: Arc<dyn WriteHook>,
 }
 
 impl RelayerStore {
-    pub fn build() -> anyhow::Result<Self> {
-        let execute = RocksDB::open_map(DB_PATH, EXECUTE_PREFIX)?;
-        let finalize = RocksDB::open_map(DB_PATH, FINALIZE_PREFIX)?;
-        let pending = RocksDB::open_map(DB_PATH, PENDING_PREFIX)?;
+    pub fn build(db_path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        let execute = RocksDB::open_map(&db_path, EXECUTE_PREFIX)?;
+        let finalize = RocksDB::open_map(&db_path, FINALIZE_PREFIX)?;
+        let pending = RocksDB::open_map(&db_path, PENDING_PREFIX)?;
+        let deferred = RocksDB::open_map(&db_path, DEFERRED_PREFIX)?;
+        let flagged = RocksDB::open_map(&db_path, FLAGGED_PREFIX)?;
+        let index = RocksDB::open_map(&db_path, INDEX_PREFIX)?;
+        let executing = RocksDB::open_map(&db_path, INFLIGHT_PREFIX)?;
+        let to_chain_fees = RocksDB::open_map(&db_path, TO_CHAIN_FEE_PREFIX)?;
+        let queued_at = RocksDB::open_map(&db_path, QUEUED_AT_PREFIX)?;
 
-        Ok(Self { execute, finalize, pending })
+        let queue = Arc::new(Mutex::new(BTreeMap::new()));
+        let hook = Arc::new(NoHook);
+        let store = Self {
+            execute,
+            finalize,
+            pending,
+            deferred,
+            flagged,
+            index,
+            executing,
+            to_chain_fees,
+            queued_at,
+            queue,
+            hook,
+        };
+        store.migrate(&db_path)?;
+        store.reindex()?;
+        for tx in store.execute.values() {
+            let queued = Queued::new(&tx, store.queued_at(&tx.from_chain_tx_hash)?);
+            store.queue().insert(queued.key.clone(), queued);
+        }
+        Ok(store)
+    }
+
+    // transfers taken before the executing store got a prefix of its own, recovered as any other
+    fn migrate(&self, db_path: impl AsRef<Path>) -> anyhow::Result<()> {
+        let legacy: DBMap<String, IzarTransaction> = RocksDB::open_map(db_path, LEGACY_EXECUTING_PREFIX)?;
+        let taken = legacy.get_all()?;
+        if taken.is_empty() {
+            return Ok(());
+        }
+        RocksDB::atomic_batch(self.index.inner(), |batch| {
+            for (hash, tx) in taken {
+                legacy.delete_append(&hash, batch)?;
+                self.executing.write_append(hash, tx, batch)?;
+            }
+            Ok(())
+        })
+    }
+
+    // stores written before the index existed
+    fn reindex(&self) -> anyhow::Result<()> {
+        if self.index.iter().next().is_some() {
+            return Ok(());
+        }
+        let queued = self.execute.iter().map(|(k, tx)| (TxState::Queued, k.into_owned(), tx.into_owned()));
+        let pending = self.pending.iter().map(|(k, tx)| (TxState::Pending, k.into_owned(), tx.into_owned()));
+        let deferred = self.deferred.values().map(|d| (TxState::Deferred, d.tx.order_key(), d.tx.clone()));
+        let flagged = self.flagged.values().map(|tx| (TxState::Flagged, tx.order_key(), tx.into_owned()));
+        for (state, key, tx) in queued.chain(pending).chain(deferred).chain(flagged) {
+            self.index.insert(tx.from_chain_tx_hash, TxIndex { state, key, updated_at: now() })?;
+        }
+        Ok(())
     }
 
     pub fn execute(&self) -> &DBMap<Vec<u8>, IzarTransaction> {
@@ -36,4 +200,512 @@ impl RelayerStore {
     pub fn pending(&self) -> &DBMap<Vec<u8>, IzarTransaction> {
         &self.pending
     }
+
+    pub fn deferred(&self) -> &DBMap<String, Deferred> {
+        &self.deferred
+    }
+
+    pub fn flagged(&self) -> &DBMap<String, IzarTransaction> {
+        &self.flagged
+    }
+
+    pub fn index(&self) -> &DBMap<String, TxIndex> {
+        &self.index
+    }
+
+    pub fn executing(&self) -> &DBMap<String, IzarTransaction> {
+        &self.executing
+    }
+
+    /// Runs `f` over the queued transfers, in the order of their keys.
+    pub fn queued<T>(&self, f: impl FnOnce(btree_map::Values<'_, Vec<u8>, Queued>) -> T) -> T {
+        f(self.queue().values())
+    }
+
+    // stores written before it was kept count from when they were opened
+    fn queued_at(&self, hash: &String) -> anyhow::Result<u64> {
+        Ok(self.queued_at.get(hash)?.unwrap_or_else(now))
+    }
+
+    pub fn to_chain_fee(&self, hash: &String) -> anyhow::Result<Option<String>> {
+        self.to_chain_fees.get(hash)
+    }
+
+    pub fn state(&self, hash: &String) -> anyhow::Result<Option<TxState>> {
+        if let Some(index) = self.index.get(hash)? {
+            return Ok(Some(index.state));
+        }
+        Ok(self.finalize.contain(hash)?.then_some(TxState::Finalized))
+    }
+
+    pub fn locate(&self, hash: &String) -> anyhow::Result<Option<Located>> {
+        if let Some(TxIndex { state, key, updated_at }) = self.index.get(hash)? {
+            let tx = match state {
+                TxState::Queued => self.execute.get(&key)?,
+                TxState::Pending => self.pending.get(&key)?,
+                TxState::Deferred => self.deferred.get(hash)?.map(|d| d.tx),
+                TxState::Flagged => self.flagged.get(hash)?,
+                TxState::Executing => self.executing.get(hash)?,
+                TxState::Finalized => None,
+            };
+            return Ok(Some(Located { state, key, tx, updated_at: Some(updated_at) }));
+        }
+        let located = self.finalize.get(hash)?.map(|tx| Located {
+            state: TxState::Finalized,
+            key: hash.as_bytes().to_vec(),
+            tx: Some(tx),
+            updated_at: None,
+        });
+        Ok(located)
+    }
+
+    /// Queues a new transfer.
+    pub fn enqueue(&self, tx: IzarTransaction) -> anyhow::Result<()> {
+        let queued = Queued::new(&tx, now());
+        self.batch(|batch| {
+            self.queued_at.write_append(tx.from_chain_tx_hash.clone(), queued.queued_at, batch)?;
+            self.set_state(&tx.from_chain_tx_hash, TxState::Queued, tx.order_key(), batch)?;
+            self.execute.write_append(tx.order_key(), tx, batch)
+        })?;
+        self.queue().insert(queued.key.clone(), queued);
+        Ok(())
+    }
+
+    /// Takes the transfer at `key` off the execute queue for the executor, none when it left the queue meanwhile.
+    pub fn take(&self, key: &Vec<u8>) -> anyhow::Result<Option<IzarTransaction>> {
+        let Some(tx) = self.execute.get(key)? else {
+            self.queue().remove(key);
+            return Ok(None);
+        };
+        Ok(self.start(&tx)?.then_some(tx))
+    }
+
+    /// Takes a transfer off the execute queue for the executor, false when it left the queue meanwhile.
+    pub fn start(&self, tx: &IzarTransaction) -> anyhow::Result<bool> {
+        let key = tx.order_key();
+        match self.index.get(&tx.from_chain_tx_hash)? {
+            Some(index) if index.state == TxState::Queued && index.key == key => {}
+            _ => {
+                self.queue().remove(&key);
+                return Ok(false);
+            }
+        }
+        self.batch(|batch| {
+            self.execute.delete_append(&key, batch)?;
+            self.set_state(&tx.from_chain_tx_hash, TxState::Executing, key.clone(), batch)?;
+            self.executing.write_append(tx.from_chain_tx_hash.clone(), tx.clone(), batch)
+        })?;
+        self.queue().remove(&key);
+        Ok(true)
+    }
+
+    /// Puts the transfers whose execution was cut short back on the execute queue, returns their hashes. Only safe
+    /// while nothing executes, the destination check before executing keeps one that was sent from running twice.
+    pub fn recover(&self) -> anyhow::Result<Vec<String>> {
+        let executing = self.executing.get_all()?;
+        let mut recovered = Vec::with_capacity(executing.len());
+        for (hash, tx) in executing {
+            match self.index.get(&hash)? {
+                Some(index) if index.state == TxState::Executing => {
+                    self.requeue(tx)?;
+                    recovered.push(hash);
+                }
+                _ => self.batch(|batch| self.executing.delete_append(&hash, batch))?,
+            }
+        }
+        Ok(recovered)
+    }
+
+    pub fn submitted(&self, key: Vec<u8>, tx: IzarTransaction) -> anyhow::Result<()> {
+        self.batch(|batch| {
+            self.set_state(&tx.from_chain_tx_hash, TxState::Pending, key.clone(), batch)?;
+            self.pending.write_append(key, tx, batch)
+        })
+    }
+
+    pub fn defer(&self, deferred: Deferred) -> anyhow::Result<()> {
+        let hash = deferred.tx.from_chain_tx_hash.clone();
+        self.batch(|batch| {
+            self.set_state(&hash, TxState::Deferred, deferred.tx.order_key(), batch)?;
+            self.deferred.write_append(hash.clone(), deferred, batch)
+        })
+    }
+
+    pub fn flag(&self, tx: IzarTransaction) -> anyhow::Result<()> {
+        let hash = tx.from_chain_tx_hash.clone();
+        self.batch(|batch| {
+            self.set_state(&hash, TxState::Flagged, tx.order_key(), batch)?;
+            self.flagged.write_append(hash.clone(), tx, batch)
+        })
+    }
+
+    /// Moves a deferred or flagged transfer back to the execute queue.
+    pub fn requeue(&self, tx: IzarTransaction) -> anyhow::Result<()> {
+        let queued = Queued::new(&tx, self.queued_at(&tx.from_chain_tx_hash)?);
+        self.batch(|batch| {
+            self.deferred.delete_append(&tx.from_chain_tx_hash, batch)?;
+            self.flagged.delete_append(&tx.from_chain_tx_hash, batch)?;
+            self.set_state(&tx.from_chain_tx_hash, TxState::Queued, tx.order_key(), batch)?;
+            self.execute.write_append(tx.order_key(), tx, batch)
+        })?;
+        self.queue().insert(queued.key.clone(), queued);
+        Ok(())
+    }
+
+    pub fn finish(&self, tx: IzarTransaction) -> anyhow::Result<()> {
+        self.batch(|batch| self.finish_append(tx, batch))
+    }
+
+    pub fn finish_append(&self, tx: IzarTransaction, batch: &mut WriteBatch) -> anyhow::Result<()> {
+        self.clear(&tx.from_chain_tx_hash, batch)?;
+        self.finalize.write_append(tx.from_chain_tx_hash.clone(), tx, batch)
+    }
+
+    /// Finalizes an executed transfer along with the `fee` its execution paid, when the chain reported it.
+    pub fn settle_append(
+        &self,
+        tx: IzarTransaction,
+        fee: Option<String>,
+        batch: &mut WriteBatch,
+    ) -> anyhow::Result<()> {
+        if let Some(fee) = fee {
+            self.to_chain_fees.write_append(tx.from_chain_tx_hash.clone(), fee, batch)?;
+        }
+        self.finish_append(tx, batch)
+    }
+
+    /// Removes a waiting, executing or pending transfer, a new submission of it is accepted again.
+    pub fn withdraw(&self, hash: &String, located: &Located) -> anyhow::Result<()> {
+        self.batch(|batch| {
+            self.remove_from(hash, located, batch)?;
+            self.queued_at.delete_append(hash, batch)?;
+            self.index.delete_append(hash, batch)
+        })?;
+        if located.state == TxState::Queued {
+            self.queue().remove(&located.key);
+        }
+        Ok(())
+    }
+
+    /// Finalizes a waiting or pending transfer as `tx`, without executing it.
+    pub fn force_finish_append(
+        &self,
+        located: &Located,
+        tx: IzarTransaction,
+        batch: &mut WriteBatch,
+    ) -> anyhow::Result<()> {
+        self.remove_from(&tx.from_chain_tx_hash, located, batch)?;
+        self.queued_at.delete_append(&tx.from_chain_tx_hash, batch)?;
+        self.index.delete_append(&tx.from_chain_tx_hash, batch)?;
+        self.finalize.write_append(tx.from_chain_tx_hash.clone(), tx, batch)
+    }
+
+    fn remove_from(&self, hash: &String, located: &Located, batch: &mut WriteBatch) -> anyhow::Result<()> {
+        match located.state {
+            TxState::Queued => self.execute.delete_append(&located.key, batch),
+            TxState::Pending => self.pending.delete_append(&located.key, batch),
+            TxState::Deferred => self.deferred.delete_append(hash, batch),
+            TxState::Flagged => self.flagged.delete_append(hash, batch),
+            TxState::Executing => self.executing.delete_append(hash, batch),
+            TxState::Finalized => anyhow::bail!("tx {hash} is {:?}", located.state),
+        }
+    }
+
+    /// Drops a failed transfer, a new submission of it is accepted again.
+    pub fn forget(&self, hash: &String) -> anyhow::Result<()> {
+        self.batch(|batch| self.clear(hash, batch))
+    }
+
+    pub fn forget_append(&self, hash: &String, batch: &mut WriteBatch) -> anyhow::Result<()> {
+        self.clear(hash, batch)
+    }
+
+    /// Bumps the fee of a pending transfer, `tx` carries the hash of the replacement.
+    pub fn replace_pending(&self, located: &Located, tx: IzarTransaction) -> anyhow::Result<()> {
+        self.batch(|batch| {
+            self.set_state(&tx.from_chain_tx_hash, TxState::Pending, located.key.clone(), batch)?;
+            self.pending.write_append(located.key.clone(), tx, batch)
+        })
+    }
+
+    /// Moves a queued transfer to the place `tx`, with its new priority, takes in the queue.
+    pub fn reorder(&self, located: &Located, tx: IzarTransaction) -> anyhow::Result<()> {
+        let queued = Queued::new(&tx, self.queued_at(&tx.from_chain_tx_hash)?);
+        self.batch(|batch| {
+            self.execute.delete_append(&located.key, batch)?;
+            self.set_state(&tx.from_chain_tx_hash, TxState::Queued, tx.order_key(), batch)?;
+            self.execute.write_append(tx.order_key(), tx, batch)
+        })?;
+        let mut queue = self.queue();
+        queue.remove(&located.key);
+        queue.insert(queued.key.clone(), queued);
+        Ok(())
+    }
+
+    // pending txs are kept until they settle
+    fn clear(&self, hash: &String, batch: &mut WriteBatch) -> anyhow::Result<()> {
+        if let Some(TxIndex { state: TxState::Pending, key, .. }) = self.index.get(hash)? {
+            self.pending.delete_append(&key, batch)?;
+        }
+        self.executing.delete_append(hash, batch)?;
+        self.queued_at.delete_append(hash, batch)?;
+        self.index.delete_append(hash, batch)
+    }
+
+    fn set_state(&self, hash: &String, state: TxState, key: Vec<u8>, batch: &mut WriteBatch) -> anyhow::Result<()> {
+        if state != TxState::Executing {
+            self.executing.delete_append(hash, batch)?;
+        }
+        self.index.write_append(hash.clone(), TxIndex { state, key, updated_at: now() }, batch)
+    }
+
+    fn queue(&self) -> MutexGuard<'_, BTreeMap<Vec<u8>, Queued>> {
+        self.queue.lock().unwrap_or_else(|e| e.into_inner())
+    }
+
+    pub(crate) fn set_hook(&mut self, hook: Arc<dyn WriteHook>) {
+        self.hook = hook;
+    }
+
+    fn batch(&self, f: impl FnOnce(&mut WriteBatch) -> anyhow::Result<()>) -> anyhow::Result<()> {
+        self.hook.before("store")?;
+        RocksDB::atomic_batch(self.index.inner(), f)?;
+        self.hook.after("store");
+        Ok(())
+    }
+}
+
+pub(crate) fn now() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use izar_core::{
+        db::{map::DBMap, RocksDB},
+        types::transaction::{IzarTransaction, Priority},
+    };
+    use izar_testkit::transfer::TransferBuilder;
+
+    use super::{
+        Deferred, RelayerStore, TxState, DEFERRED_PREFIX, EXECUTE_PREFIX, FINALIZE_PREFIX, FLAGGED_PREFIX,
+        INDEX_PREFIX, INFLIGHT_PREFIX, LEGACY_EXECUTING_PREFIX, PENDING_PREFIX, QUEUED_AT_PREFIX, TO_CHAIN_FEE_PREFIX,
+    };
+
+    fn tx(hash: &str) -> IzarTransaction {
+        TransferBuilder::new(hash, 2, 1).with_priority(Priority::Low, 100).build()
+    }
+
+    #[test]
+    fn test_lifecycle() {
+        let db = tempfile::tempdir().unwrap();
+        let store = RelayerStore::build(db.path()).unwrap();
+        let hash = "t1".to_string();
+
+        store.enqueue(tx("t1")).unwrap();
+        assert_eq!(store.state(&hash).unwrap(), Some(TxState::Queued));
+        assert!(store.start(&tx("t1")).unwrap());
+        // taken already
+        assert!(!store.start(&tx("t1")).unwrap());
+        let located = store.locate(&hash).unwrap().unwrap();
+        assert_eq!((located.state, located.tx), (TxState::Executing, Some(tx("t1"))));
+        assert!(store.execute().get_all().unwrap().is_empty());
+
+        let mut sent = tx("t1");
+        sent.to_chain_tx_hash = Some("0xsent".to_string());
+        store.submitted(sent.order_key(), sent.clone()).unwrap();
+        assert_eq!(store.state(&hash).unwrap(), Some(TxState::Pending));
+        assert!(store.executing().get_all().unwrap().is_empty());
+
+        let mut bumped = sent.clone();
+        bumped.to_chain_tx_hash = Some("0xbumped".to_string());
+        store.replace_pending(&store.locate(&hash).unwrap().unwrap(), bumped.clone()).unwrap();
+        assert_eq!(store.pending().get(&sent.order_key()).unwrap(), Some(bumped.clone()));
+
+        let fee = Some("21000".to_string());
+        RocksDB::atomic_batch(store.index().inner(), |batch| store.settle_append(bumped.clone(), fee, batch)).unwrap();
+        let located = store.locate(&hash).unwrap().unwrap();
+        assert_eq!((located.state, located.tx, located.updated_at), (TxState::Finalized, Some(bumped), None));
+        assert_eq!(store.to_chain_fee(&hash).unwrap(), Some("21000".to_string()));
+        assert!(store.pending().get_all().unwrap().is_empty());
+        assert!(store.index().get_all().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_defer_flag_withdraw() {
+        let db = tempfile::tempdir().unwrap();
+        let store = RelayerStore::build(db.path()).unwrap();
+        let hash = "t1".to_string();
+
+        store.enqueue(tx("t1")).unwrap();
+        assert!(store.start(&tx("t1")).unwrap());
+        store.defer(Deferred { until: 10, tx: tx("t1") }).unwrap();
+        assert_eq!(store.state(&hash).unwrap(), Some(TxState::Deferred));
+        assert!(store.executing().get_all().unwrap().is_empty());
+
+        store.requeue(tx("t1")).unwrap();
+        assert_eq!(store.state(&hash).unwrap(), Some(TxState::Queued));
+        assert!(store.deferred().get_all().unwrap().is_empty());
+
+        assert!(store.start(&tx("t1")).unwrap());
+        store.flag(tx("t1")).unwrap();
+        let located = store.locate(&hash).unwrap().unwrap();
+        assert_eq!((located.state, located.tx.clone()), (TxState::Flagged, Some(tx("t1"))));
+
+        // a withdrawn transfer is unknown again
+        store.withdraw(&hash, &located).unwrap();
+        assert_eq!(store.state(&hash).unwrap(), None);
+        assert!(store.flagged().get_all().unwrap().is_empty());
+        store.enqueue(tx("t1")).unwrap();
+        assert_eq!(store.state(&hash).unwrap(), Some(TxState::Queued));
+    }
+
+    #[test]
+    fn test_reorder() {
+        let db = tempfile::tempdir().unwrap();
+        let store = RelayerStore::build(db.path()).unwrap();
+        let hash = "t1".to_string();
+
+        store.enqueue(tx("t1")).unwrap();
+        let located = store.locate(&hash).unwrap().unwrap();
+        let mut high = tx("t1");
+        high.priority = Priority::High;
+        store.reorder(&located, high.clone()).unwrap();
+
+        let queued = store.execute().get_all().unwrap();
+        assert_eq!(queued, vec![(high.order_key(), high.clone())]);
+        assert_eq!(store.locate(&hash).unwrap().unwrap().key, high.order_key());
+        // the old key does not start it any more
+        assert!(!store.start(&tx("t1")).unwrap());
+        assert!(store.start(&high).unwrap());
+    }
+
+    #[test]
+    fn test_queue() {
+        let db = tempfile::tempdir().unwrap();
+        let store = RelayerStore::build(db.path()).unwrap();
+        let keys = |store: &RelayerStore| store.queued(|queued| queued.map(|q| q.key.clone()).collect::<Vec<_>>());
+
+        store.enqueue(tx("t1")).unwrap();
+        store.enqueue(tx("t2")).unwrap();
+        let mut high = tx("t2");
+        high.priority = Priority::High;
+        store.reorder(&store.locate(&"t2".to_string()).unwrap().unwrap(), high.clone()).unwrap();
+        assert_eq!(keys(&store), vec![high.order_key(), tx("t1").order_key()]);
+
+        // a transfer finalized by hand stays in memory until the executor finds it gone
+        let located = store.locate(&"t1".to_string()).unwrap().unwrap();
+        RocksDB::atomic_batch(store.index().inner(), |batch| store.force_finish_append(&located, tx("t1"), batch))
+            .unwrap();
+        assert_eq!(store.take(&tx("t1").order_key()).unwrap(), None);
+        assert_eq!(store.take(&high.order_key()).unwrap(), Some(high));
+        assert!(keys(&store).is_empty());
+
+        // reopened, the queue is read back from the store
+        store.recover().unwrap();
+        assert_eq!(keys(&RelayerStore::build(db.path()).unwrap()).len(), 1);
+    }
+
+    #[test]
+    fn test_queued_at_kept() {
+        let db = tempfile::tempdir().unwrap();
+        let store = RelayerStore::build(db.path()).unwrap();
+        let hash = "t1".to_string();
+        let queued_at = |store: &RelayerStore| store.queued(|mut queued| queued.next().map(|q| q.queued_at));
+
+        store.enqueue(tx("t1")).unwrap();
+        store.queued_at.insert(hash.clone(), 5).unwrap();
+        let mut high = tx("t1");
+        high.priority = Priority::High;
+        store.reorder(&store.locate(&hash).unwrap().unwrap(), high.clone()).unwrap();
+        assert_eq!(queued_at(&store), Some(5));
+
+        assert!(store.start(&high).unwrap());
+        store.defer(Deferred { until: 10, tx: high.clone() }).unwrap();
+        store.requeue(high.clone()).unwrap();
+        assert!(store.start(&high).unwrap());
+        store.recover().unwrap();
+        assert_eq!(queued_at(&store), Some(5));
+        assert_eq!(queued_at(&RelayerStore::build(db.path()).unwrap()), Some(5));
+
+        // a transfer submitted again after it left counts from then
+        store.forget(&hash).unwrap();
+        assert_eq!(store.queued_at.get(&hash).unwrap(), None);
+    }
+
+    #[test]
+    fn test_recover() {
+        let db = tempfile::tempdir().unwrap();
+        let store = RelayerStore::build(db.path()).unwrap();
+
+        store.enqueue(tx("t1")).unwrap();
+        store.enqueue(tx("t2")).unwrap();
+        assert!(store.start(&tx("t1")).unwrap());
+        assert!(store.start(&tx("t2")).unwrap());
+        store.forget(&"t2".to_string()).unwrap();
+
+        // a forgotten transfer is not brought back
+        assert_eq!(store.recover().unwrap(), vec!["t1".to_string()]);
+        assert_eq!(store.state(&"t1".to_string()).unwrap(), Some(TxState::Queued));
+        assert_eq!(store.state(&"t2".to_string()).unwrap(), None);
+        assert!(store.executing().get_all().unwrap().is_empty());
+        assert!(store.recover().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_reindex() {
+        let db = tempfile::tempdir().unwrap();
+        {
+            let store = RelayerStore::build(db.path()).unwrap();
+            store.execute().insert(tx("t1").order_key(), tx("t1")).unwrap();
+            store.pending().insert(tx("t2").order_key(), tx("t2")).unwrap();
+            store.flagged().insert("t3".to_string(), tx("t3")).unwrap();
+        }
+
+        // a store written before the index had one gets it on open
+        let store = RelayerStore::build(db.path()).unwrap();
+        assert_eq!(store.state(&"t1".to_string()).unwrap(), Some(TxState::Queued));
+        assert_eq!(store.state(&"t2".to_string()).unwrap(), Some(TxState::Pending));
+        assert_eq!(store.state(&"t3".to_string()).unwrap(), Some(TxState::Flagged));
+    }
+
+    #[test]
+    fn test_prefixes() {
+        let prefixes = [
+            EXECUTE_PREFIX,
+            FINALIZE_PREFIX,
+            PENDING_PREFIX,
+            DEFERRED_PREFIX,
+            FLAGGED_PREFIX,
+            INDEX_PREFIX,
+            INFLIGHT_PREFIX,
+            TO_CHAIN_FEE_PREFIX,
+            QUEUED_AT_PREFIX,
+        ];
+        for a in prefixes {
+            for b in prefixes {
+                assert!(a == b || !b.starts_with(a), "{b} runs into {a}");
+            }
+        }
+    }
+
+    #[test]
+    fn test_migrate_executing() {
+        let db = tempfile::tempdir().unwrap();
+        {
+            let store = RelayerStore::build(db.path()).unwrap();
+            store.enqueue(tx("t1")).unwrap();
+            store.enqueue(tx("t2")).unwrap();
+            assert!(store.start(&tx("t1")).unwrap());
+            // where a relayer from before the rename kept it
+            let legacy: DBMap<String, IzarTransaction> = RocksDB::open_map(db.path(), LEGACY_EXECUTING_PREFIX).unwrap();
+            legacy.insert("t1".to_string(), tx("t1")).unwrap();
+            store.executing().remove(&"t1".to_string()).unwrap();
+        }
+
+        let store = RelayerStore::build(db.path()).unwrap();
+        assert_eq!(store.executing().get(&"t1".to_string()).unwrap(), Some(tx("t1")));
+        assert_eq!(store.recover().unwrap(), vec!["t1".to_string()]);
+        assert_eq!(store.execute().get_all().unwrap().len(), 2);
+    }
 }
@@ -1,59 +1,43 @@
-use std::sync::Arc;
-
 use aleo_rust::Network;
 use aleo_rust::Transaction;
-use axum::response::IntoResponse;
 use backon::ExponentialBuilder;
 use backon::Retryable;
 use ethers::{
-    prelude::SignerMiddleware,
     providers::{Http, Middleware, PendingTransaction, Provider},
-    signers::LocalWallet,
-    types::H256,
+    types::{TransactionReceipt, H256},
 };
 use izar_core::network::IzarNetwork;
-use serde::ser::SerializeStruct;
+pub use izar_core::sequencer::{PatchRequest, TransactionStatus};
 use serde::Serialize;
 
+/// Failures an operator detects before broadcasting, they never reach the destination chain.
 #[derive(Debug, Clone)]
-pub enum TransactionStatus {
-    Success(String),
-    NotBroadcasted(String),
-    Rejected(String),
+pub enum ExecuteError {
+    Reverted(String),
 }
 
-impl Serialize for TransactionStatus {
-    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
-    where
-        S: serde::Serializer,
-    {
+impl std::fmt::Display for ExecuteError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
         match self {
-            TransactionStatus::Success(tx_hash) => {
-                let mut s = serializer.serialize_struct("status", 2)?;
-                s.serialize_field("code", &1)?;
-                s.serialize_field("result", tx_hash)?;
-                s.end()
-            }
-            TransactionStatus::NotBroadcasted(e) => {
-                let mut s = serializer.serialize_struct("status", 2)?;
-                s.serialize_field("code", &2)?;
-                s.serialize_field("result", e)?;
-                s.end()
-            }
-            TransactionStatus::Rejected(e) => {
-                let mut s = serializer.serialize_struct("status", 2)?;
-                s.serialize_field("code", &3)?;
-                s.serialize_field("result", e)?;
-                s.end()
-            }
+            ExecuteError::Reverted(reason) => write!(f, "execution reverted: {}", reason),
         }
     }
 }
 
-impl IntoResponse for TransactionStatus {
-    fn into_response(self) -> axum::response::Response {
-        axum::response::Json(self).into_response()
-    }
+impl std::error::Error for ExecuteError {}
+
+/// Destination side view of a transfer, `Received` carries the destination tx hash when it can be found.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ReceiveState {
+    NotReceived,
+    Received(Option<String>),
+}
+
+/// Balance of one relayer key, in the smallest unit of the chain's fee currency.
+#[derive(Debug, Clone, Serialize)]
+pub struct KeyBalance {
+    pub address: String,
+    pub balance: u128,
 }
 
 #[derive(Debug, Clone)]
@@ -67,15 +51,12 @@ impl<I: IzarNetwork> IzarPendingTransaction<I> {
         Self::Aleo(AleoPendingTx { tx_hash, client, base_url: base_url.to_string() })
     }
 
-    pub fn eth(
-        tx_hash: H256,
-        client: Arc<SignerMiddleware<Provider<Http>, LocalWallet>>,
-        confirmations: usize,
-    ) -> Self {
-        Self::Eth(EthPendingTx { tx_hash, client, confirmations })
+    pub fn eth(tx_hash: H256, replaced: Vec<H256>, provider: Provider<Http>, confirmations: usize) -> Self {
+        Self::Eth(EthPendingTx { tx_hash, replaced, provider, confirmations })
     }
 
-    pub async fn checking(self) -> TransactionStatus {
+    /// Waits for the outcome of the execution, along with the fee it paid when that is known.
+    pub async fn checking(self) -> (TransactionStatus, Option<String>) {
         match self {
             Self::Aleo(a) => {
                 let AleoPendingTx { tx_hash, client, base_url } = a;
@@ -86,20 +67,36 @@ impl<I: IzarNetwork> IzarPendingTransaction<I> {
                 };
                 let receipt = fut.retry(&ExponentialBuilder::default().with_max_times(10));
                 match receipt.await {
-                    Ok(receipt) if receipt.is_fee() => {
-                        TransactionStatus::Rejected("tx was rejected by onchain finalize".to_string())
+                    Ok(receipt) => {
+                        let fee = receipt.fee_amount().ok().map(|fee| (*fee).to_string());
+                        match receipt.is_fee() {
+                            true => {
+                                (TransactionStatus::Rejected("tx was rejected by onchain finalize".to_string()), fee)
+                            }
+                            false => (TransactionStatus::Success(tx_hash.to_string()), fee),
+                        }
                     }
-                    Ok(_) => TransactionStatus::Success(tx_hash.to_string()),
-                    Err(e) => TransactionStatus::NotBroadcasted(format!("tx not broadcasted: {}", e)),
+                    Err(e) => (TransactionStatus::NotBroadcasted(format!("tx not broadcasted: {}", e)), None),
                 }
             }
             Self::Eth(e) => {
-                let pending_tx = PendingTransaction::new(e.tx_hash, e.client.provider()).confirmations(e.confirmations);
-                match pending_tx.await {
-                    Ok(r) => {
-                        TransactionStatus::Success(format!("{:#020x}", r.expect("must have receipt").transaction_hash))
-                    }
-                    Err(e) => TransactionStatus::Rejected(format!("tx execute error: {}", e)),
+                let pending_tx = PendingTransaction::new(e.tx_hash, &e.provider).confirmations(e.confirmations);
+                let receipt = match pending_tx.await {
+                    Ok(Some(r)) => r,
+                    // dropped, which is what happens to a tx once one it replaced or that replaced it lands
+                    Ok(None) => match e.landed_replacement().await {
+                        Some(r) => r,
+                        None => {
+                            return (TransactionStatus::NotBroadcasted("tx dropped from mempool".to_string()), None)
+                        }
+                    },
+                    Err(e) => return (TransactionStatus::Rejected(format!("tx execute error: {}", e)), None),
+                };
+                let fee =
+                    receipt.gas_used.zip(receipt.effective_gas_price).map(|(gas, price)| (gas * price).to_string());
+                match receipt.status.map(|s| s.as_u64()) {
+                    Some(0) => (TransactionStatus::Rejected("tx reverted onchain".to_string()), fee),
+                    _ => (TransactionStatus::Success(format!("{:#020x}", receipt.transaction_hash)), fee),
                 }
             }
         }
@@ -122,24 +119,27 @@ impl<N: Network> std::fmt::Debug for AleoPendingTx<N> {
 #[derive(Clone)]
 pub struct EthPendingTx {
     pub tx_hash: H256,
+    /// earlier txs at the same nonce, replaced by fee bumps
+    pub replaced: Vec<H256>,
     pub confirmations: usize,
-    pub client: Arc<SignerMiddleware<Provider<Http>, LocalWallet>>,
+    pub provider: Provider<Http>,
 }
 
-impl std::fmt::Debug for EthPendingTx {
-    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-        f.debug_struct("EthPendingTx").field("tx_hash", &self.tx_hash).finish()
+impl EthPendingTx {
+    async fn landed_replacement(&self) -> Option<TransactionReceipt> {
+        for hash in self.replaced.iter() {
+            match self.provider.get_transaction_receipt(*hash).await {
+                Ok(Some(receipt)) => return Some(receipt),
+                Ok(None) => {}
+                Err(e) => tracing::warn!("failed to get receipt of {:?}: {}", hash, e),
+            }
+        }
+        None
     }
 }
 
-#[derive(Debug, Clone, Serialize)]
-pub struct PatchRequest {
-    from_chain_tx_hash: String,
-    status: TransactionStatus,
-}
-
-impl PatchRequest {
-    pub fn new(from_chain_tx_hash: String, status: TransactionStatus) -> Self {
-        Self { from_chain_tx_hash, status }
+impl std::fmt::Debug for EthPendingTx {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("EthPendingTx").field("tx_hash", &self.tx_hash).field("replaced", &self.replaced).finish()
     }
 }
@@ -0,0 +1,347 @@
+use std::{
+    collections::{BTreeMap, HashMap, VecDeque},
+    fmt::Display,
+    net::SocketAddr,
+    path::{Path, PathBuf},
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use aleo_rust::{Block, Network, Transaction};
+use axum::{
+    extract::{Path as UrlPath, Query, State},
+    http::StatusCode,
+    response::{IntoResponse, Response},
+    routing::{get, post},
+    Json, Router,
+};
+use serde::Deserialize;
+use serde_json::Value;
+
+// what the node allows for one `blocks` request
+const MAX_BLOCK_RANGE: u32 = 50;
+
+/// The file next to the fixture blocks listing what each of them holds, it is not a block itself.
+pub const FIXTURE_TXS: &str = "expected.json";
+
+/// Where real testnet blocks are kept for the Aleo tests, see the README there for capturing more.
+pub fn fixtures_dir() -> PathBuf {
+    Path::new(env!("CARGO_MANIFEST_DIR")).join("fixtures").join("aleo")
+}
+
+/// Ids of the `cross_public` and `speed_up_eth` transactions a fixture block holds, read off the explorer when it
+/// was captured.
+#[derive(Debug, Default, Deserialize)]
+pub struct FixtureTxs {
+    #[serde(default)]
+    pub transfers: Vec<String>,
+    #[serde(default)]
+    pub speedups: Vec<String>,
+}
+
+/// The transactions of every fixture block in `dir` by height, as listed in its `expected.json`.
+pub fn fixture_txs(dir: &Path) -> anyhow::Result<BTreeMap<u32, FixtureTxs>> {
+    let path = dir.join(FIXTURE_TXS);
+    if !path.exists() {
+        return Ok(BTreeMap::new());
+    }
+    serde_json::from_slice(&std::fs::read(&path)?).map_err(|e| anyhow::anyhow!("{path:?}: {e}"))
+}
+
+#[derive(Default)]
+struct Node {
+    height: u32,
+    blocks: BTreeMap<u32, Value>,
+    // served at heights without a block of their own
+    empty_block: Option<Value>,
+    transactions: HashMap<String, Value>,
+    // program, mapping and key to the plaintext the node holds
+    mappings: HashMap<(String, String, String), String>,
+    broadcast: Vec<Value>,
+    errors: HashMap<String, VecDeque<(StatusCode, String)>>,
+    delays: HashMap<String, Duration>,
+    requests: HashMap<String, usize>,
+}
+
+impl Node {
+    /// Counts the request of `route` and answers the error queued for it, if any.
+    fn request(&mut self, route: &str) -> Result<(), (StatusCode, String)> {
+        *self.requests.entry(route.to_string()).or_default() += 1;
+        match self.errors.get_mut(route).and_then(VecDeque::pop_front) {
+            Some(e) => Err(e),
+            None => Ok(()),
+        }
+    }
+
+    fn block(&self, height: u32) -> Option<Value> {
+        if height > self.height {
+            return None;
+        }
+        self.blocks.get(&height).or(self.empty_block.as_ref()).cloned()
+    }
+
+    fn insert_block(&mut self, block: Value) -> anyhow::Result<u32> {
+        let height = block
+            .pointer("/header/metadata/height")
+            .and_then(Value::as_u64)
+            .ok_or_else(|| anyhow::anyhow!("block without a height"))? as u32;
+        let transactions = block.get("transactions").and_then(Value::as_array).cloned().unwrap_or_default();
+        for confirmed in transactions {
+            if let Some(tx) = confirmed.get("transaction") {
+                self.insert_transaction(tx.clone());
+            }
+        }
+        self.blocks.insert(height, block);
+        self.height = self.height.max(height);
+        Ok(height)
+    }
+
+    fn insert_transaction(&mut self, tx: Value) -> Option<String> {
+        let id = tx.get("id").and_then(Value::as_str)?.to_string();
+        self.transactions.insert(id.clone(), tx);
+        Some(id)
+    }
+}
+
+/// An Aleo node REST API in the test process, serving `testnet3` the way `AleoAPIClient` reads it: the latest height,
+/// blocks and block ranges, transactions, program mappings and broadcast. Blocks are pushed typed or loaded from
+/// fixture files, heights without a block serve the one set with `set_empty_block`.
+#[derive(Clone)]
+pub struct MockAleo {
+    url: String,
+    node: Arc<Mutex<Node>>,
+}
+
+#[derive(Debug, Deserialize)]
+struct Range {
+    start: u32,
+    end: u32,
+}
+
+impl MockAleo {
+    /// Serves an empty chain on a free local port.
+    pub async fn start() -> anyhow::Result<Self> {
+        let node = Arc::new(Mutex::new(Node::default()));
+        let listener = tokio::net::TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let router = Router::new()
+            .route("/testnet3/latest/height", get(latest_height))
+            .route("/testnet3/latest/block", get(latest_block))
+            .route("/testnet3/block/:height", get(block))
+            .route("/testnet3/blocks", get(blocks))
+            .route("/testnet3/transaction/broadcast", post(broadcast))
+            .route("/testnet3/transaction/:id", get(transaction))
+            .route("/testnet3/program/:program/mapping/:mapping/:key", get(mapping))
+            .with_state(node.clone());
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, router).await {
+                tracing::error!("mock aleo stopped: {}", e);
+            }
+        });
+        Ok(Self { url, node })
+    }
+
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    fn with_node<T>(&self, f: impl FnOnce(&mut Node) -> T) -> T {
+        f(&mut self.node.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    /// The latest height, the highest block pushed unless set.
+    pub fn height(&self) -> u32 {
+        self.with_node(|n| n.height)
+    }
+
+    pub fn set_height(&self, height: u32) {
+        self.with_node(|n| n.height = height)
+    }
+
+    /// Serves `block` at its own height and its transactions by id, returns the height.
+    pub fn push_block<N: Network>(&self, block: &Block<N>) -> anyhow::Result<u32> {
+        let block = serde_json::to_value(block)?;
+        self.with_node(|n| n.insert_block(block))
+    }
+
+    /// Served at every height up to the latest one that has no block pushed, the genesis block makes a quiet chain.
+    pub fn set_empty_block<N: Network>(&self, block: &Block<N>) -> anyhow::Result<()> {
+        let block = serde_json::to_value(block)?;
+        self.with_node(|n| n.empty_block = Some(block));
+        Ok(())
+    }
+
+    /// Loads every `*.json` block in `dir`, as the node served them, returns their heights in order.
+    pub fn load_fixtures(&self, dir: &Path) -> anyhow::Result<Vec<u32>> {
+        let mut heights = vec![];
+        if !dir.exists() {
+            return Ok(heights);
+        }
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().map_or(true, |e| e != "json") || path.file_name().map_or(false, |f| f == FIXTURE_TXS) {
+                continue;
+            }
+            let block = serde_json::from_slice(&std::fs::read(&path)?)?;
+            let height = self.with_node(|n| n.insert_block(block)).map_err(|e| anyhow::anyhow!("{path:?}: {e}"))?;
+            heights.push(height);
+        }
+        heights.sort();
+        Ok(heights)
+    }
+
+    pub fn push_transaction<N: Network>(&self, tx: &Transaction<N>) -> anyhow::Result<()> {
+        let tx = serde_json::to_value(tx)?;
+        self.with_node(|n| n.insert_transaction(tx));
+        Ok(())
+    }
+
+    /// What `program/{program}/mapping/{mapping}/{key}` answers, `null` for keys never set.
+    pub fn set_mapping(&self, program: &str, mapping: &str, key: impl Display, value: impl Display) {
+        let key = (program.to_string(), mapping.to_string(), key.to_string());
+        self.with_node(|n| n.mappings.insert(key, value.to_string()));
+    }
+
+    pub fn remove_mapping(&self, program: &str, mapping: &str, key: impl Display) {
+        let key = (program.to_string(), mapping.to_string(), key.to_string());
+        self.with_node(|n| n.mappings.remove(&key));
+    }
+
+    /// The next request to `route` (`latest/height`, `block`, `blocks`, `transaction`, `mapping` or `broadcast`)
+    /// answers `status` with `body`, queued errors are used up in order.
+    pub fn fail(&self, route: &str, status: u16, body: &str) {
+        let status = StatusCode::from_u16(status).unwrap_or(StatusCode::INTERNAL_SERVER_ERROR);
+        self.with_node(|n| n.errors.entry(route.to_string()).or_default().push_back((status, body.to_string())))
+    }
+
+    /// Requests of `route` are handled right away but answered `delay` later, until cleared with `None`.
+    pub fn delay(&self, route: &str, delay: Option<Duration>) {
+        self.with_node(|n| match delay {
+            Some(delay) => n.delays.insert(route.to_string(), delay),
+            None => n.delays.remove(route),
+        });
+    }
+
+    /// Transactions broadcast so far, in order.
+    pub fn broadcast<N: Network>(&self) -> anyhow::Result<Vec<Transaction<N>>> {
+        let sent = self.with_node(|n| n.broadcast.clone());
+        sent.into_iter().map(|tx| Ok(serde_json::from_value(tx)?)).collect()
+    }
+
+    /// How many requests of `route` were answered.
+    pub fn requests(&self, route: &str) -> usize {
+        self.with_node(|n| n.requests.get(route).copied().unwrap_or_default())
+    }
+}
+
+type Shared = Arc<Mutex<Node>>;
+
+async fn with_node<T>(node: &Shared, route: &str, f: impl FnOnce(&mut Node) -> T) -> Result<T, (StatusCode, String)> {
+    let (answer, delay) = {
+        let mut node = node.lock().unwrap_or_else(|e| e.into_inner());
+        let answer = node.request(route).map(|()| f(&mut node));
+        (answer, node.delays.get(route).copied())
+    };
+    if let Some(delay) = delay {
+        tokio::time::sleep(delay).await;
+    }
+    answer
+}
+
+fn found(value: Option<Value>, what: String) -> Response {
+    match value {
+        Some(value) => Json(value).into_response(),
+        None => (StatusCode::NOT_FOUND, format!("{what} not found")).into_response(),
+    }
+}
+
+async fn latest_height(State(node): State<Shared>) -> Result<impl IntoResponse, (StatusCode, String)> {
+    with_node(&node, "latest/height", |n| Json(n.height)).await
+}
+
+async fn latest_block(State(node): State<Shared>) -> Result<impl IntoResponse, (StatusCode, String)> {
+    with_node(&node, "block", |n| found(n.block(n.height), format!("block {}", n.height))).await
+}
+
+async fn block(
+    State(node): State<Shared>,
+    UrlPath(height): UrlPath<u32>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    with_node(&node, "block", |n| found(n.block(height), format!("block {height}"))).await
+}
+
+async fn blocks(State(node): State<Shared>, Query(range): Query<Range>) -> Result<Response, (StatusCode, String)> {
+    with_node(&node, "blocks", |n| {
+        if range.end < range.start || range.end - range.start > MAX_BLOCK_RANGE {
+            return (StatusCode::BAD_REQUEST, format!("invalid block range {}..{}", range.start, range.end))
+                .into_response();
+        }
+        match (range.start..range.end).map(|h| n.block(h)).collect::<Option<Vec<_>>>() {
+            Some(blocks) => Json(blocks).into_response(),
+            None => (StatusCode::NOT_FOUND, format!("blocks {}..{} not found", range.start, range.end)).into_response(),
+        }
+    })
+    .await
+}
+
+async fn transaction(
+    State(node): State<Shared>,
+    UrlPath(id): UrlPath<String>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    with_node(&node, "transaction", |n| found(n.transactions.get(&id).cloned(), format!("transaction {id}"))).await
+}
+
+async fn mapping(
+    State(node): State<Shared>,
+    UrlPath((program, mapping, key)): UrlPath<(String, String, String)>,
+) -> Result<impl IntoResponse, (StatusCode, String)> {
+    // a key that was never set is `null`, not an error
+    with_node(&node, "mapping", |n| Json(n.mappings.get(&(program, mapping, key)).cloned())).await
+}
+
+async fn broadcast(State(node): State<Shared>, Json(tx): Json<Value>) -> Result<Response, (StatusCode, String)> {
+    with_node(&node, "broadcast", |n| {
+        n.broadcast.push(tx.clone());
+        match n.insert_transaction(tx) {
+            Some(id) => Json(id).into_response(),
+            None => (StatusCode::BAD_REQUEST, "transaction without an id".to_string()).into_response(),
+        }
+    })
+    .await
+}
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use aleo_rust::{Address, AleoAPIClient, Block, Network, Plaintext, PrivateKey, Testnet3};
+
+    use super::MockAleo;
+
+    #[tokio::test]
+    async fn test_chain() {
+        let aleo = MockAleo::start().await.unwrap();
+        let genesis = Block::<Testnet3>::from_bytes_le(Testnet3::genesis_bytes()).unwrap();
+        aleo.set_empty_block(&genesis).unwrap();
+        aleo.set_height(120);
+        let owner = Address::try_from(PrivateKey::<Testnet3>::new(&mut rand::thread_rng()).unwrap()).unwrap();
+        aleo.set_mapping("credits.aleo", "account", owner, "5000u64");
+
+        let client = AleoAPIClient::<Testnet3>::new(&aleo.url(), "testnet3").unwrap();
+        let (height, blocks, too_high, value) = tokio::task::spawn_blocking(move || {
+            let key = Plaintext::<Testnet3>::from_str(&owner.to_string()).unwrap();
+            (
+                client.latest_height().unwrap(),
+                client.get_blocks(70, 120).unwrap(),
+                client.get_blocks(100, 130).is_err(),
+                client.get_mapping_value("credits.aleo", "account", key).unwrap(),
+            )
+        })
+        .await
+        .unwrap();
+        assert_eq!((height, blocks.len(), blocks[0].hash()), (120, 50, genesis.hash()));
+        assert!(too_high);
+        assert_eq!(value.to_string(), "5000u64");
+        assert_eq!(aleo.requests("blocks"), 2);
+    }
+}
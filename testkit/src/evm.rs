@@ -0,0 +1,509 @@
+use std::{
+    collections::{HashMap, VecDeque},
+    net::SocketAddr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
+
+use axum::{extract::State, routing::post, Json, Router};
+use ethers::{
+    abi::{self, Token},
+    types::{
+        Address, Block, BlockNumber, Bytes, FeeHistory, Filter, FilteredParams, Log, Transaction, TransactionReceipt,
+        H256, U256, U64,
+    },
+    utils::{keccak256, rlp},
+};
+use izar_core::{network::eth::EthNetwork, serde::to_payload};
+use serde::de::DeserializeOwned;
+use serde_json::{json, Value};
+
+const PACKET_EVENT: &str = "Packet(address,uint256,uint16,bytes,bytes)";
+const WRAPPER_LOCK_EVENT: &str = "WrapperLock(address,address,uint64,bytes,uint256,uint256)";
+// `Error(string)`
+const REVERT_SELECTOR: [u8; 4] = [0x08, 0xc3, 0x79, 0xa0];
+const GAS_USED: u64 = 100_000;
+const BLOCK_GAS_LIMIT: u64 = 30_000_000;
+
+/// An error answer of the node, `data` is what a revert returned.
+#[derive(Debug, Clone)]
+pub struct RpcError {
+    pub code: i64,
+    pub message: String,
+    pub data: Option<Bytes>,
+}
+
+impl RpcError {
+    pub fn new(code: i64, message: impl Into<String>) -> Self {
+        Self { code, message: message.into(), data: None }
+    }
+
+    /// What a node answers to a call reverting with `Error(reason)`.
+    pub fn revert(reason: &str) -> Self {
+        let data = [REVERT_SELECTOR.as_slice(), abi::encode(&[Token::String(reason.to_string())]).as_slice()].concat();
+        Self { code: 3, message: format!("execution reverted: {reason}"), data: Some(data.into()) }
+    }
+
+    pub fn nonce_too_low() -> Self {
+        Self::new(-32000, "nonce too low")
+    }
+
+    fn invalid_params(e: impl std::fmt::Display) -> Self {
+        Self::new(-32602, format!("invalid params: {e}"))
+    }
+
+    fn to_json(&self) -> Value {
+        match &self.data {
+            Some(data) => json!({ "code": self.code, "message": self.message, "data": data }),
+            None => json!({ "code": self.code, "message": self.message }),
+        }
+    }
+}
+
+/// A bridge lock on an EVM chain, as the voter reads it from the `Packet` and `WrapperLock` logs.
+#[derive(Debug, Clone)]
+pub struct Lock {
+    pub tx_hash: H256,
+    pub block: u64,
+    pub sender: Address,
+    pub token: Address,
+    pub to_chain_id: u32,
+    pub to_asset_addr: String,
+    pub to_addr: String,
+    pub amount: U256,
+    pub fee: U256,
+    pub nonce: U256,
+}
+
+impl Lock {
+    /// The two logs of the lock on `E`, in the order the voter pairs them.
+    pub fn logs<E: EthNetwork>(&self) -> anyhow::Result<[Log; 2]> {
+        let payload = to_payload(&self.to_asset_addr, &self.to_addr, self.amount);
+        let packet = abi::encode(&[
+            Token::Address(self.sender),
+            Token::Uint(self.nonce),
+            Token::Uint(self.to_chain_id.into()),
+            Token::Bytes(vec![]),
+            Token::Bytes(payload),
+        ]);
+
+        let mut lock = vec![0u8; 256];
+        U256::from(self.to_chain_id).to_big_endian(&mut lock[0..32]);
+        self.amount.to_big_endian(&mut lock[64..96]);
+        self.fee.to_big_endian(&mut lock[96..128]);
+
+        let log = |address: &str, topics: Vec<H256>, data: Vec<u8>| -> anyhow::Result<Log> {
+            Ok(Log {
+                address: address.parse()?,
+                topics,
+                data: data.into(),
+                block_number: Some(self.block.into()),
+                block_hash: Some(block_hash(self.block)),
+                transaction_hash: Some(self.tx_hash),
+                transaction_index: Some(U64::zero()),
+                removed: Some(false),
+                ..Default::default()
+            })
+        };
+        Ok([
+            log(E::PROXY_CONTRACT, vec![H256(keccak256(PACKET_EVENT))], packet)?,
+            log(
+                E::WRAPPER_CONTRACT,
+                vec![H256(keccak256(WRAPPER_LOCK_EVENT)), self.token.into(), self.sender.into()],
+                lock,
+            )?,
+        ])
+    }
+}
+
+fn block_hash(number: u64) -> H256 {
+    H256(keccak256(number.to_be_bytes()))
+}
+
+#[derive(Default)]
+struct Chain {
+    chain_id: u64,
+    head: u64,
+    gas_price: U256,
+    base_fee: U256,
+    default_balance: U256,
+    balances: HashMap<Address, U256>,
+    nonces: HashMap<Address, U256>,
+    logs: Vec<Log>,
+    txs: HashMap<H256, Transaction>,
+    receipts: HashMap<H256, TransactionReceipt>,
+    mempool: Vec<H256>,
+    sent: Vec<H256>,
+    call_results: HashMap<Address, Bytes>,
+    call_revert: Option<String>,
+    reverts_onchain: u32,
+    errors: HashMap<String, VecDeque<RpcError>>,
+    delays: HashMap<String, Duration>,
+    auto_mine: bool,
+    requests: HashMap<String, usize>,
+}
+
+/// An EVM JSON-RPC node in the test process. Point a `Provider<Http>` at `url()` and script what it answers: blocks
+/// advance with `mine`, logs and receipts are replayed as pushed, raw transactions are decoded and kept in a mempool
+/// until mined, and errors can be queued for any method.
+#[derive(Clone)]
+pub struct MockEvm {
+    url: String,
+    chain: Arc<Mutex<Chain>>,
+}
+
+impl MockEvm {
+    /// Serves a chain at height `head` on a free local port.
+    pub async fn start(chain_id: u64, head: u64) -> anyhow::Result<Self> {
+        let chain = Arc::new(Mutex::new(Chain {
+            chain_id,
+            head,
+            gas_price: U256::exp10(9) * 2,
+            base_fee: U256::exp10(9),
+            default_balance: U256::exp10(18) * 100,
+            ..Default::default()
+        }));
+        let listener = tokio::net::TcpListener::bind(SocketAddr::from(([127, 0, 0, 1], 0))).await?;
+        let url = format!("http://{}", listener.local_addr()?);
+        let router = Router::new().route("/", post(rpc)).with_state(chain.clone());
+        tokio::spawn(async move {
+            if let Err(e) = axum::serve(listener, router).await {
+                tracing::error!("mock evm stopped: {}", e);
+            }
+        });
+        Ok(Self { url, chain })
+    }
+
+    pub fn url(&self) -> String {
+        self.url.clone()
+    }
+
+    fn with_chain<T>(&self, f: impl FnOnce(&mut Chain) -> T) -> T {
+        f(&mut self.chain.lock().unwrap_or_else(|e| e.into_inner()))
+    }
+
+    pub fn head(&self) -> u64 {
+        self.with_chain(|c| c.head)
+    }
+
+    /// Moves the head without mining the mempool, for replaying logs at known heights.
+    pub fn set_head(&self, head: u64) {
+        self.with_chain(|c| c.head = head)
+    }
+
+    /// Mines `blocks` blocks, the mempool goes into the first one.
+    pub fn mine(&self, blocks: u64) {
+        self.with_chain(|c| (0..blocks).for_each(|_| c.mine()))
+    }
+
+    /// Mines every sent transaction right away.
+    pub fn set_auto_mine(&self, auto_mine: bool) {
+        self.with_chain(|c| c.auto_mine = auto_mine)
+    }
+
+    pub fn set_gas_price(&self, gas_price: U256) {
+        self.with_chain(|c| c.gas_price = gas_price)
+    }
+
+    pub fn set_base_fee(&self, base_fee: U256) {
+        self.with_chain(|c| c.base_fee = base_fee)
+    }
+
+    pub fn set_balance(&self, address: Address, balance: U256) {
+        self.with_chain(|c| c.balances.insert(address, balance));
+    }
+
+    /// The next nonce of `address`, as if it sent transactions this node did not see.
+    pub fn set_nonce(&self, address: Address, nonce: U256) {
+        self.with_chain(|c| c.nonces.insert(address, nonce));
+    }
+
+    /// Logs are returned by block and then in the order they were pushed.
+    pub fn push_logs(&self, logs: impl IntoIterator<Item = Log>) {
+        self.with_chain(|c| {
+            for mut log in logs {
+                let block = log.block_number.unwrap_or_default();
+                let index = c.logs.iter().filter(|l| l.block_number.unwrap_or_default() == block).count();
+                log.log_index.get_or_insert(index.into());
+                c.logs.push(log);
+            }
+        })
+    }
+
+    /// Serves a transaction the node did not see sent, such as one broadcast before a restart.
+    pub fn push_transaction(&self, tx: Transaction) {
+        self.with_chain(|c| c.txs.insert(tx.hash, tx));
+    }
+
+    pub fn push_receipt(&self, receipt: TransactionReceipt) {
+        self.with_chain(|c| c.receipts.insert(receipt.transaction_hash, receipt));
+    }
+
+    /// What `eth_call` to `to` returns, `0x` otherwise.
+    pub fn set_call_result(&self, to: Address, result: Bytes) {
+        self.with_chain(|c| c.call_results.insert(to, result));
+    }
+
+    /// `eth_call` and `eth_estimateGas` revert with `reason` until cleared with `None`.
+    pub fn revert_calls(&self, reason: Option<&str>) {
+        self.with_chain(|c| c.call_revert = reason.map(str::to_string))
+    }
+
+    /// The next `count` mined transactions get a failed receipt.
+    pub fn revert_onchain(&self, count: u32) {
+        self.with_chain(|c| c.reverts_onchain += count)
+    }
+
+    /// The next request of `method` answers `error`, queued errors are used up in order.
+    pub fn fail(&self, method: &str, error: RpcError) {
+        self.with_chain(|c| c.errors.entry(method.to_string()).or_default().push_back(error))
+    }
+
+    /// Requests of `method` are handled right away but answered `delay` later, until cleared with `None`.
+    pub fn delay(&self, method: &str, delay: Option<Duration>) {
+        self.with_chain(|c| match delay {
+            Some(delay) => c.delays.insert(method.to_string(), delay),
+            None => c.delays.remove(method),
+        });
+    }
+
+    /// Transactions sent with `eth_sendRawTransaction`, in order, replaced ones included.
+    pub fn sent(&self) -> Vec<H256> {
+        self.with_chain(|c| c.sent.clone())
+    }
+
+    pub fn transaction(&self, hash: H256) -> Option<Transaction> {
+        self.with_chain(|c| c.txs.get(&hash).cloned())
+    }
+
+    pub fn receipt(&self, hash: H256) -> Option<TransactionReceipt> {
+        self.with_chain(|c| c.receipts.get(&hash).cloned())
+    }
+
+    /// How many requests of `method` were answered.
+    pub fn requests(&self, method: &str) -> usize {
+        self.with_chain(|c| c.requests.get(method).copied().unwrap_or_default())
+    }
+}
+
+async fn rpc(State(chain): State<Arc<Mutex<Chain>>>, Json(req): Json<Value>) -> Json<Value> {
+    let id = req.get("id").cloned().unwrap_or(Value::Null);
+    let method = req.get("method").and_then(Value::as_str).unwrap_or_default().to_string();
+    let params = req.get("params").and_then(Value::as_array).cloned().unwrap_or_default();
+
+    let (result, delay) = {
+        let mut chain = chain.lock().unwrap_or_else(|e| e.into_inner());
+        (chain.handle(&method, &params), chain.delays.get(&method).copied())
+    };
+    if let Some(delay) = delay {
+        tokio::time::sleep(delay).await;
+    }
+    Json(match result {
+        Ok(result) => json!({ "jsonrpc": "2.0", "id": id, "result": result }),
+        Err(e) => json!({ "jsonrpc": "2.0", "id": id, "error": e.to_json() }),
+    })
+}
+
+fn param<T: DeserializeOwned>(params: &[Value], i: usize) -> Result<T, RpcError> {
+    serde_json::from_value(params.get(i).cloned().unwrap_or(Value::Null)).map_err(RpcError::invalid_params)
+}
+
+fn to_json(value: impl serde::Serialize) -> Result<Value, RpcError> {
+    serde_json::to_value(value).map_err(|e| RpcError::new(-32603, e.to_string()))
+}
+
+impl Chain {
+    fn handle(&mut self, method: &str, params: &[Value]) -> Result<Value, RpcError> {
+        *self.requests.entry(method.to_string()).or_default() += 1;
+        if let Some(e) = self.errors.get_mut(method).and_then(VecDeque::pop_front) {
+            return Err(e);
+        }
+
+        match method {
+            "eth_chainId" => to_json(U64::from(self.chain_id)),
+            "net_version" => to_json(self.chain_id.to_string()),
+            "eth_blockNumber" => to_json(U64::from(self.head)),
+            "eth_gasPrice" => to_json(self.gas_price),
+            "eth_maxPriorityFeePerGas" => to_json(self.tip()),
+            "eth_getBlockByNumber" => match self.block_number(&param(params, 0)?) {
+                Some(n) if n <= self.head => to_json(self.block(n)),
+                _ => Ok(Value::Null),
+            },
+            "eth_feeHistory" => {
+                let count = param::<U256>(params, 0)?.as_u64().clamp(1, 1024) as usize;
+                let percentiles = param::<Vec<f64>>(params, 2).unwrap_or_default();
+                to_json(FeeHistory {
+                    base_fee_per_gas: vec![self.base_fee; count + 1],
+                    gas_used_ratio: vec![0.5; count],
+                    oldest_block: (self.head + 1).saturating_sub(count as u64).into(),
+                    reward: vec![vec![self.tip(); percentiles.len()]; count],
+                })
+            }
+            "eth_getBalance" => {
+                let address = param::<Address>(params, 0)?;
+                to_json(self.balances.get(&address).copied().unwrap_or(self.default_balance))
+            }
+            "eth_getTransactionCount" => {
+                to_json(self.nonces.get(&param::<Address>(params, 0)?).copied().unwrap_or_default())
+            }
+            "eth_call" => {
+                if let Some(reason) = &self.call_revert {
+                    return Err(RpcError::revert(reason));
+                }
+                let to = params.first().and_then(|tx| tx.get("to")).cloned().unwrap_or(Value::Null);
+                let to = serde_json::from_value::<Address>(to).unwrap_or_default();
+                to_json(self.call_results.get(&to).cloned().unwrap_or_default())
+            }
+            "eth_estimateGas" => match &self.call_revert {
+                Some(reason) => Err(RpcError::revert(reason)),
+                None => to_json(U256::from(GAS_USED)),
+            },
+            "eth_sendRawTransaction" => to_json(self.send_raw(param::<Bytes>(params, 0)?)?),
+            "eth_getTransactionByHash" => to_json(self.txs.get(&param::<H256>(params, 0)?)),
+            "eth_getTransactionReceipt" => to_json(self.receipts.get(&param::<H256>(params, 0)?)),
+            "eth_getLogs" => {
+                let filter = FilteredParams::new(Some(param::<Filter>(params, 0)?));
+                let mut logs = self
+                    .logs
+                    .iter()
+                    .filter(|l| l.block_number.map_or(false, |b| b.as_u64() <= self.head))
+                    .filter(|l| filter.filter_block_range(l.block_number.unwrap_or_default().as_u64()))
+                    .filter(|l| filter.filter_address(l) && filter.filter_topics(l))
+                    .cloned()
+                    .collect::<Vec<_>>();
+                logs.sort_by_key(|l| l.block_number);
+                to_json(logs)
+            }
+            _ => Err(RpcError::new(-32601, format!("method {method} not found"))),
+        }
+    }
+
+    fn tip(&self) -> U256 {
+        self.gas_price.saturating_sub(self.base_fee).max(U256::one())
+    }
+
+    fn block_number(&self, tag: &Value) -> Option<u64> {
+        match serde_json::from_value::<BlockNumber>(tag.clone()).ok()? {
+            BlockNumber::Number(n) => Some(n.as_u64()),
+            BlockNumber::Earliest => Some(0),
+            _ => Some(self.head),
+        }
+    }
+
+    fn block(&self, number: u64) -> Block<H256> {
+        Block {
+            hash: Some(block_hash(number)),
+            parent_hash: block_hash(number.saturating_sub(1)),
+            number: Some(number.into()),
+            timestamp: number.into(),
+            gas_limit: BLOCK_GAS_LIMIT.into(),
+            base_fee_per_gas: Some(self.base_fee),
+            ..Default::default()
+        }
+    }
+
+    fn send_raw(&mut self, raw: Bytes) -> Result<H256, RpcError> {
+        let mut tx = rlp::decode::<Transaction>(&raw).map_err(RpcError::invalid_params)?;
+        tx.recover_from_mut().map_err(RpcError::invalid_params)?;
+
+        let expected = self.nonces.get(&tx.from).copied().unwrap_or_default();
+        if tx.nonce < expected {
+            // a pending transaction at the same nonce is replaced, anything else is too late
+            let replaced = self
+                .mempool
+                .iter()
+                .copied()
+                .find(|h| self.txs.get(h).map_or(false, |p| p.from == tx.from && p.nonce == tx.nonce));
+            let replaced = replaced.ok_or_else(RpcError::nonce_too_low)?;
+            self.mempool.retain(|h| *h != replaced);
+            self.txs.remove(&replaced);
+        }
+        self.nonces.insert(tx.from, expected.max(tx.nonce + 1));
+
+        let hash = tx.hash;
+        self.txs.insert(hash, tx);
+        self.mempool.push(hash);
+        self.sent.push(hash);
+        if self.auto_mine {
+            self.mine();
+        }
+        Ok(hash)
+    }
+
+    fn mine(&mut self) {
+        self.head += 1;
+        let (number, hash) = (self.head, block_hash(self.head));
+        let mut cumulative = U256::zero();
+        for (index, tx_hash) in std::mem::take(&mut self.mempool).into_iter().enumerate() {
+            let Some(tx) = self.txs.get_mut(&tx_hash) else {
+                continue;
+            };
+            tx.block_number = Some(number.into());
+            tx.block_hash = Some(hash);
+            tx.transaction_index = Some(index.into());
+
+            let tip = tx.max_priority_fee_per_gas.map(|tip| self.base_fee + tip);
+            let price = match (tx.max_fee_per_gas, tip) {
+                (Some(max_fee), Some(price)) => price.min(max_fee),
+                _ => tx.gas_price.unwrap_or(self.gas_price),
+            };
+            let reverted = self.reverts_onchain > 0;
+            self.reverts_onchain = self.reverts_onchain.saturating_sub(1);
+            cumulative += U256::from(GAS_USED);
+
+            let receipt = TransactionReceipt {
+                transaction_hash: tx_hash,
+                transaction_index: index.into(),
+                block_hash: Some(hash),
+                block_number: Some(number.into()),
+                from: tx.from,
+                to: tx.to,
+                cumulative_gas_used: cumulative,
+                gas_used: Some(GAS_USED.into()),
+                status: Some(U64::from(!reverted as u64)),
+                effective_gas_price: Some(price),
+                transaction_type: tx.transaction_type,
+                ..Default::default()
+            };
+            self.receipts.insert(tx_hash, receipt);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{str::FromStr, time::Duration};
+
+    use ethers::{
+        prelude::SignerMiddleware,
+        providers::{Http, Middleware, Provider},
+        signers::{LocalWallet, Signer},
+        types::{Address, TransactionRequest},
+    };
+
+    use super::{MockEvm, RpcError};
+
+    #[tokio::test]
+    async fn test_send_and_mine() {
+        let evm = MockEvm::start(5, 100).await.unwrap();
+        let provider = Provider::<Http>::try_from(evm.url()).unwrap().interval(Duration::from_millis(10));
+        let wallet = LocalWallet::new(&mut rand::thread_rng()).with_chain_id(5u64);
+        let client = SignerMiddleware::new(provider.clone(), wallet);
+        assert_eq!(provider.get_block_number().await.unwrap().as_u64(), 100);
+
+        let to = Address::from_str("0x0000000000000000000000000000000000000001").unwrap();
+        let pending = client.send_transaction(TransactionRequest::new().to(to).gas(21000), None).await.unwrap();
+        let hash = pending.tx_hash();
+        assert_eq!(evm.sent(), vec![hash]);
+        assert!(provider.get_transaction_receipt(hash).await.unwrap().is_none());
+
+        evm.revert_onchain(1);
+        evm.mine(1);
+        let receipt = provider.get_transaction_receipt(hash).await.unwrap().unwrap();
+        assert_eq!((receipt.block_number.unwrap().as_u64(), receipt.status.unwrap().as_u64()), (101, 0));
+
+        evm.fail("eth_sendRawTransaction", RpcError::nonce_too_low());
+        let tx = TransactionRequest::new().to(to).gas(21000).nonce(1);
+        assert!(client.send_transaction(tx, None).await.is_err());
+    }
+}
@@ -0,0 +1,5 @@
+//! Offline stand-ins for the nodes the voter and relayer talk to, served in the test process.
+
+pub mod aleo;
+pub mod evm;
+pub mod transfer;
@@ -0,0 +1,64 @@
+use izar_core::types::{
+    cert::Certificate,
+    transaction::{IzarTransaction, Priority},
+};
+
+/// Builds the transfers tests feed the voter, relayer and sequencer. Unset fields are empty, the nonce is 1 and
+/// the fee 0.
+#[derive(Debug, Clone)]
+pub struct TransferBuilder {
+    tx: IzarTransaction,
+}
+
+impl TransferBuilder {
+    pub fn new(from_chain_tx_hash: impl ToString, from_chain_id: u32, to_chain_id: u32) -> Self {
+        Self {
+            tx: IzarTransaction {
+                priority: Priority::Low,
+                timestamp: 0,
+                from_chain_tx_hash: from_chain_tx_hash.to_string(),
+                from_chain_id,
+                from_asset_addr: String::new(),
+                from_addr: String::new(),
+                to_chain_id,
+                to_asset_addr: String::new(),
+                to_addr: String::new(),
+                to_chain_tx_hash: None,
+                payload: String::new(),
+                nonce: "1".to_string(),
+                certificates: vec![],
+                fee: "0".to_string(),
+            },
+        }
+    }
+
+    pub fn with_priority(mut self, priority: Priority, timestamp: u64) -> Self {
+        (self.tx.priority, self.tx.timestamp) = (priority, timestamp);
+        self
+    }
+
+    pub fn with_from(mut self, asset_addr: impl ToString, addr: impl ToString) -> Self {
+        (self.tx.from_asset_addr, self.tx.from_addr) = (asset_addr.to_string(), addr.to_string());
+        self
+    }
+
+    pub fn with_to(mut self, asset_addr: impl ToString, addr: impl ToString) -> Self {
+        (self.tx.to_asset_addr, self.tx.to_addr) = (asset_addr.to_string(), addr.to_string());
+        self
+    }
+
+    pub fn with_nonce(mut self, nonce: impl ToString) -> Self {
+        self.tx.nonce = nonce.to_string();
+        self
+    }
+
+    /// Adds a certificate, call once per signer.
+    pub fn with_certificate(mut self, signer: impl ToString, signature: impl ToString) -> Self {
+        self.tx.certificates.push(Certificate { signature: signature.to_string(), signer: signer.to_string() });
+        self
+    }
+
+    pub fn build(self) -> IzarTransaction {
+        self.tx
+    }
+}
@@ -1,7 +1,15 @@
-use std::{str::FromStr, time::Duration};
+use std::{
+    path::Path,
+    str::FromStr,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
-use aleo_rust::{Address, AleoAPIClient, Block, Identifier, Network, PrivateKey, ProgramID, ViewKey};
-use backon::{BlockingRetryable, ExponentialBuilder};
+use aleo_rust::{
+    Address, AleoAPIClient, Block, Ciphertext, Credits, Identifier, Network, Plaintext, PrivateKey, ProgramID, Record,
+    Value, ViewKey,
+};
+use backon::{ExponentialBuilder, Retryable};
 use izar_core::{
     db::{map::DBMap, RocksDB},
     network::aleo::AleoNetworkExt,
@@ -12,13 +20,14 @@ use izar_core::{
     utils::PlaintextCodec,
     Argument, Input, Output, Transition,
 };
-use rayon::prelude::*;
-use tracing::error_span;
+use tracing::{error_span, Instrument};
 
-use crate::{DB_PATH, SPEEDUP_TXS, UNCONFIRMED_TXS};
+use crate::{EventSender, VoterEvent, SPEEDUP_TXS, UNCONFIRMED_TXS};
 
 use super::filter::TransitionFilter;
 
+const RECORDS_PREFIX: &str = "aleo_records";
+
 #[derive(Clone)]
 pub struct AleoConnector<N: Network> {
     private_key: PrivateKey<N>,
@@ -28,11 +37,20 @@ pub struct AleoConnector<N: Network> {
     unconfirmed_txs: DBMap<String, IzarTransaction>,
     speedup_txs: DBMap<String, String>,
     current_height: DBMap<u16, u32>,
+    // serial number to microcredits of the unspent records the key holds
+    records: DBMap<String, u64>,
     filter: TransitionFilter<N>,
+    balance: Arc<Mutex<Option<u128>>>,
+    events: Option<EventSender>,
 }
 
 impl<N: Network> AleoConnector<N> {
-    pub fn new(pk: PrivateKey<N>, dest: Option<String>, from_height: Option<u32>) -> anyhow::Result<Self> {
+    pub fn new(
+        pk: PrivateKey<N>,
+        dest: Option<String>,
+        from_height: Option<u32>,
+        db_path: impl AsRef<Path>,
+    ) -> anyhow::Result<Self> {
         let aleo_client = match dest {
             Some(dest) => AleoAPIClient::new(&dest, "testnet3")?,
             None => AleoAPIClient::testnet3(),
@@ -41,9 +59,10 @@ impl<N: Network> AleoConnector<N> {
         let vk = ViewKey::try_from(pk)?;
         let address = Address::try_from(pk)?;
 
-        let unconfirmed_txs = RocksDB::open_map(DB_PATH, UNCONFIRMED_TXS)?;
-        let speedup_txs = RocksDB::open_map(DB_PATH, SPEEDUP_TXS)?;
-        let current_height = RocksDB::open_map(DB_PATH, "aleo-chains")?;
+        let unconfirmed_txs = RocksDB::open_map(&db_path, UNCONFIRMED_TXS)?;
+        let speedup_txs = RocksDB::open_map(&db_path, SPEEDUP_TXS)?;
+        let current_height = RocksDB::open_map(&db_path, "aleo-chains")?;
+        let records = RocksDB::open_map(&db_path, RECORDS_PREFIX)?;
         let cur = current_height.get(&N::ID)?.unwrap_or(N::START_HEIGHT);
         if let Some(from_height) = from_height {
             if cur < from_height {
@@ -64,7 +83,10 @@ impl<N: Network> AleoConnector<N> {
             unconfirmed_txs,
             speedup_txs,
             current_height,
+            records,
             filter,
+            balance: Default::default(),
+            events: None,
         })
     }
 
@@ -72,9 +94,18 @@ impl<N: Network> AleoConnector<N> {
         &self.aleo_client
     }
 
-    pub fn sync(&self) -> anyhow::Result<()> {
+    // the aleo client blocks, so every call to it runs off the runtime threads
+    async fn blocking<T: Send + 'static>(
+        &self,
+        f: impl FnOnce(AleoAPIClient<N>) -> anyhow::Result<T> + Send + 'static,
+    ) -> anyhow::Result<T> {
+        let client = self.aleo_client.clone();
+        tokio::task::spawn_blocking(move || f(client)).await?
+    }
+
+    pub async fn sync(&self) -> anyhow::Result<()> {
         let cur = self.current_height.get(&N::ID)?.unwrap_or(N::START_HEIGHT);
-        let latest = self.aleo_client().latest_height()?;
+        let latest = self.blocking(|client| client.latest_height()).await?;
 
         const BATCH_SIZE: u32 = 50;
 
@@ -85,21 +116,21 @@ impl<N: Network> AleoConnector<N> {
         for start in (cur..latest).step_by(BATCH_SIZE as usize) {
             let end = (start + BATCH_SIZE).min(latest);
             tracing::warn!("fetching aleo blocks from {} to {}", start, end);
-            self.aleo_client()
-                .get_blocks(start, end)?
-                .into_iter()
-                .flat_map(|b| self.filter.filter_block_with_txid(b))
-                .for_each(|(tid, t)| self.transition_handler(tid, t));
+            for b in self.blocking(move |client| client.get_blocks(start, end)).await? {
+                self.handle_records(&b);
+                for (tid, t) in self.filter.filter_block_with_txid(b) {
+                    self.transition_handler(tid, t);
+                }
+            }
             self.current_height.insert(N::ID, end)?;
         }
 
         Ok(())
     }
 
-    // TODO: use futures
-    pub fn fast_sync(&self) -> anyhow::Result<()> {
+    pub async fn fast_sync(&self) -> anyhow::Result<()> {
         let cur = self.current_height.get(&N::ID)?.unwrap_or(N::START_HEIGHT);
-        let latest = self.aleo_client.latest_height()?;
+        let latest = self.blocking(|client| client.latest_height()).await?;
         tracing::info!("sync aleo from {} to {}", cur, latest);
 
         if cur >= latest {
@@ -111,7 +142,8 @@ impl<N: Network> AleoConnector<N> {
         for start in (cur..latest).step_by(BATCH_SIZE) {
             let end = (start + BATCH_SIZE as u32).min(latest);
 
-            self.fast_get_blocks(start, end)?.into_iter().for_each(|b| {
+            self.fast_get_blocks(start, end).await?.into_iter().for_each(|b| {
+                self.handle_records(&b);
                 let transactions = self.filter.filter_block_with_txid(b);
                 for (tid, t) in transactions {
                     self.transition_handler(tid, t);
@@ -124,50 +156,65 @@ impl<N: Network> AleoConnector<N> {
         Ok(())
     }
 
-    fn fast_get_blocks(&self, st: u32, ed: u32) -> anyhow::Result<Vec<Block<N>>> {
+    async fn fast_get_blocks(&self, st: u32, ed: u32) -> anyhow::Result<Vec<Block<N>>> {
         const BATCH_SIZE: usize = 500;
 
         if ed - st > BATCH_SIZE as u32 {
             return Err(anyhow::anyhow!("too large batch size"));
         }
 
-        let par_getter = (st..ed).step_by(50).map(|st| (st, (st + 50).min(ed))).collect::<Vec<(u32, u32)>>();
-
-        let blocks = par_getter
-            .par_iter()
-            .map(|(st, ed)| {
-                tracing::warn!("fetching aleo blocks from {} to {}", st, ed);
-                self.aleo_client.get_blocks(*st, *ed)
+        // the ranges are fetched side by side and handled in height order
+        let fetches = (st..ed)
+            .step_by(50)
+            .map(|st| {
+                let (client, ed) = (self.aleo_client.clone(), (st + 50).min(ed));
+                tokio::task::spawn_blocking(move || {
+                    tracing::warn!("fetching aleo blocks from {} to {}", st, ed);
+                    client.get_blocks(st, ed)
+                })
             })
-            .collect::<anyhow::Result<Vec<Vec<Block<N>>>>>()?
-            .into_iter()
-            .flatten()
-            .collect::<Vec<Block<N>>>();
+            .collect::<Vec<_>>();
+
+        let mut blocks = Vec::with_capacity((ed - st) as usize);
+        for fetch in fetches {
+            blocks.extend(fetch.await??);
+        }
 
         Ok(blocks)
     }
 
-    fn initial(self) -> Self {
+    pub fn with_events(mut self, events: EventSender) -> Self {
+        self.events = Some(events);
+        self
+    }
+
+    pub fn sync_and_initial(self) -> Self {
         let self_clone = self.clone();
+        let fut = async move {
+            let fast_sync = || self.fast_sync();
+            if let Err(e) = fast_sync.retry(&ExponentialBuilder::default().with_max_times(5)).await {
+                tracing::error!("Failed to aleo fast sync: {:?}", e);
+            }
 
-        std::thread::spawn(move || loop {
-            if let Err(e) = self.sync() {
-                tracing::error!("Failed to aleo sync: {:?}", e);
+            loop {
+                if let Err(e) = self.sync().await {
+                    tracing::error!("Failed to aleo sync: {:?}", e);
+                }
+                if let Err(e) = self.refresh_balance().await {
+                    tracing::error!("Failed to get aleo balance: {:?}", e);
+                }
+                tokio::time::sleep(Duration::from_secs(10)).await;
             }
-            std::thread::sleep(Duration::from_secs(10));
-        });
+        };
 
+        tokio::spawn(fut.instrument(error_span!("ALEO")));
         self_clone
     }
 
-    pub fn sync_and_initial(self) -> Self {
-        error_span!("ALEO").in_scope(|| {
-            let sync_op = || self.fast_sync();
-            if let Err(e) = sync_op.retry(&ExponentialBuilder::default().with_max_times(5)).call() {
-                tracing::error!("Failed to aleo fast sync: {:?}", e);
-            }
-            self.initial()
-        })
+    fn notify(&self, event: VoterEvent) {
+        if let Some(events) = &self.events {
+            let _ = events.try_send(event);
+        }
     }
 
     pub fn transition_handler(&self, tid: N::TransactionID, t: Transition<N>) {
@@ -181,6 +228,31 @@ impl<N: Network> AleoConnector<N> {
         }
     }
 
+    // credits records sent to the key count to its balance until their serial number shows up spent
+    pub fn handle_records(&self, block: &Block<N>) {
+        for sn in block.clone().into_serial_numbers() {
+            if let Err(e) = self.records.remove(&sn.to_string()) {
+                tracing::error!("Failed to spend record {sn}: {:?}", e);
+            }
+        }
+        for (commit, record) in block.clone().into_records() {
+            if !record.is_owner(&self.view_key) {
+                continue;
+            }
+            let result = record.decrypt(&self.view_key).and_then(|record| {
+                // records of other programs hold no credits
+                let Ok(credits) = record.microcredits() else {
+                    return Ok(());
+                };
+                let sn = Record::<N, Ciphertext<N>>::serial_number(self.private_key, commit)?;
+                self.records.insert(sn.to_string(), credits)
+            });
+            if let Err(e) = result {
+                tracing::error!("Failed to keep a record of {}: {:?}", self.address, e);
+            }
+        }
+    }
+
     pub fn handle_speed_up_eth(&self, tid: N::TransactionID, t: Transition<N>) -> anyhow::Result<()> {
         tracing::info!("got a speedup tx {tid}");
         let inputs = t.inputs();
@@ -188,6 +260,7 @@ impl<N: Network> AleoConnector<N> {
             let tx_hash: EthRawHash = PlaintextCodec::<N>::decode(p)?;
             let tx_hash = format!("{:#032x}", tx_hash.to_h256());
             self.speedup_txs.insert(tid.to_string(), tx_hash)?;
+            self.notify(VoterEvent::Speedup(tid.to_string()));
         }
 
         Ok(())
@@ -210,10 +283,30 @@ impl<N: Network> AleoConnector<N> {
             );
 
             self.unconfirmed_txs.insert(tid.to_string(), aleo_tx.try_into()?)?;
+            self.notify(VoterEvent::Transfer(tid.to_string()));
         }
         Ok(())
     }
 
+    // the public credits plus the records the sync found unspent
+    async fn refresh_balance(&self) -> anyhow::Result<()> {
+        let key = Plaintext::<N>::from_str(&self.address.to_string())?;
+        let value = self.blocking(move |client| client.get_mapping_value("credits.aleo", "account", key)).await?;
+        let public: u64 = match value {
+            Value::Plaintext(p) => PlaintextCodec::<N>::decode(&p)?,
+            _ => anyhow::bail!("invalid account balance"),
+        };
+        let private = self.records.get_all()?.into_iter().map(|(_, credits)| credits as u128).sum::<u128>();
+        if let Ok(mut cached) = self.balance.lock() {
+            *cached = Some(public as u128 + private);
+        }
+        Ok(())
+    }
+
+    pub fn balance(&self) -> Option<u128> {
+        self.balance.lock().ok().and_then(|b| *b)
+    }
+
     pub fn address(&self) -> &Address<N> {
         &self.address
     }
@@ -246,3 +339,115 @@ pub fn get_address_from_burn<N: Network>(output: &Output<N>) -> anyhow::Result<A
         anyhow::bail!("invalid burn transition output")
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::str::FromStr;
+
+    use aleo_rust::{Block, Network, PrivateKey, ProgramID, Testnet3};
+    use izar_core::network::aleo::AleoNetworkExt;
+    use izar_testkit::aleo::{fixture_txs, fixtures_dir, MockAleo, FIXTURE_TXS};
+
+    use super::{AleoConnector, TransitionFilter};
+    use crate::VoterEvent;
+
+    #[tokio::test]
+    async fn test_sync() {
+        let start = Testnet3::START_HEIGHT;
+        let aleo = MockAleo::start().await.unwrap();
+        let genesis = Block::<Testnet3>::from_bytes_le(Testnet3::genesis_bytes()).unwrap();
+        aleo.set_empty_block(&genesis).unwrap();
+        aleo.set_height(start + 120);
+
+        let (events, mut receiver) = tokio::sync::mpsc::channel(16);
+        let pk = PrivateKey::<Testnet3>::new(&mut rand::thread_rng()).unwrap();
+        let db = tempfile::tempdir().unwrap();
+        let conn = AleoConnector::new(pk, Some(aleo.url()), None, db.path()).unwrap().with_events(events);
+
+        // the genesis block only holds credits.aleo transitions
+        let credits =
+            TransitionFilter::default().add_programs(vec![ProgramID::<Testnet3>::from_str("credits.aleo").unwrap()]);
+        assert_eq!(credits.filter_block_with_txid(genesis.clone()).len(), genesis.transitions().count());
+        assert!(conn.filter.filter_block_with_txid(genesis).is_empty());
+
+        conn.sync().await.unwrap();
+        assert_eq!(aleo.requests("blocks"), 3);
+        assert_eq!(conn.current_height.get(&Testnet3::ID).unwrap(), Some(start + 120));
+        assert!(receiver.try_recv().is_err());
+
+        // a failed range is fetched again by the next sync
+        aleo.set_height(start + 200);
+        aleo.fail("blocks", 500, "node busy");
+        assert!(conn.fast_sync().await.is_err());
+        assert_eq!(conn.current_height.get(&Testnet3::ID).unwrap(), Some(start + 120));
+        conn.fast_sync().await.unwrap();
+        assert_eq!(conn.current_height.get(&Testnet3::ID).unwrap(), Some(start + 200));
+    }
+
+    #[tokio::test]
+    async fn test_balance() {
+        let aleo = MockAleo::start().await.unwrap();
+        let genesis = Block::<Testnet3>::from_bytes_le(Testnet3::genesis_bytes()).unwrap();
+        let pk = PrivateKey::<Testnet3>::new(&mut rand::thread_rng()).unwrap();
+        let db = tempfile::tempdir().unwrap();
+        let conn = AleoConnector::new(pk, Some(aleo.url()), None, db.path()).unwrap();
+        aleo.set_mapping("credits.aleo", "account", conn.address(), "5000u64");
+        conn.refresh_balance().await.unwrap();
+        assert_eq!(conn.balance(), Some(5000));
+
+        // the records of other keys are not counted
+        conn.handle_records(&genesis);
+        assert!(conn.records.get_all().unwrap().is_empty());
+
+        // unspent records add to the public credits
+        conn.records.insert("sn1".to_string(), 1000).unwrap();
+        conn.records.insert("sn2".to_string(), 2000).unwrap();
+        conn.refresh_balance().await.unwrap();
+        assert_eq!(conn.balance(), Some(8000));
+
+        // a balance that can't be read keeps the last one
+        aleo.fail("mapping", 500, "node down");
+        assert!(conn.refresh_balance().await.is_err());
+        assert_eq!(conn.balance(), Some(8000));
+    }
+
+    #[tokio::test]
+    async fn test_fixtures() {
+        let aleo = MockAleo::start().await.unwrap();
+        let heights = aleo.load_fixtures(&fixtures_dir()).unwrap();
+        assert!(!heights.is_empty(), "no block fixtures in {:?}, its README tells how to fetch them", fixtures_dir());
+        let expected = fixture_txs(&fixtures_dir()).unwrap();
+        assert_eq!(expected.keys().copied().collect::<Vec<_>>(), heights, "every block is listed in {FIXTURE_TXS}");
+        assert!(expected.values().any(|txs| !txs.transfers.is_empty()), "no fixture holds a transfer");
+        assert!(expected.values().any(|txs| !txs.speedups.is_empty()), "no fixture holds a speedup");
+
+        let (events, mut receiver) = tokio::sync::mpsc::channel(1024);
+        let pk = PrivateKey::<Testnet3>::new(&mut rand::thread_rng()).unwrap();
+        let db = tempfile::tempdir().unwrap();
+        let conn = AleoConnector::new(pk, Some(aleo.url()), None, db.path()).unwrap().with_events(events);
+
+        // every fixture block is synced on its own, exactly the transactions listed for it have to come out
+        for (height, txs) in expected {
+            aleo.set_height(height + 1);
+            conn.current_height.insert(Testnet3::ID, height).unwrap();
+            conn.sync().await.unwrap();
+
+            let (mut transfers, mut speedups) = (vec![], vec![]);
+            while let Ok(event) = receiver.try_recv() {
+                match event {
+                    VoterEvent::Transfer(tid) => transfers.push(tid),
+                    VoterEvent::Speedup(tid) => speedups.push(tid),
+                }
+            }
+            assert_eq!(transfers, txs.transfers, "transfers of block {height}");
+            assert_eq!(speedups, txs.speedups, "speedups of block {height}");
+            for tid in &txs.transfers {
+                let tx = conn.unconfirmed_txs.get(tid).unwrap().unwrap();
+                assert_eq!(tx.from_chain_id, Testnet3::IZAR_CHAIN_ID);
+            }
+            for tid in &txs.speedups {
+                assert!(conn.speedup_txs.get(tid).unwrap().is_some());
+            }
+        }
+    }
+}
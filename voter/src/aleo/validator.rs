@@ -1,5 +1,6 @@
 use aleo_rust::Network;
 use izar_core::{
+    envelope::EnvelopeSigner,
     types::{aleo::IzarRecvMsg, cert::Certificate, transaction::IzarTransaction},
     utils::PlaintextCodec,
     ToFields,
@@ -20,4 +21,12 @@ impl<N: Network> Validator for AleoConnector<N> {
 
         Ok(Certificate { signature, signer })
     }
+
+    fn envelope_signer(&self) -> &dyn EnvelopeSigner {
+        self.private_key()
+    }
+
+    fn balance(&self) -> Option<(String, u128)> {
+        Some((self.address().to_string(), AleoConnector::balance(self)?))
+    }
 }
@@ -1,4 +1,8 @@
-use std::time::Duration;
+use std::{
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use anyhow::anyhow;
 use ethers::{
@@ -8,7 +12,7 @@ use ethers::{
     types::{Address, Filter},
 };
 
-use crate::{DB_PATH, UNCONFIRMED_TXS};
+use crate::{EventSender, VoterEvent, UNCONFIRMED_TXS};
 use izar_core::{
     db::{map::DBMap, RocksDB},
     network::eth::EthNetwork,
@@ -26,20 +30,22 @@ pub struct EthConnector<E: EthNetwork> {
     unconfrimed_txs: DBMap<String, IzarTransaction>,
     current_height: DBMap<u32, u64>,
     address: Address,
+    balance: Arc<Mutex<Option<u128>>>,
+    events: Option<EventSender>,
 
     phantom: std::marker::PhantomData<E>,
 }
 
 impl<E: EthNetwork> EthConnector<E> {
-    pub fn new(pk: String, dest: String, from_height: Option<u64>) -> anyhow::Result<Self> {
+    pub fn new(pk: String, dest: String, from_height: Option<u64>, db_path: impl AsRef<Path>) -> anyhow::Result<Self> {
         let wallet = pk.parse::<LocalWallet>()?;
         let provider = Provider::<Http>::try_from(dest)?;
         let address = wallet.address();
 
         let client = SignerMiddleware::new(provider, wallet.with_chain_id(E::ETH_CHAIN_ID));
 
-        let unconfrimed_txs = RocksDB::open_map(DB_PATH, UNCONFIRMED_TXS)?;
-        let current_height = RocksDB::open_map(DB_PATH, "eth-chains")?;
+        let unconfrimed_txs = RocksDB::open_map(&db_path, UNCONFIRMED_TXS)?;
+        let current_height = RocksDB::open_map(&db_path, "eth-chains")?;
 
         let cur = current_height.get(&E::ETH_CHAIN_ID)?.unwrap_or(E::START_HEIGHT);
         if let Some(from_height) = from_height {
@@ -48,7 +54,20 @@ impl<E: EthNetwork> EthConnector<E> {
             }
         }
 
-        Ok(Self { client, unconfrimed_txs, current_height, address, phantom: std::marker::PhantomData })
+        Ok(Self {
+            client,
+            unconfrimed_txs,
+            current_height,
+            address,
+            balance: Default::default(),
+            events: None,
+            phantom: std::marker::PhantomData,
+        })
+    }
+
+    pub fn with_events(mut self, events: EventSender) -> Self {
+        self.events = Some(events);
+        self
     }
 
     pub async fn sync(&self) -> anyhow::Result<()> {
@@ -87,6 +106,9 @@ impl<E: EthNetwork> EthConnector<E> {
                         let tx = EthTransaction::<E>::from_logs(fee_log, payload_log, tid)?;
                         tracing::info!("got a eth tx {:?}", tx); // TODO
                         self.unconfrimed_txs.insert(E::format_str(tid), tx.try_into()?)?;
+                        if let Some(events) = &self.events {
+                            let _ = events.try_send(VoterEvent::Transfer(E::format_str(tid)));
+                        }
                     }
                     _ => tracing::error!("invalid log pair"),
                 }
@@ -106,6 +128,9 @@ impl<E: EthNetwork> EthConnector<E> {
                 if let Err(e) = self_clone.sync().instrument(error_span!("ETH", network = E::IZAR_CHAIN_ID)).await {
                     tracing::error!("eth sync error: {:?}", e);
                 }
+                if let Err(e) = self_clone.refresh_balance().await {
+                    tracing::error!("eth balance error: {:?}", e);
+                }
                 tokio::time::sleep(Duration::from_secs(20)).await;
             }
         };
@@ -114,6 +139,19 @@ impl<E: EthNetwork> EthConnector<E> {
         self
     }
 
+    async fn refresh_balance(&self) -> anyhow::Result<()> {
+        let balance = self.client.get_balance(self.address, None).await?;
+        if let Ok(mut cached) = self.balance.lock() {
+            // no chain holds that much, but a cut off balance would read low
+            *cached = Some(u128::try_from(balance).unwrap_or(u128::MAX));
+        }
+        Ok(())
+    }
+
+    pub fn balance(&self) -> Option<u128> {
+        self.balance.lock().ok().and_then(|b| *b)
+    }
+
     pub fn address(&self) -> &Address {
         &self.address
     }
@@ -122,3 +160,63 @@ impl<E: EthNetwork> EthConnector<E> {
         &self.client
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use ethers::types::{Address, H256};
+    use izar_core::network::{eth::EthNetwork, testnet::IzarTestnet, IzarNetwork};
+    use izar_testkit::evm::{Lock, MockEvm};
+
+    use super::EthConnector;
+    use crate::VoterEvent;
+
+    type Sepolia = <IzarTestnet as IzarNetwork>::Sepolia;
+
+    const PK: &str = "4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318";
+
+    #[tokio::test]
+    async fn test_sync() {
+        let start = Sepolia::START_HEIGHT;
+        let evm = MockEvm::start(Sepolia::ETH_CHAIN_ID as u64, start).await.unwrap();
+        let (events, mut receiver) = tokio::sync::mpsc::channel(16);
+        let db = tempfile::tempdir().unwrap();
+        let conn =
+            EthConnector::<Sepolia>::new(PK.to_string(), evm.url(), None, db.path()).unwrap().with_events(events);
+
+        let lock = Lock {
+            tx_hash: H256::from_low_u64_be(0x10c),
+            block: start + 3,
+            sender: Address::from_low_u64_be(1),
+            token: Address::from_low_u64_be(2),
+            to_chain_id: 2,
+            to_asset_addr: "aleo1asset".to_string(),
+            to_addr: "aleo1to".to_string(),
+            amount: 100.into(),
+            fee: 1.into(),
+            nonce: 7.into(),
+        };
+        evm.push_logs(lock.logs::<Sepolia>().unwrap());
+        // logs above the head are not served yet
+        let mut later = lock.clone();
+        (later.tx_hash, later.block, later.nonce) = (H256::from_low_u64_be(0x20c), start + 8, 8.into());
+        evm.push_logs(later.logs::<Sepolia>().unwrap());
+        evm.set_head(start + 5);
+        conn.sync().await.unwrap();
+
+        let hash = Sepolia::format_str(lock.tx_hash);
+        let tx = conn.unconfrimed_txs.get(&hash).unwrap().unwrap();
+        assert_eq!((tx.from_chain_id, tx.to_chain_id), (Sepolia::IZAR_CHAIN_ID, 2));
+        assert_eq!((tx.to_asset_addr.as_str(), tx.to_addr.as_str()), ("aleo1asset", "aleo1to"));
+        assert_eq!((tx.nonce.as_str(), tx.fee.as_str()), ("7", "1"));
+        assert!(matches!(receiver.try_recv(), Ok(VoterEvent::Transfer(h)) if h == hash));
+        assert!(receiver.try_recv().is_err());
+        assert_eq!(conn.current_height.get(&Sepolia::ETH_CHAIN_ID).unwrap(), Some(start + 5));
+
+        // nothing to fetch until the head moves
+        conn.sync().await.unwrap();
+        assert_eq!(evm.requests("eth_getLogs"), 1);
+        evm.set_head(start + 10);
+        conn.sync().await.unwrap();
+        assert!(matches!(receiver.try_recv(), Ok(VoterEvent::Transfer(h)) if h == Sepolia::format_str(later.tx_hash)));
+    }
+}
@@ -1,21 +1,35 @@
-use izar_core::types::{cert::Certificate, transaction::IzarTransaction};
-use serde::{Deserialize, Serialize};
+use izar_core::{
+    envelope::EnvelopeSigner,
+    types::{cert::Certificate, transaction::IzarTransaction},
+};
 
 pub mod aleo;
 pub mod eth;
+pub mod outbox;
 pub mod validators;
 
-pub(crate) const DB_PATH: &str = ".izar-voter";
+pub const DB_PATH: &str = ".izar-voter";
 pub(crate) const UNCONFIRMED_TXS: &str = "uncomfirmed_txs";
 pub(crate) const SPEEDUP_TXS: &str = "speedup_txs";
+pub(crate) const SUBMIT_OUTBOX: &str = "submit_outbox";
+pub(crate) const REJECTED_TXS: &str = "rejected_txs";
 
-pub trait Validator {
-    fn sign(&self, msg: IzarTransaction) -> anyhow::Result<Certificate>;
+/// Tells the pipeline a connector stored something to sign or forward, keyed as in its store.
+#[derive(Debug, Clone)]
+pub enum VoterEvent {
+    Transfer(String),
+    Speedup(String),
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-#[serde(rename_all = "camelCase")]
-pub struct SpeedupRequest {
-    lock_tx_hash: String,
-    speed_up_hash: String,
+/// Events only hurry the pipeline along, one that finds the channel full is dropped and left to the sweep.
+pub type EventSender = tokio::sync::mpsc::Sender<VoterEvent>;
+
+pub trait Validator: Send + Sync {
+    fn sign(&self, msg: IzarTransaction) -> anyhow::Result<Certificate>;
+    /// The validator key submissions to the sequencer are signed with.
+    fn envelope_signer(&self) -> &dyn EnvelopeSigner;
+    /// Signer address and its last known balance, refreshed by the connector sync loop.
+    fn balance(&self) -> Option<(String, u128)> {
+        None
+    }
 }
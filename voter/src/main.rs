@@ -1,9 +1,16 @@
-use std::str::FromStr;
+use std::{collections::HashMap, str::FromStr, time::Duration};
 
 use aleo_rust::Network;
 use clap::Parser;
-use izar_core::network::{aleo::AleoNetworkExt, eth::EthNetwork, testnet::IzarTestnet, IzarNetwork};
-use izar_voter::{aleo::connector::AleoConnector, eth::connector::EthConnector, validators::IzarValidators};
+use izar_core::{
+    metrics::voter::VOTER_BALANCE,
+    monitor::{deserialize_thresholds, Alerter, BalanceMonitor},
+    network::{aleo::AleoNetworkExt, eth::EthNetwork, testnet::IzarTestnet, IzarNetwork},
+    sequencer::SequencerClient,
+};
+use izar_voter::{
+    aleo::connector::AleoConnector, eth::connector::EthConnector, validators::IzarValidators, EventSender, DB_PATH,
+};
 use serde::Deserialize;
 
 #[derive(Debug, Parser)]
@@ -18,6 +25,14 @@ pub struct Cli {
 #[derive(Debug, Deserialize)]
 pub struct VoterConfig {
     pub api_dest: String,
+    /// sequencers to fail over to when `api_dest` is down
+    #[serde(default)]
+    pub api_dests: Vec<String>,
+    /// seconds before a sequencer request times out, 30 by default
+    pub api_timeout: Option<u64>,
+    /// directory of the voter store, `.izar-voter` by default
+    pub db_path: Option<String>,
+    pub monitor: Option<MonitorConfig>,
     pub aleo_config: Option<AleoConfig>,
     pub sepolia_config: Option<EthConfig>,
     pub linea_config: Option<EthConfig>,
@@ -29,6 +44,20 @@ pub struct VoterConfig {
     pub taiko_config: Option<EthConfig>,
 }
 
+#[derive(Debug, Deserialize)]
+pub struct MonitorConfig {
+    webhook: Option<String>,
+    // izar chain id to the lowest balance before alerting, in the smallest unit of the chain
+    #[serde(default, deserialize_with = "deserialize_thresholds")]
+    thresholds: HashMap<u32, u128>,
+}
+
+impl MonitorConfig {
+    pub fn parse(self) -> BalanceMonitor {
+        BalanceMonitor::new(VOTER_BALANCE, Alerter::new(self.webhook), self.thresholds)
+    }
+}
+
 #[derive(Debug, Deserialize)]
 pub struct AleoConfig {
     pk: String,
@@ -37,9 +66,10 @@ pub struct AleoConfig {
 }
 
 impl AleoConfig {
-    pub fn parse<N: Network>(self) -> AleoConnector<N> {
+    pub fn parse<N: Network>(self, events: EventSender, db_path: &str) -> AleoConnector<N> {
         let pk = aleo_rust::PrivateKey::<N>::from_str(&self.pk).expect("parse pk");
-        AleoConnector::new(pk, self.dest, self.from_height).expect("init aleo").sync_and_initial()
+        let conn = AleoConnector::new(pk, self.dest, self.from_height, db_path).expect("init aleo");
+        conn.with_events(events).sync_and_initial()
     }
 }
 
@@ -51,8 +81,9 @@ pub struct EthConfig {
 }
 
 impl EthConfig {
-    pub fn parse<E: EthNetwork>(self) -> EthConnector<E> {
-        EthConnector::<E>::new(self.pk, self.dest, self.from_height).expect("eth init").initial()
+    pub fn parse<E: EthNetwork>(self, events: EventSender, db_path: &str) -> EthConnector<E> {
+        let conn = EthConnector::<E>::new(self.pk, self.dest, self.from_height, db_path).expect("eth init");
+        conn.with_events(events).initial()
     }
 }
 
@@ -71,61 +102,68 @@ async fn init<I: IzarNetwork>(cli: Cli) {
     let config_str = std::fs::read_to_string(cli.config).expect("read config file");
     let config: VoterConfig = toml::from_str(&config_str).expect("parse config");
     tracing::info!("voter init with {:#?}", config);
-    let mut validators = IzarValidators::new(config.api_dest);
+    let urls = std::iter::once(config.api_dest).chain(config.api_dests).collect();
+    let timeout = Duration::from_secs(config.api_timeout.unwrap_or(30));
+    let sequencer = SequencerClient::new(urls, timeout).expect("init sequencer client");
+    let db_path = config.db_path.unwrap_or(DB_PATH.to_string());
+    let mut validators = IzarValidators::new(sequencer, &db_path);
+    if let Some(monitor) = config.monitor {
+        validators.set_monitor(monitor.parse());
+    }
 
     // init aleo connector
     if let Some(aleo_config) = config.aleo_config {
         tracing::info!("init aleo connector");
-        let aleo_conn = Box::new(aleo_config.parse::<I::Aleo>());
+        let aleo_conn = Box::new(aleo_config.parse::<I::Aleo>(validators.events(), &db_path));
         validators.insert_connector(I::Aleo::IZAR_CHAIN_ID, aleo_conn);
     }
 
     // init sepolia connector
     if let Some(config) = config.sepolia_config {
         tracing::info!("init sepolia connector");
-        let conn = Box::new(config.parse::<I::Sepolia>());
+        let conn = Box::new(config.parse::<I::Sepolia>(validators.events(), &db_path));
         validators.insert_connector(I::Sepolia::IZAR_CHAIN_ID, conn);
     }
 
     // init linea connector
     if let Some(config) = config.linea_config {
-        let conn = Box::new(config.parse::<I::Linea>());
+        let conn = Box::new(config.parse::<I::Linea>(validators.events(), &db_path));
         validators.insert_connector(I::Linea::IZAR_CHAIN_ID, conn);
     }
 
     // init zksync connector
     if let Some(config) = config.zksync_config {
-        let conn = Box::new(config.parse::<I::Zksync>());
+        let conn = Box::new(config.parse::<I::Zksync>(validators.events(), &db_path));
         validators.insert_connector(I::Zksync::IZAR_CHAIN_ID, conn);
     }
 
     // init goerli connector
     if let Some(config) = config.goerli_config {
-        let conn = Box::new(config.parse::<I::Goerli>());
+        let conn = Box::new(config.parse::<I::Goerli>(validators.events(), &db_path));
         validators.insert_connector(I::Goerli::IZAR_CHAIN_ID, conn);
     }
 
     // init scroll connector
     if let Some(config) = config.scroll_config {
-        let conn = Box::new(config.parse::<I::Scroll>());
+        let conn = Box::new(config.parse::<I::Scroll>(validators.events(), &db_path));
         validators.insert_connector(I::Scroll::IZAR_CHAIN_ID, conn);
     }
 
     // init optimism connector
     if let Some(config) = config.optimism_config {
-        let conn = Box::new(config.parse::<I::Optimism>());
+        let conn = Box::new(config.parse::<I::Optimism>(validators.events(), &db_path));
         validators.insert_connector(I::Optimism::IZAR_CHAIN_ID, conn);
     }
 
     // init arbitrum
     if let Some(config) = config.arbitrum_config {
-        let conn = Box::new(config.parse::<I::Arbitrum>());
+        let conn = Box::new(config.parse::<I::Arbitrum>(validators.events(), &db_path));
         validators.insert_connector(I::Arbitrum::IZAR_CHAIN_ID, conn);
     }
 
     // init taiko
     if let Some(taiko_config) = config.taiko_config {
-        let conn = Box::new(taiko_config.parse::<I::Taiko>());
+        let conn = Box::new(taiko_config.parse::<I::Taiko>(validators.events(), &db_path));
         validators.insert_connector(I::Taiko::IZAR_CHAIN_ID, conn);
     }
 
@@ -0,0 +1,147 @@
+use std::path::Path;
+
+use izar_core::{
+    db::{map::DBMap, RocksDB},
+    sequencer::SequencerError,
+    types::transaction::IzarTransaction,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{REJECTED_TXS, SUBMIT_OUTBOX};
+
+const BASE_BACKOFF: u64 = 20;
+const MAX_BACKOFF: u64 = 600;
+
+/// A transfer signed by this voter, kept until the sequencer takes the certificate.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Submission {
+    pub tx: IzarTransaction,
+    pub attempts: u32,
+    /// unix seconds of the next attempt
+    pub next_at: u64,
+}
+
+/// A submission the sequencer refused for good, kept for an operator to look at.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct Rejected {
+    pub tx: IzarTransaction,
+    pub status: u16,
+    pub reason: String,
+    pub rejected_at: u64,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Outcome {
+    Delivered,
+    Retry,
+    Rejected,
+}
+
+impl Outcome {
+    /// What an answer of the sequencer means for a submission.
+    pub fn of(result: &Result<(), SequencerError>) -> Self {
+        match result {
+            Ok(()) => Self::Delivered,
+            // the sequencer has this certificate already
+            Err(e) if e.status() == Some(409) => Self::Delivered,
+            // the body itself is refused, sending it again changes nothing
+            Err(e) if e.is_permanent() => Self::Rejected,
+            // timeouts, rate limits, auth being fixed and server errors
+            Err(_) => Self::Retry,
+        }
+    }
+}
+
+pub struct Outbox {
+    submissions: DBMap<String, Submission>,
+    rejected: DBMap<String, Rejected>,
+}
+
+impl Outbox {
+    pub fn new(db_path: impl AsRef<Path>) -> anyhow::Result<Self> {
+        Ok(Self {
+            submissions: RocksDB::open_map(&db_path, SUBMIT_OUTBOX)?,
+            rejected: RocksDB::open_map(&db_path, REJECTED_TXS)?,
+        })
+    }
+
+    /// Stores `tx` with its new certificate and drops it from `unconfirmed` in one write.
+    pub fn stage(&self, tx: IzarTransaction, unconfirmed: &DBMap<String, IzarTransaction>) -> anyhow::Result<()> {
+        RocksDB::atomic_batch(self.submissions.inner(), |batch| {
+            unconfirmed.delete_append(&tx.from_chain_tx_hash, batch)?;
+            let hash = tx.from_chain_tx_hash.clone();
+            self.submissions.write_append(hash, Submission { tx, attempts: 0, next_at: 0 }, batch)
+        })
+    }
+
+    pub fn get(&self, hash: &String) -> anyhow::Result<Option<Submission>> {
+        self.submissions.get(hash)
+    }
+
+    /// Submissions whose next attempt is due at `now`.
+    pub fn due(&self, now: u64) -> Vec<Submission> {
+        self.submissions.values().filter(|s| s.next_at <= now).map(|s| s.into_owned()).collect()
+    }
+
+    pub fn len(&self) -> usize {
+        self.submissions.keys().count()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.submissions.keys().next().is_none()
+    }
+
+    pub fn delivered(&self, hash: &String) -> anyhow::Result<()> {
+        self.submissions.remove(hash)
+    }
+
+    pub fn retry(&self, mut submission: Submission, now: u64) -> anyhow::Result<()> {
+        submission.attempts += 1;
+        submission.next_at = now + backoff(submission.attempts);
+        self.submissions.insert(submission.tx.from_chain_tx_hash.clone(), submission)
+    }
+
+    pub fn reject(&self, submission: Submission, status: u16, reason: String, now: u64) -> anyhow::Result<()> {
+        let hash = submission.tx.from_chain_tx_hash.clone();
+        RocksDB::atomic_batch(self.submissions.inner(), |batch| {
+            self.submissions.delete_append(&hash, batch)?;
+            let rejected = Rejected { tx: submission.tx, status, reason, rejected_at: now };
+            self.rejected.write_append(hash.clone(), rejected, batch)
+        })
+    }
+
+    pub fn rejected(&self) -> &DBMap<String, Rejected> {
+        &self.rejected
+    }
+}
+
+fn backoff(attempts: u32) -> u64 {
+    BASE_BACKOFF.saturating_mul(1 << attempts.min(16)).min(MAX_BACKOFF)
+}
+
+pub(crate) fn now() -> u64 {
+    std::time::SystemTime::now().duration_since(std::time::SystemTime::UNIX_EPOCH).unwrap_or_default().as_secs()
+}
+
+#[cfg(test)]
+mod tests {
+    use izar_core::sequencer::SequencerError;
+
+    use super::{backoff, Outcome};
+
+    fn status(status: u16) -> Outcome {
+        Outcome::of(&Err(SequencerError::Status { status, body: String::new() }))
+    }
+
+    #[test]
+    fn test_outcome() {
+        assert_eq!(Outcome::of(&Ok(())), Outcome::Delivered);
+        assert_eq!(status(409), Outcome::Delivered);
+        assert_eq!(status(422), Outcome::Rejected);
+        assert_eq!(status(429), Outcome::Retry);
+        assert_eq!(status(401), Outcome::Retry);
+        assert_eq!(status(503), Outcome::Retry);
+        assert_eq!(Outcome::of(&Err(SequencerError::Unreachable(String::new()))), Outcome::Retry);
+        assert_eq!((backoff(1), backoff(20)), (40, 600));
+    }
+}
@@ -1,29 +1,60 @@
-use std::collections::HashMap;
+use std::{
+    collections::{HashMap, HashSet},
+    path::Path,
+    sync::{Arc, Mutex},
+    time::Duration,
+};
 
 use izar_core::{
     db::{map::DBMap, RocksDB},
+    envelope::Scheme,
+    monitor::BalanceMonitor,
+    sequencer::SequencerClient,
     types::transaction::{IzarTransaction, SpeedupTransaction},
 };
+use tokio::sync::{mpsc, Semaphore};
 use tracing::{error_span, Instrument};
 
-use crate::{Validator, DB_PATH, SPEEDUP_TXS, UNCONFIRMED_TXS};
+use crate::{
+    outbox::{now, Outbox, Outcome, Submission},
+    EventSender, Validator, VoterEvent, SPEEDUP_TXS, UNCONFIRMED_TXS,
+};
+
+// transfers signed and submitted at once
+const CONCURRENCY: usize = 16;
+// events waiting for a slot, the connectors drop any more
+const EVENTS: usize = 1024;
+// retries, balances and whatever was stored before a restart are picked up on this tick
+const SWEEP_INTERVAL: Duration = Duration::from_secs(20);
 
 pub struct IzarValidators {
     connectors: HashMap<u32, Box<dyn Validator>>,
     unconfirmd_txs: DBMap<String, IzarTransaction>,
     speedup_txs: DBMap<String, String>,
-    dest: String,
-    client: ureq::Agent,
+    outbox: Outbox,
+    sequencer: SequencerClient,
+    monitor: Option<BalanceMonitor>,
+    events: EventSender,
+    receiver: Option<mpsc::Receiver<VoterEvent>>,
+    slots: Arc<Semaphore>,
+    // store keys being handled, an event for one of them while it is in flight is dropped
+    in_flight: Mutex<HashSet<String>>,
 }
 
 impl IzarValidators {
-    pub fn new(dest: String) -> Self {
+    pub fn new(sequencer: SequencerClient, db_path: impl AsRef<Path>) -> Self {
+        let (events, receiver) = mpsc::channel(EVENTS);
         Self {
             connectors: HashMap::new(),
-            unconfirmd_txs: RocksDB::open_map(DB_PATH, UNCONFIRMED_TXS).expect("Failed to open uncomfirmed txs db"),
-            speedup_txs: RocksDB::open_map(DB_PATH, SPEEDUP_TXS).expect("Failed to open speedup txs db"),
-            dest,
-            client: ureq::agent(),
+            unconfirmd_txs: RocksDB::open_map(&db_path, UNCONFIRMED_TXS).expect("Failed to open uncomfirmed txs db"),
+            speedup_txs: RocksDB::open_map(&db_path, SPEEDUP_TXS).expect("Failed to open speedup txs db"),
+            outbox: Outbox::new(&db_path).expect("Failed to open submit outbox db"),
+            sequencer,
+            monitor: None,
+            events,
+            receiver: Some(receiver),
+            slots: Arc::new(Semaphore::new(CONCURRENCY)),
+            in_flight: Mutex::new(HashSet::new()),
         }
     }
 
@@ -31,72 +62,250 @@ impl IzarValidators {
         self.connectors.insert(chain_id, connector);
     }
 
+    pub fn set_monitor(&mut self, monitor: BalanceMonitor) {
+        self.monitor = Some(monitor);
+    }
+
+    /// Connectors report what they stored here.
+    pub fn events(&self) -> EventSender {
+        self.events.clone()
+    }
+
+    async fn observe_balances(&self) {
+        let monitor = match &self.monitor {
+            Some(monitor) => monitor,
+            None => return,
+        };
+        for (chain_id, connector) in self.connectors.iter() {
+            if let Some((address, balance)) = connector.balance() {
+                monitor.observe(*chain_id, &address, balance).await;
+            }
+        }
+    }
+
     fn sign_tx(&self, tx: IzarTransaction) -> anyhow::Result<izar_core::types::cert::Certificate> {
-        let connector = self
-            .connectors
-            .get(&tx.to_chain_id)
-            .ok_or_else(|| anyhow::anyhow!("chain id {:?} validator not found", tx.to_chain_id))?;
-        connector.sign(tx)
-    }
-
-    async fn handle_txs(&self) -> anyhow::Result<()> {
-        let url = format!("{}/api/v1/BridgeTx", self.dest);
-        let txs = self.unconfirmd_txs.get_all()?;
-        for (_, mut tx) in txs {
-            self.unconfirmd_txs.remove(&tx.from_chain_tx_hash)?;
-            let cert = self.sign_tx(tx.clone())?;
-            tx.certificates.push(cert);
-
-            let resp = self.client.post(&url).send_json(&tx)?;
-            match resp.status() {
-                400..=499 => tracing::error!("submit response error {}", resp.into_string()?),
-                200..=299 => tracing::info!("submit sigs success: {:?}", tx),
-                _ => {
-                    tracing::error!("unimplemented status code {}", resp.status());
-                    self.unconfirmd_txs.insert(tx.from_chain_tx_hash.clone(), tx)?;
-                }
+        self.connector(tx.to_chain_id)?.sign(tx)
+    }
+
+    fn connector(&self, chain_id: u32) -> anyhow::Result<&dyn Validator> {
+        let connector = self.connectors.get(&chain_id);
+        connector.map(|c| c.as_ref()).ok_or_else(|| anyhow::anyhow!("chain id {chain_id} validator not found"))
+    }
+
+    async fn dispatch(self: &Arc<Self>, event: VoterEvent) {
+        let key = match &event {
+            VoterEvent::Transfer(hash) => hash.clone(),
+            VoterEvent::Speedup(hash) => format!("speedup:{hash}"),
+        };
+        if !self.in_flight.lock().unwrap_or_else(|e| e.into_inner()).insert(key.clone()) {
+            return;
+        }
+        // no event is read while every slot is taken, a burst waits in the channel instead of in spawned tasks
+        let slot = self.slots.clone().acquire_owned().await.expect("voter slots closed");
+
+        let izar = self.clone();
+        let fut = async move {
+            let result = match &event {
+                VoterEvent::Transfer(hash) => izar.handle_tx(hash).instrument(error_span!("TXS")).await,
+                VoterEvent::Speedup(hash) => izar.handle_speedup_tx(hash).instrument(error_span!("SPEEDUP")).await,
+            };
+            if let Err(e) = result {
+                tracing::error!("handle {:?} error: {:?}", event, e);
             }
+            izar.in_flight.lock().unwrap_or_else(|e| e.into_inner()).remove(&key);
+            drop(slot);
+        };
+        tokio::spawn(fut);
+    }
+
+    async fn sweep(self: &Arc<Self>) {
+        let mut transfers = self.unconfirmd_txs.keys().map(|k| k.into_owned()).collect::<HashSet<_>>();
+        transfers.extend(self.outbox.due(now()).into_iter().map(|s| s.tx.from_chain_tx_hash));
+        let speedups = self.speedup_txs.keys().map(|k| k.into_owned()).collect::<Vec<_>>();
+
+        for hash in transfers {
+            self.dispatch(VoterEvent::Transfer(hash)).await;
+        }
+        for hash in speedups {
+            self.dispatch(VoterEvent::Speedup(hash)).await;
+        }
+
+        if !self.outbox.is_empty() {
+            tracing::warn!("{} certificate submissions waiting for the sequencer", self.outbox.len());
         }
-        Ok(())
-    }
-
-    async fn handle_speedup_txs(&self) -> anyhow::Result<()> {
-        let url = format!("{}/api/v1/BridgeTxSpeedUp", self.dest);
-        let txs = self.speedup_txs.get_all()?;
-        for tx in txs {
-            let (lock_tx_hash, speed_up_hash) = (tx.0, tx.1);
-            self.speedup_txs.remove(&speed_up_hash)?;
-            let req =
-                SpeedupTransaction { from_chain_tx_hash: lock_tx_hash.clone(), speed_up_hash: speed_up_hash.clone() };
-            let resp = self.client.post(&url).send_json(&req)?;
-            match resp.status() {
-                400..=499 => tracing::error!("submit response error {}", resp.into_string()?),
-                200..=299 => tracing::info!("submit speedup tx success: {:?}", req),
-                _ => {
-                    tracing::error!("unimplemented status code {}", resp.status());
-                    self.speedup_txs.insert(speed_up_hash, lock_tx_hash)?;
+    }
+
+    // a transfer leaves the unconfirmed store only together with its signed submission
+    async fn handle_tx(self: &Arc<Self>, hash: &String) -> anyhow::Result<()> {
+        if let Some(mut tx) = self.unconfirmd_txs.get(hash)? {
+            let (signer, unsigned) = (self.clone(), tx.clone());
+            match tokio::task::spawn_blocking(move || signer.sign_tx(unsigned)).await? {
+                Ok(cert) => {
+                    tx.certificates.push(cert);
+                    self.outbox.stage(tx, &self.unconfirmd_txs)?;
                 }
+                Err(e) => {
+                    tracing::error!("sign tx {hash} error, retry next sweep: {:?}", e);
+                    return Ok(());
+                }
+            }
+        }
+
+        match self.outbox.get(hash)? {
+            Some(submission) if submission.next_at <= now() => self.submit(submission).await,
+            _ => Ok(()),
+        }
+    }
+
+    async fn submit(&self, submission: Submission) -> anyhow::Result<()> {
+        let hash = submission.tx.from_chain_tx_hash.clone();
+        // the key of the certificate vouches for the submission
+        let key = self.connector(submission.tx.to_chain_id)?.envelope_signer();
+        let result = self.sequencer.submit_certificates(&submission.tx, key).await;
+        match (Outcome::of(&result), result) {
+            (Outcome::Delivered, _) => {
+                tracing::info!("submit sigs success: {hash}");
+                self.outbox.delivered(&hash)
+            }
+            (Outcome::Rejected, Err(e)) => {
+                tracing::error!("submit sigs of tx {hash} rejected: {e}");
+                self.outbox.reject(submission, e.status().unwrap_or_default(), e.to_string(), now())
+            }
+            (_, result) => {
+                let e = result.err().map(|e| e.to_string()).unwrap_or_default();
+                tracing::warn!("submit sigs of tx {hash} failed, attempt {}: {e}", submission.attempts + 1);
+                self.outbox.retry(submission, now())
             }
         }
+    }
 
-        Ok(())
+    async fn handle_speedup_tx(&self, speed_up_hash: &String) -> anyhow::Result<()> {
+        let lock_tx_hash = match self.speedup_txs.get(speed_up_hash)? {
+            Some(lock_tx_hash) => lock_tx_hash,
+            None => return Ok(()),
+        };
+        let req = SpeedupTransaction { from_chain_tx_hash: lock_tx_hash, speed_up_hash: speed_up_hash.clone() };
+        // speedups are seen on aleo only
+        let connector = self.connectors.values().find(|c| c.envelope_signer().scheme() == Scheme::Aleo);
+        let key = connector.ok_or_else(|| anyhow::anyhow!("no aleo validator to sign speedups"))?.envelope_signer();
+        let result = self.sequencer.submit_speedup(&req, key).await;
+        match (Outcome::of(&result), result) {
+            (Outcome::Delivered, _) => tracing::info!("submit speedup tx success: {:?}", req),
+            (Outcome::Rejected, Err(e)) => tracing::error!("submit speedup tx {speed_up_hash} rejected: {e}"),
+            (_, result) => {
+                let e = result.err().map(|e| e.to_string()).unwrap_or_default();
+                tracing::error!("submit speedup tx {speed_up_hash} failed, retry next sweep: {e}");
+                return Ok(());
+            }
+        }
+        self.speedup_txs.remove(speed_up_hash)
     }
 
-    pub async fn initial(self) {
+    pub async fn initial(mut self) {
         if self.connectors.is_empty() {
             panic!("no connector found");
         }
 
+        let mut events = self.receiver.take().expect("voter pipeline started twice");
+        let izar = Arc::new(self);
+        let mut sweep = tokio::time::interval(SWEEP_INTERVAL);
         loop {
-            if let Err(e) = self.handle_txs().instrument(error_span!("TXS")).await {
-                tracing::error!("handle txs error: {:?}", e);
+            tokio::select! {
+                Some(event) = events.recv() => izar.dispatch(event).await,
+                _ = sweep.tick() => {
+                    izar.sweep().await;
+                    let monitor = izar.clone();
+                    tokio::spawn(async move { monitor.observe_balances().await });
+                }
             }
+        }
+    }
+}
 
-            if let Err(e) = self.handle_speedup_txs().instrument(error_span!("SPEEDUP")).await {
-                tracing::error!("handle speedup txs error: {:?}", e);
-            }
+#[cfg(test)]
+mod tests {
+    use std::{sync::Arc, time::Duration};
 
-            tokio::time::sleep(tokio::time::Duration::from_secs(20)).await;
+    use ethers::signers::LocalWallet;
+    use izar_core::{
+        envelope::EnvelopeSigner,
+        sequencer::SequencerClient,
+        types::{cert::Certificate, transaction::IzarTransaction},
+    };
+    use izar_mock_sequencer::{MockConfig, MockSequencer};
+    use izar_testkit::transfer::TransferBuilder;
+
+    use super::IzarValidators;
+    use crate::{Validator, VoterEvent};
+
+    struct Signing(LocalWallet);
+
+    impl Validator for Signing {
+        fn sign(&self, _tx: IzarTransaction) -> anyhow::Result<Certificate> {
+            Ok(Certificate { signature: "sign1".to_string(), signer: self.0.signer()? })
         }
+
+        fn envelope_signer(&self) -> &dyn EnvelopeSigner {
+            &self.0
+        }
+    }
+
+    async fn pipeline(db: &tempfile::TempDir) -> (Arc<MockSequencer>, Arc<IzarValidators>) {
+        let seq = MockSequencer::new(MockConfig { threshold: Some(1), ..Default::default() });
+        let url = format!("http://{}", seq.clone().spawn(([127, 0, 0, 1], 0).into()).await.unwrap());
+        let sequencer = SequencerClient::new(vec![url], Duration::from_secs(5)).unwrap();
+        let mut voters = IzarValidators::new(sequencer, db.path());
+        voters.insert_connector(1, Box::new(Signing(LocalWallet::new(&mut rand::thread_rng()))));
+        (seq, Arc::new(voters))
+    }
+
+    // waits for the dispatched work to finish
+    async fn settle(voters: &IzarValidators) {
+        for _ in 0..200 {
+            if voters.in_flight.lock().unwrap().is_empty() {
+                return;
+            }
+            tokio::time::sleep(Duration::from_millis(10)).await;
+        }
+        panic!("dispatched work never finished");
+    }
+
+    #[tokio::test]
+    async fn test_event_submitted() {
+        let db = tempfile::tempdir().unwrap();
+        let (seq, voters) = pipeline(&db).await;
+        let hash = "t1".to_string();
+        voters.unconfirmd_txs.insert(hash.clone(), TransferBuilder::new(&hash, 2, 1).build()).unwrap();
+
+        // the way a connector tells the pipeline, read the way its loop does
+        let (events, mut receiver) = tokio::sync::mpsc::channel(1);
+        events.try_send(VoterEvent::Transfer(hash.clone())).unwrap();
+        assert!(events.try_send(VoterEvent::Transfer(hash.clone())).is_err());
+        voters.dispatch(receiver.recv().await.unwrap()).await;
+        settle(&voters).await;
+
+        assert_eq!(seq.snapshot().txs[&hash].tx.certificates.len(), 1);
+        assert!(voters.unconfirmd_txs.get(&hash).unwrap().is_none());
+        assert!(voters.outbox.get(&hash).unwrap().is_none());
+    }
+
+    #[tokio::test]
+    async fn test_dropped_event_swept() {
+        let db = tempfile::tempdir().unwrap();
+        let (seq, voters) = pipeline(&db).await;
+        let hash = "t1".to_string();
+        voters.unconfirmd_txs.insert(hash.clone(), TransferBuilder::new(&hash, 2, 1).build()).unwrap();
+
+        // an event for a transfer in flight is dropped
+        voters.in_flight.lock().unwrap().insert(hash.clone());
+        voters.dispatch(VoterEvent::Transfer(hash.clone())).await;
+        assert!(voters.unconfirmd_txs.get(&hash).unwrap().is_some());
+        voters.in_flight.lock().unwrap().remove(&hash);
+
+        // the sweep finds it in the store
+        voters.sweep().await;
+        settle(&voters).await;
+        assert_eq!(seq.snapshot().txs[&hash].tx.certificates.len(), 1);
+        assert!(voters.unconfirmd_txs.get(&hash).unwrap().is_none());
     }
 }